@@ -0,0 +1,727 @@
+//! `calc`: the interactive REPL and batch front end for `calc-core`
+
+mod session;
+
+use std::fs;
+use std::io::{self, IsTerminal, StdoutLock, Write};
+use std::process::ExitCode;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use calc_core::{
+    backend_for, evaluate, execute, summarize, AngleUnit, CalcError, Env, IntType, Outcome,
+    OverflowPolicy, Value, DEFAULT_SCALE, DEFAULT_TOLERANCE, MODES,
+};
+
+/// Render the result of a line for the terminal
+fn report(line: &str, result: Result<Outcome, CalcError>) -> String {
+    match result {
+        Ok(outcome) => outcome.to_string(),
+        Err(CalcError::Parse(e)) => {
+            // Point at the column the parser complained about
+            format!(
+                "{}\n{}^\n{}",
+                line,
+                " ".repeat(e.column.saturating_sub(1)),
+                CalcError::Parse(e)
+            )
+        }
+        Err(e) => format!("Error: {}", e),
+    }
+}
+
+/// Handle `:mode`, `:mode <name>`, `:mode decimal <scale>` and
+/// `:mode <type> wrapping|checked`
+fn run_mode_command(args: &[&str], env: &mut Env) -> Result<String, String> {
+    let Some(mode) = args.first() else {
+        return Ok(format!(
+            "Current mode: {} (available: {})",
+            env.backend.name(),
+            MODES
+        ));
+    };
+    let (mut scale, mut overflow) = (DEFAULT_SCALE, OverflowPolicy::Wrapping);
+    match args.get(1) {
+        None => {}
+        Some(text) if IntType::parse(mode).is_some() => overflow = parse_overflow(text)?,
+        Some(text) => {
+            scale = text
+                .parse()
+                .map_err(|_| format!("Invalid scale '{}'", text))?
+        }
+    }
+    match backend_for(mode, scale, overflow) {
+        Some(backend) => {
+            env.backend = backend;
+            Ok(format!("Switched to {} mode", env.backend.name()))
+        }
+        None => Err(format!("Unknown mode '{}', choose one of {}", mode, MODES)),
+    }
+}
+
+fn parse_overflow(text: &str) -> Result<OverflowPolicy, String> {
+    match text {
+        "wrapping" | "wrap" => Ok(OverflowPolicy::Wrapping),
+        "checked" => Ok(OverflowPolicy::Checked),
+        _ => Err(format!(
+            "Unknown overflow policy '{}', use wrapping or checked",
+            text
+        )),
+    }
+}
+
+/// Handle `:angle`, `:angle deg` and `:angle rad`
+fn run_angle_command(args: &[&str], env: &mut Env) -> Result<String, String> {
+    match args.first().copied() {
+        None => {}
+        Some("deg" | "degrees") => env.angle = AngleUnit::Degrees,
+        Some("rad" | "radians") => env.angle = AngleUnit::Radians,
+        Some(other) => return Err(format!("Unknown angle unit '{}', use deg or rad", other)),
+    }
+    Ok(format!("Angles are in {}", env.angle))
+}
+
+fn parse_tolerance(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        // Anything finer than `f64::EPSILON` is below what a float can resolve
+        Ok(tolerance) if (f64::EPSILON..1.0).contains(&tolerance) => Ok(tolerance),
+        _ => Err(format!(
+            "Invalid tolerance '{}', use a small positive number such as 1e-9",
+            text
+        )),
+    }
+}
+
+/// Handle `:tolerance` and `:tolerance <value>`
+fn run_tolerance_command(args: &[&str], env: &mut Env) -> Result<String, String> {
+    if let Some(text) = args.first() {
+        env.tolerance = parse_tolerance(text)?;
+    }
+    Ok(format!("solve() tolerance is {:e}", env.tolerance))
+}
+
+/// Handle `:save <name>`, where the name is a workspace in the data
+/// directory or a path ending in `.calc`
+fn run_save_command(args: &[&str], env: &Env) -> Result<String, String> {
+    let [name] = args else {
+        return Err("Usage: :save <name> or :save <path.calc>".to_string());
+    };
+    let path = session::workspace_path(name)?;
+    session::save(env, &path).map_err(|e| format!("cannot write '{}': {}", path.display(), e))?;
+    Ok(format!(
+        "Saved {} to {}",
+        session::summary(env),
+        path.display()
+    ))
+}
+
+/// Handle `:load`, listing saved workspaces, and `:load <name>`
+fn run_load_command(args: &[&str], env: &mut Env) -> Result<String, String> {
+    match args {
+        [] => {
+            let names = session::saved_workspaces();
+            if names.is_empty() {
+                Ok("No saved workspaces yet, use :save <name>".to_string())
+            } else {
+                Ok(format!("Saved workspaces: {}", names.join(", ")))
+            }
+        }
+        [name] => {
+            let path = session::workspace_path(name)?;
+            session::load(env, &path)?;
+            Ok(format!(
+                "Loaded {} from {}",
+                session::summary(env),
+                path.display()
+            ))
+        }
+        _ => Err("Usage: :load <name> or :load <path.calc>".to_string()),
+    }
+}
+
+/// Size of the images `:export` draws plots into, in pixels
+const IMAGE_WIDTH: u32 = 800;
+const IMAGE_HEIGHT: u32 = 480;
+
+/// Handle `:export <path>`. A plot is written as SVG or PNG, chosen by the
+/// extension. Anything else is written as CSV with one line per matrix row
+/// and an optional comma-separated header such as
+/// `period,payment,interest,principal,balance`.
+fn run_export_command(args: &[&str], env: &Env) -> Result<String, String> {
+    let (path, header) = match args {
+        [path] => (path, None),
+        [path, header] => (path, Some(header)),
+        _ => return Err("Usage: :export <path.csv|.svg|.png> [header,names]".to_string()),
+    };
+    let Some(ans) = env.ans() else {
+        return Err("There is no result to export yet".to_string());
+    };
+    if let Value::Plot(plot) = ans {
+        let bytes = if path.ends_with(".svg") {
+            plot.to_svg(IMAGE_WIDTH, IMAGE_HEIGHT).into_bytes()
+        } else if path.ends_with(".png") {
+            plot.to_png(IMAGE_WIDTH, IMAGE_HEIGHT)
+        } else {
+            return Err("Export a plot to a file ending in .svg or .png".to_string());
+        };
+        fs::write(path, bytes).map_err(|e| format!("cannot write '{}': {}", path, e))?;
+        return Ok(format!("Wrote the {} to {}", plot.title(), path));
+    }
+    // Tables such as `amortize`'s name their own columns unless a header is given
+    let header = match (header, ans) {
+        (Some(header), _) => Some(header.to_string()),
+        (None, Value::Matrix(matrix)) => matrix.columns().map(|names| names.join(",")),
+        (None, _) => None,
+    };
+    let mut lines: Vec<String> = header.iter().cloned().collect();
+    match ans {
+        Value::Matrix(matrix) => lines.extend((0..matrix.rows()).map(|row| {
+            (0..matrix.cols())
+                .map(|col| matrix.get(row, col).to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })),
+        other => lines.push(csv_field(&other.to_string())),
+    }
+    let rows = lines.len() - header.iter().count();
+    fs::write(path, lines.join("\n") + "\n")
+        .map_err(|e| format!("cannot write '{}': {}", path, e))?;
+    Ok(format!(
+        "Wrote {} row{} to {}",
+        rows,
+        if rows == 1 { "" } else { "s" },
+        path
+    ))
+}
+
+/// Run a `:command` line other than `:quit`, returning the text to show
+fn run_command(words: &[&str], env: &mut Env) -> Result<String, String> {
+    match words[0] {
+        ":clear" => {
+            env.clear();
+            Ok("Cleared all variables and history".to_string())
+        }
+        ":vars" => {
+            if env.variables.is_empty() && env.functions.is_empty() && env.history.is_empty() {
+                return Ok("No variables defined".to_string());
+            }
+            let mut lines: Vec<String> = env
+                .variables
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value))
+                .collect();
+            lines.extend(
+                env.functions
+                    .iter()
+                    .map(|(name, function)| format!("{}{}", name, function)),
+            );
+            if let Some(ans) = env.ans() {
+                lines.push(format!("ans = {}", ans));
+            }
+            Ok(lines.join("\n"))
+        }
+        ":mode" => run_mode_command(&words[1..], env),
+        ":angle" => run_angle_command(&words[1..], env),
+        ":tolerance" => run_tolerance_command(&words[1..], env),
+        ":save" => run_save_command(&words[1..], env),
+        ":load" => run_load_command(&words[1..], env),
+        ":export" => run_export_command(&words[1..], env),
+        command => Err(format!(
+            "Unknown command '{}', try :vars, :clear, :mode, :angle, :tolerance, :save, :load, :export or :quit",
+            command
+        )),
+    }
+}
+
+/// Bring back the session saved when the REPL last exited, if there is one
+fn restore_session(env: &mut Env) {
+    let Some(path) = session::session_path().filter(|path| path.exists()) else {
+        return;
+    };
+    match session::load(env, &path) {
+        Ok(()) => println!("Restored the last session: {}", session::summary(env)),
+        Err(message) => eprintln!("Warning: could not restore the last session: {}", message),
+    }
+}
+
+/// Save the session and the typed lines so the next REPL picks them up
+fn save_session(env: &Env, editor: &mut DefaultEditor) {
+    let Some(path) = session::session_path() else {
+        return;
+    };
+    if let Err(e) = session::save(env, &path) {
+        eprintln!(
+            "Warning: could not save the session to '{}': {}",
+            path.display(),
+            e
+        );
+    }
+    if let Some(history) = session::history_path() {
+        if let Err(e) = editor.save_history(&history) {
+            eprintln!(
+                "Warning: could not save the history to '{}': {}",
+                history.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Interactive read-eval-print loop with line editing: the arrow keys walk
+/// the history and Ctrl-R searches it
+fn run_repl(env: &mut Env) {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Error: cannot start the line editor: {}", e);
+            return;
+        }
+    };
+    // The history file is missing on the first run, which is fine
+    if let Some(history) = session::history_path() {
+        let _ = editor.load_history(&history);
+    }
+
+    println!("Enter an expression (e.g. 3 + 4 * (2 - 1)), `let x = 5` to store a value,");
+    println!("`f(x) = x^2 + 1` to define a function, `ans` or `$1` to reuse results,");
+    println!("or :vars, :clear, :mode, :angle, :tolerance, :save, :load, :export, :quit");
+
+    loop {
+        let input = match editor.readline("> ") {
+            Ok(input) => input,
+            // Ctrl-C abandons the current line
+            Err(ReadlineError::Interrupted) => continue,
+            // End of input (Ctrl-D)
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Error: failed to read line: {}", e);
+                break;
+            }
+        };
+
+        let line = input.trim_end();
+        if !line.trim().is_empty() {
+            // Only fails for lines the history ignores, such as duplicates
+            let _ = editor.add_history_entry(line);
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first().copied() {
+            None => continue,
+            Some(":quit") | Some(":q") => break,
+            Some(command) if command.starts_with(':') => match run_command(&words, env) {
+                Ok(message) => println!("{}", message),
+                Err(message) => println!("Error: {}", message),
+            },
+            _ => println!("{}", report(line, execute(line, env))),
+        }
+    }
+    save_session(env, &mut editor);
+}
+
+/// How batch results are written out
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    /// One result per line on stdout, errors on stderr
+    Text,
+    /// A JSON array with one object per evaluated line
+    Json,
+    /// A CSV table with a header row
+    Csv,
+}
+
+/// Quote a string for JSON output
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quote a field for CSV output when it contains a separator, quote or newline
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes batch results in the chosen format, one line at a time so long
+/// pipes produce output as they go. Writes fail rather than panic once the
+/// reader has gone away, as `head` does.
+struct BatchWriter {
+    format: OutputFormat,
+    written: usize,
+    out: StdoutLock<'static>,
+}
+
+impl BatchWriter {
+    fn begin(format: OutputFormat) -> io::Result<BatchWriter> {
+        let mut out = io::stdout().lock();
+        match format {
+            OutputFormat::Text => {}
+            OutputFormat::Json => writeln!(out, "[")?,
+            OutputFormat::Csv => writeln!(out, "line,input,result,error_kind,error")?,
+        }
+        Ok(BatchWriter {
+            format,
+            written: 0,
+            out,
+        })
+    }
+
+    fn write(
+        &mut self,
+        line: usize,
+        input: &str,
+        result: &Result<Outcome, CalcError>,
+    ) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Text => match result {
+                Ok(outcome) => writeln!(self.out, "{}", outcome.value_text())?,
+                Err(e) => eprintln!("Error on line {}: {}", line, e),
+            },
+            OutputFormat::Json => {
+                let body = match result {
+                    Ok(outcome) => {
+                        let name = match outcome {
+                            Outcome::Value {
+                                name: Some(name), ..
+                            } => format!(", \"name\": {}", json_string(name)),
+                            _ => String::new(),
+                        };
+                        format!("\"result\": {}{}", json_string(&outcome.value_text()), name)
+                    }
+                    Err(e) => format!(
+                        "\"error\": {{\"kind\": {}, \"message\": {}}}",
+                        json_string(e.kind()),
+                        json_string(&e.to_string())
+                    ),
+                };
+                let separator = if self.written == 0 { "" } else { ",\n" };
+                write!(
+                    self.out,
+                    "{}  {{\"line\": {}, \"input\": {}, {}}}",
+                    separator,
+                    line,
+                    json_string(input),
+                    body
+                )?;
+            }
+            OutputFormat::Csv => {
+                let (value, kind, message) = match result {
+                    Ok(outcome) => (outcome.value_text(), "", String::new()),
+                    Err(e) => (String::new(), e.kind(), e.to_string()),
+                };
+                writeln!(
+                    self.out,
+                    "{},{},{},{},{}",
+                    line,
+                    csv_field(input),
+                    csv_field(&value),
+                    kind,
+                    csv_field(&message)
+                )?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        if self.format == OutputFormat::Json {
+            if self.written > 0 {
+                writeln!(self.out)?;
+            }
+            writeln!(self.out, "]")?;
+        }
+        self.out.flush()
+    }
+}
+
+/// Evaluate numbered lines without prompting. Blank lines and `#` comments
+/// are skipped, `:` commands such as `:mode` apply to the lines after them.
+/// Returns whether every line succeeded.
+fn run_batch(lines: &[(usize, String)], env: &mut Env, format: OutputFormat) -> io::Result<bool> {
+    let mut ok = true;
+    let mut writer = BatchWriter::begin(format)?;
+
+    for (number, line) in lines {
+        let line = line.trim_end();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first().copied() {
+            None => continue,
+            Some(word) if word.starts_with('#') => continue,
+            Some(":quit") | Some(":q") => break,
+            Some(command) if command.starts_with(':') => {
+                // Commands only change settings, so their messages are not
+                // part of the results; failures still count
+                if let Err(message) = run_command(&words, env) {
+                    eprintln!("Error on line {}: {}", number, message);
+                    ok = false;
+                }
+            }
+            _ => {
+                let result = execute(line, env);
+                ok &= result.is_ok();
+                writer.write(*number, line, &result)?;
+            }
+        }
+    }
+
+    writer.finish()?;
+    Ok(ok)
+}
+
+/// Summarize a column of numbers, one per line, for `--stats`. Blank
+/// lines and `#` comments are skipped; each other line may be any
+/// expression that gives a plain number. Lines that do not are reported
+/// and left out of the summary. Returns whether every line was a number.
+fn run_stats(lines: &[(usize, String)], env: &mut Env, format: OutputFormat) -> io::Result<bool> {
+    let mut ok = true;
+    let mut numbers = Vec::new();
+    for (number, line) in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match evaluate(line, env) {
+            Ok(Value::Number(n)) => numbers.push(n),
+            Ok(other) => {
+                eprintln!(
+                    "Error on line {}: expected a number, found {}",
+                    number, other
+                );
+                ok = false;
+            }
+            Err(e) => {
+                eprintln!("Error on line {}: {}", number, e);
+                ok = false;
+            }
+        }
+    }
+
+    let rows = match summarize(&numbers, env.backend.as_ref()) {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Ok(false);
+        }
+    };
+    let mut out = io::stdout().lock();
+    match format {
+        OutputFormat::Text => {
+            for (name, value) in &rows {
+                writeln!(out, "{:<9}{}", name, value)?;
+            }
+        }
+        OutputFormat::Json => {
+            let fields: Vec<String> = rows
+                .iter()
+                .map(|(name, value)| {
+                    format!(
+                        "  {}: {}",
+                        json_string(name),
+                        json_string(&value.to_string())
+                    )
+                })
+                .collect();
+            writeln!(out, "{{\n{}\n}}", fields.join(",\n"))?;
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "statistic,value")?;
+            for (name, value) in &rows {
+                writeln!(out, "{},{}", name, csv_field(&value.to_string()))?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(ok)
+}
+
+/// Settings taken from the command line
+struct Options {
+    /// `None` keeps the mode of a restored session, or float otherwise
+    mode: Option<String>,
+    scale: u32,
+    overflow: OverflowPolicy,
+    tolerance: Option<f64>,
+    format: OutputFormat,
+    stats: bool,
+    fresh: bool,
+    expressions: Vec<String>,
+    file: Option<String>,
+}
+
+const USAGE: &str = "Usage: calc [options]
+
+Options:
+  -e, --eval <expr>       Evaluate an expression and exit (may be repeated)
+  -f, --file <path>       Evaluate each line of a file and exit
+      --format <format>   Batch output: text, json or csv (default text)
+      --stats             Summarize the numbers read, one per line, instead
+                          of printing each result
+      --mode <mode>       Number mode: float, rational, decimal, integer,
+                          or a fixed-width type i8..i128 / u8..u128
+      --scale <places>    Decimal places kept in decimal mode (default 20)
+      --overflow <policy> Fixed-width overflow: wrapping or checked
+                          (default wrapping)
+      --tolerance <value> Accuracy of numeric roots found by solve()
+                          (default 1e-12)
+      --fresh             Start the REPL without restoring the last session
+  -h, --help              Show this help
+
+Without -e or --file, expressions are read from stdin when it is a pipe,
+and an interactive session starts otherwise. The REPL saves its session
+and line history under $XDG_DATA_HOME/calc (~/.local/share/calc) on exit
+and restores them on the next start; :save and :load keep named
+workspaces there.";
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        mode: None,
+        scale: DEFAULT_SCALE,
+        overflow: OverflowPolicy::Wrapping,
+        tolerance: None,
+        format: OutputFormat::Text,
+        stats: false,
+        fresh: false,
+        expressions: Vec::new(),
+        file: None,
+    };
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} expects a value", flag))
+        };
+        match arg.as_str() {
+            "-e" | "--eval" => options.expressions.push(value(&arg)?),
+            "-f" | "--file" => options.file = Some(value(&arg)?),
+            "--stats" => options.stats = true,
+            "--fresh" => options.fresh = true,
+            "--mode" => options.mode = Some(value(&arg)?),
+            "--scale" => {
+                options.scale = value(&arg)?
+                    .parse()
+                    .map_err(|_| "--scale expects a whole number of decimal places".to_string())?
+            }
+            "--overflow" => options.overflow = parse_overflow(&value(&arg)?)?,
+            "--tolerance" => options.tolerance = Some(parse_tolerance(&value(&arg)?)?),
+            "--format" => {
+                options.format = match value(&arg)?.as_str() {
+                    "text" => OutputFormat::Text,
+                    "json" => OutputFormat::Json,
+                    "csv" => OutputFormat::Csv,
+                    other => {
+                        return Err(format!("Unknown format '{}', use text, json or csv", other))
+                    }
+                }
+            }
+            other => return Err(format!("Unknown argument '{}'", other)),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_args(args.into_iter()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let mode = options.mode.as_deref().unwrap_or("float");
+    let Some(backend) = backend_for(mode, options.scale, options.overflow) else {
+        eprintln!("Error: Unknown mode '{}', choose one of {}", mode, MODES);
+        return ExitCode::from(2);
+    };
+    let mut env = Env::new(backend);
+    env.tolerance = options.tolerance.unwrap_or(DEFAULT_TOLERANCE);
+
+    // Collect numbered lines for batch mode: `-e` expressions first, then the file
+    let mut lines: Vec<(usize, String)> = options
+        .expressions
+        .iter()
+        .enumerate()
+        .map(|(i, expression)| (i + 1, expression.clone()))
+        .collect();
+    if let Some(path) = &options.file {
+        match fs::read_to_string(path) {
+            Ok(contents) => lines.extend(
+                contents
+                    .lines()
+                    .enumerate()
+                    .map(|(i, line)| (i + 1, line.to_string())),
+            ),
+            Err(e) => {
+                eprintln!("Error: cannot read '{}': {}", path, e);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    if lines.is_empty() && options.file.is_none() {
+        if io::stdin().is_terminal() && !options.stats {
+            if !options.fresh {
+                restore_session(&mut env);
+                // Settings given on the command line win over the restored ones
+                if options.mode.is_some() {
+                    if let Some(backend) = backend_for(mode, options.scale, options.overflow) {
+                        env.backend = backend;
+                    }
+                }
+                if let Some(tolerance) = options.tolerance {
+                    env.tolerance = tolerance;
+                }
+            }
+            run_repl(&mut env);
+            return ExitCode::SUCCESS;
+        }
+        for (i, line) in io::stdin().lines().enumerate() {
+            match line {
+                Ok(line) => lines.push((i + 1, line)),
+                Err(e) => {
+                    eprintln!("Error: failed to read line {}: {}", i + 1, e);
+                    return ExitCode::from(2);
+                }
+            }
+        }
+    }
+
+    let result = if options.stats {
+        run_stats(&lines, &mut env, options.format)
+    } else {
+        run_batch(&lines, &mut env, options.format)
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        // The reader stopped early, as `head` does, so there is no one
+        // left to tell
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: cannot write the results: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
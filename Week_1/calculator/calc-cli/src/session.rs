@@ -0,0 +1,97 @@
+//! Where sessions, workspaces and the line-editing history live on disk
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use calc_core::{load_workspace, save_workspace, Env};
+
+/// Extension of workspace files
+const EXTENSION: &str = "calc";
+
+/// `$XDG_DATA_HOME/calc`, falling back to `~/.local/share/calc` as the XDG
+/// base directory spec asks
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("calc"))
+}
+
+/// The session saved when the REPL exits and restored when it starts
+pub fn session_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(format!("session.{}", EXTENSION)))
+}
+
+/// Lines typed at the prompt, for the arrow keys and Ctrl-R
+pub fn history_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("history.txt"))
+}
+
+fn workspace_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("workspaces"))
+}
+
+/// A workspace named in `:save` or `:load`. A bare name lives in the data
+/// directory; anything that looks like a path, such as `team/budget.calc`,
+/// is used as given so workspaces can be shared as ordinary files.
+pub fn workspace_path(name: &str) -> Result<PathBuf, String> {
+    if name.contains(['/', '\\']) || name.ends_with(&format!(".{}", EXTENSION)) {
+        return Ok(PathBuf::from(name));
+    }
+    workspace_dir()
+        .map(|dir| dir.join(format!("{}.{}", name, EXTENSION)))
+        .ok_or_else(|| {
+            "Cannot find a data directory; set XDG_DATA_HOME or give a path ending in .calc"
+                .to_string()
+        })
+}
+
+/// Names of the workspaces saved in the data directory, sorted
+pub fn saved_workspaces() -> Vec<String> {
+    let Some(entries) = workspace_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != EXTENSION {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// Write the session to `path`. The text goes to a temporary file first and
+/// is renamed into place, so a crash never leaves half a workspace behind.
+pub fn save(env: &Env, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let temporary = path.with_extension(format!("{}.tmp", EXTENSION));
+    fs::write(&temporary, save_workspace(env))?;
+    fs::rename(&temporary, path)
+}
+
+/// Replace the session with the workspace at `path`
+pub fn load(env: &mut Env, path: &Path) -> Result<(), String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+    load_workspace(&text, env).map_err(|e| format!("'{}' {}", path.display(), e))
+}
+
+/// `2 variables, 1 function, 5 results`
+pub fn summary(env: &Env) -> String {
+    let count = |n: usize, noun: &str| format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" });
+    format!(
+        "{}, {}, {}",
+        count(env.variables.len(), "variable"),
+        count(env.functions.len(), "function"),
+        count(env.history.len(), "result")
+    )
+}
@@ -0,0 +1,596 @@
+//! The numeric modes. Each one implements `Backend`, and the evaluator only
+//! talks to that trait.
+
+use std::fmt;
+
+use crate::error::CalcError;
+use crate::number::{exact_exponent, BigInt, Decimal, IntType, Number, Rational};
+
+/// Decimal places kept by the decimal backend unless `--scale` says otherwise
+pub const DEFAULT_SCALE: u32 = 20;
+
+/// Arithmetic shared by every numeric mode. The evaluator only talks to
+/// this trait, so adding a mode never touches the parser or the REPL.
+pub trait Backend {
+    /// Name shown by `:mode`
+    fn name(&self) -> String;
+
+    /// Read a number literal from the source text
+    fn literal(&self, text: &str) -> Result<Number, CalcError>;
+
+    /// Bring a value from another mode (e.g. a stored variable) into this one
+    fn convert(&self, value: &Number) -> Result<Number, CalcError>;
+
+    fn add(&self, a: &Number, b: &Number) -> Result<Number, CalcError>;
+    fn sub(&self, a: &Number, b: &Number) -> Result<Number, CalcError>;
+    fn mul(&self, a: &Number, b: &Number) -> Result<Number, CalcError>;
+    fn div(&self, a: &Number, b: &Number) -> Result<Number, CalcError>;
+    fn rem(&self, a: &Number, b: &Number) -> Result<Number, CalcError>;
+    fn pow(&self, a: &Number, b: &Number) -> Result<Number, CalcError>;
+    fn neg(&self, a: &Number) -> Result<Number, CalcError>;
+
+    /// Read a `0x`, `0o` or `0b` literal given its bit pattern
+    fn radix_literal(&self, pattern: u128) -> Result<Number, CalcError> {
+        self.convert(&Number::Integer(BigInt::from_u128(pattern)))
+    }
+
+    /// `&`, `|`, xor (`x`), `<<` (`<`) and `>>` (`>`) on whole numbers.
+    /// Without a fixed width, negative numbers act as if they had infinitely
+    /// many leading ones and shifts multiply or floor-divide by powers of two.
+    fn bitwise(&self, a: &Number, op: char, b: &Number) -> Result<Number, CalcError> {
+        let (x, y) = (a.to_integer()?, b.to_integer()?);
+        let result = match op {
+            '<' | '>' => {
+                if y.is_negative() {
+                    return Err(CalcError::Domain(format!("Cannot shift by {}", y)));
+                }
+                let factor = Rational::from_bigint(BigInt::from_i64(2)).pow(&y)?;
+                let x = Rational::from_bigint(x);
+                if op == '<' {
+                    x.mul(&factor).num
+                } else {
+                    x.div(&factor).floor()
+                }
+            }
+            _ => {
+                let too_wide = || {
+                    CalcError::Overflow(
+                        "Bitwise operators work on values up to 128 bits wide".to_string(),
+                    )
+                };
+                let x = x.to_i128().ok_or_else(too_wide)?;
+                let y = y.to_i128().ok_or_else(too_wide)?;
+                BigInt::from_i128(match op {
+                    '&' => x & y,
+                    '|' => x | y,
+                    _ => x ^ y,
+                })
+            }
+        };
+        self.convert(&Number::Integer(result))
+    }
+
+    /// `~x`, which is `-x - 1` without a fixed width
+    fn bit_not(&self, a: &Number) -> Result<Number, CalcError> {
+        let value = a.to_integer()?;
+        self.convert(&Number::Integer(value.neg().sub(&BigInt::from_i64(1))))
+    }
+
+    /// Whether `^` means xor instead of power in this mode
+    fn caret_is_xor(&self) -> bool {
+        false
+    }
+}
+
+/// Plain `f64` arithmetic, fast but subject to binary rounding
+pub(crate) struct FloatBackend;
+
+impl FloatBackend {
+    /// Turn `inf` and `NaN` into errors so they are never shown as results
+    pub(crate) fn finite(value: f64, operation: &str) -> Result<Number, CalcError> {
+        if value.is_nan() {
+            Err(CalcError::Domain(format!(
+                "The {} has no real result",
+                operation
+            )))
+        } else if value.is_infinite() {
+            Err(CalcError::Overflow(format!(
+                "The {} is too large for float mode",
+                operation
+            )))
+        } else {
+            Ok(Number::Float(value))
+        }
+    }
+}
+
+impl Backend for FloatBackend {
+    fn name(&self) -> String {
+        "float".to_string()
+    }
+
+    fn literal(&self, text: &str) -> Result<Number, CalcError> {
+        let value = text
+            .parse()
+            .map_err(|_| CalcError::Domain(format!("Invalid number '{}'", text)))?;
+        FloatBackend::finite(value, "number")
+    }
+
+    fn convert(&self, value: &Number) -> Result<Number, CalcError> {
+        FloatBackend::finite(value.to_f64(), "result")
+    }
+
+    fn add(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        FloatBackend::finite(a.to_f64() + b.to_f64(), "sum")
+    }
+
+    fn sub(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        FloatBackend::finite(a.to_f64() - b.to_f64(), "difference")
+    }
+
+    fn mul(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        FloatBackend::finite(a.to_f64() * b.to_f64(), "product")
+    }
+
+    fn div(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_f64();
+        if divisor == 0.0 {
+            return Err(CalcError::DivisionByZero);
+        }
+        FloatBackend::finite(a.to_f64() / divisor, "quotient")
+    }
+
+    fn rem(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_f64();
+        if divisor == 0.0 {
+            return Err(CalcError::DivisionByZero);
+        }
+        FloatBackend::finite(a.to_f64() % divisor, "remainder")
+    }
+
+    fn pow(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let (base, exponent) = (a.to_f64(), b.to_f64());
+        if base == 0.0 && exponent < 0.0 {
+            return Err(CalcError::DivisionByZero);
+        }
+        FloatBackend::finite(base.powf(exponent), "power")
+    }
+
+    fn neg(&self, a: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Float(-a.to_f64()))
+    }
+}
+
+/// Exact fractions, so `1/3 * 3` is exactly `1`
+pub struct RationalBackend;
+
+impl Backend for RationalBackend {
+    fn name(&self) -> String {
+        "rational".to_string()
+    }
+
+    fn literal(&self, text: &str) -> Result<Number, CalcError> {
+        Rational::parse_decimal(text)
+            .map(Number::Rational)
+            .ok_or_else(|| CalcError::Domain(format!("Invalid number '{}'", text)))
+    }
+
+    fn convert(&self, value: &Number) -> Result<Number, CalcError> {
+        value.to_rational().map(Number::Rational)
+    }
+
+    fn add(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Rational(a.to_rational()?.add(&b.to_rational()?)))
+    }
+
+    fn sub(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Rational(a.to_rational()?.sub(&b.to_rational()?)))
+    }
+
+    fn mul(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Rational(a.to_rational()?.mul(&b.to_rational()?)))
+    }
+
+    fn div(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_rational()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        Ok(Number::Rational(a.to_rational()?.div(&divisor)))
+    }
+
+    fn rem(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_rational()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        Ok(Number::Rational(exact_rem(&a.to_rational()?, &divisor)))
+    }
+
+    fn pow(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        match exact_pow(a, b)? {
+            Some(result) => Ok(Number::Rational(result)),
+            None => self.convert(&FloatBackend.pow(a, b)?),
+        }
+    }
+
+    fn neg(&self, a: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Rational(a.to_rational()?.neg()))
+    }
+}
+
+/// Extra places `DecimalBackend::pow` keeps while it works
+const GUARD_DIGITS: u32 = 10;
+
+/// Decimals with a fixed number of places. Every operation is done exactly
+/// and then rounded to `scale` places, which is what money sums expect.
+pub struct DecimalBackend {
+    scale: u32,
+}
+
+impl DecimalBackend {
+    pub(crate) fn new(scale: u32) -> DecimalBackend {
+        DecimalBackend { scale }
+    }
+
+    fn round(&self, value: Rational) -> Number {
+        Number::Decimal(Decimal::from_rational(&value, self.scale))
+    }
+}
+
+impl Backend for DecimalBackend {
+    fn name(&self) -> String {
+        format!("decimal (scale {})", self.scale)
+    }
+
+    fn literal(&self, text: &str) -> Result<Number, CalcError> {
+        Rational::parse_decimal(text)
+            .map(|value| self.round(value))
+            .ok_or_else(|| CalcError::Domain(format!("Invalid number '{}'", text)))
+    }
+
+    fn convert(&self, value: &Number) -> Result<Number, CalcError> {
+        Ok(self.round(value.to_rational()?))
+    }
+
+    fn add(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(self.round(a.to_rational()?.add(&b.to_rational()?)))
+    }
+
+    fn sub(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(self.round(a.to_rational()?.sub(&b.to_rational()?)))
+    }
+
+    fn mul(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(self.round(a.to_rational()?.mul(&b.to_rational()?)))
+    }
+
+    fn div(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_rational()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        Ok(self.round(a.to_rational()?.div(&divisor)))
+    }
+
+    fn rem(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_rational()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        Ok(self.round(exact_rem(&a.to_rational()?, &divisor)))
+    }
+
+    /// Whole powers square their way up, rounding each step a few places
+    /// past the scale. Exact powers of a decimal gain digits with every
+    /// step, and `1.005 ^ 360` would otherwise carry over a thousand.
+    fn pow(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let exponent = b.to_rational()?;
+        if !exponent.is_integer() {
+            return self.convert(&FloatBackend.pow(a, b)?);
+        }
+        if a.to_rational()?.is_zero() && exponent.num.is_negative() {
+            return Err(CalcError::DivisionByZero);
+        }
+        let mut remaining = exact_exponent(&exponent.num)?;
+        let working = DecimalBackend::new(self.scale + GUARD_DIGITS);
+        let mut result = working.literal("1")?;
+        let mut base = working.convert(a)?;
+        while remaining > 0 {
+            if remaining % 2 == 1 {
+                result = working.mul(&result, &base)?;
+            }
+            remaining /= 2;
+            if remaining > 0 {
+                base = working.mul(&base, &base)?;
+            }
+        }
+        if exponent.num.is_negative() {
+            result = working.div(&working.literal("1")?, &result)?;
+        }
+        self.convert(&result)
+    }
+
+    fn neg(&self, a: &Number) -> Result<Number, CalcError> {
+        Ok(self.round(a.to_rational()?.neg()))
+    }
+}
+
+/// A whole-number literal for the integer modes. An exponent may make a
+/// decimal whole, as in `6.02e23`.
+fn whole_literal(text: &str) -> Option<BigInt> {
+    if !text.contains(['e', 'E']) {
+        return BigInt::parse(text);
+    }
+    let value = Rational::parse_decimal(text)?;
+    value.is_integer().then_some(value.num)
+}
+
+/// Whole numbers of any size. Division truncates toward zero.
+struct IntegerBackend;
+
+impl Backend for IntegerBackend {
+    fn name(&self) -> String {
+        "integer".to_string()
+    }
+
+    fn literal(&self, text: &str) -> Result<Number, CalcError> {
+        whole_literal(text).map(Number::Integer).ok_or_else(|| {
+            CalcError::Domain(format!(
+                "Integer mode only accepts whole numbers, found '{}'",
+                text
+            ))
+        })
+    }
+
+    fn convert(&self, value: &Number) -> Result<Number, CalcError> {
+        value.to_integer().map(Number::Integer)
+    }
+
+    fn add(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Integer(a.to_integer()?.add(&b.to_integer()?)))
+    }
+
+    fn sub(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Integer(a.to_integer()?.sub(&b.to_integer()?)))
+    }
+
+    fn mul(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Integer(a.to_integer()?.mul(&b.to_integer()?)))
+    }
+
+    fn div(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_integer()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        Ok(Number::Integer(a.to_integer()?.div_rem(&divisor).0))
+    }
+
+    fn rem(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_integer()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        Ok(Number::Integer(a.to_integer()?.div_rem(&divisor).1))
+    }
+
+    fn pow(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let exponent = b.to_integer()?;
+        if exponent.is_negative() {
+            return Err(CalcError::Domain(
+                "Integer mode cannot raise to a negative power".to_string(),
+            ));
+        }
+        let result = Rational::from_bigint(a.to_integer()?).pow(&exponent)?;
+        Ok(Number::Integer(result.num))
+    }
+
+    fn neg(&self, a: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Integer(a.to_integer()?.neg()))
+    }
+}
+
+/// What fixed-width arithmetic does with a result that does not fit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowPolicy {
+    /// Keep the low bits, like `wrapping_add`
+    Wrapping,
+    /// Report an overflow error, like `checked_add`
+    Checked,
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverflowPolicy::Wrapping => write!(f, "wrapping"),
+            OverflowPolicy::Checked => write!(f, "checked"),
+        }
+    }
+}
+
+/// Programmer mode: integers of one fixed width such as `u8` or `i64`.
+/// Results are worked out exactly and then wrapped or rejected by the
+/// overflow policy; `^` is xor here.
+struct FixedBackend {
+    int_type: IntType,
+    overflow: OverflowPolicy,
+}
+
+impl FixedBackend {
+    /// Apply the overflow policy to an exact result
+    fn fit(&self, value: BigInt) -> Result<Number, CalcError> {
+        if self.overflow == OverflowPolicy::Checked && !self.int_type.fits(&value) {
+            return Err(CalcError::Overflow(format!(
+                "{} does not fit in {}",
+                value, self.int_type
+            )));
+        }
+        Ok(Number::Word(self.int_type.wrap(&value), self.int_type))
+    }
+
+    fn pattern(&self, value: &Number) -> Result<u128, CalcError> {
+        match self.convert(value)? {
+            Number::Word(pattern, _) => Ok(pattern),
+            _ => unreachable!("convert always returns a word"),
+        }
+    }
+
+    /// Shift amount after the overflow policy: checked mode rejects shifting
+    /// by the width or more, wrapping mode reduces the amount modulo the width
+    fn shift_amount(&self, amount: &Number) -> Result<u32, CalcError> {
+        let amount = amount.to_integer()?;
+        let bits = BigInt::from_i64(self.int_type.bits as i64);
+        if amount.is_negative() || (self.overflow == OverflowPolicy::Checked && amount >= bits) {
+            return Err(CalcError::Overflow(format!(
+                "Shift by {} is out of range for {}",
+                amount, self.int_type
+            )));
+        }
+        let reduced = amount.div_rem(&bits).1;
+        Ok(reduced.to_u128().expect("below 128") as u32)
+    }
+}
+
+impl Backend for FixedBackend {
+    fn name(&self) -> String {
+        format!("{} ({})", self.int_type, self.overflow)
+    }
+
+    fn literal(&self, text: &str) -> Result<Number, CalcError> {
+        let value = whole_literal(text).ok_or_else(|| {
+            CalcError::Domain(format!(
+                "Programmer mode only accepts whole numbers, found '{}'",
+                text
+            ))
+        })?;
+        self.fit(value)
+    }
+
+    /// A radix literal is a bit pattern, so `0xff` is `-1` as an `i8`
+    fn radix_literal(&self, pattern: u128) -> Result<Number, CalcError> {
+        if pattern & !self.int_type.mask() != 0 {
+            return Err(CalcError::Overflow(format!(
+                "0x{:x} does not fit in {}",
+                pattern, self.int_type
+            )));
+        }
+        Ok(Number::Word(pattern, self.int_type))
+    }
+
+    fn convert(&self, value: &Number) -> Result<Number, CalcError> {
+        match value {
+            Number::Word(_, int_type) if *int_type == self.int_type => Ok(value.clone()),
+            _ => self.fit(value.to_integer()?),
+        }
+    }
+
+    fn add(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        self.fit(a.to_integer()?.add(&b.to_integer()?))
+    }
+
+    fn sub(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        self.fit(a.to_integer()?.sub(&b.to_integer()?))
+    }
+
+    fn mul(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        self.fit(a.to_integer()?.mul(&b.to_integer()?))
+    }
+
+    fn div(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_integer()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        self.fit(a.to_integer()?.div_rem(&divisor).0)
+    }
+
+    fn rem(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let divisor = b.to_integer()?;
+        if divisor.is_zero() {
+            return Err(CalcError::DivisionByZero);
+        }
+        self.fit(a.to_integer()?.div_rem(&divisor).1)
+    }
+
+    fn pow(&self, a: &Number, b: &Number) -> Result<Number, CalcError> {
+        let exponent = b.to_integer()?;
+        if exponent.is_negative() {
+            return Err(CalcError::Domain(
+                "Programmer mode cannot raise to a negative power".to_string(),
+            ));
+        }
+        self.fit(Rational::from_bigint(a.to_integer()?).pow(&exponent)?.num)
+    }
+
+    fn neg(&self, a: &Number) -> Result<Number, CalcError> {
+        self.fit(a.to_integer()?.neg())
+    }
+
+    fn bitwise(&self, a: &Number, op: char, b: &Number) -> Result<Number, CalcError> {
+        let mask = self.int_type.mask();
+        let x = self.pattern(a)?;
+        let pattern = match op {
+            '<' => (x << self.shift_amount(b)?) & mask,
+            // Signed types shift in copies of the sign bit
+            '>' if self.int_type.signed => {
+                let value = self.int_type.value(x).to_i128().expect("fits in i128");
+                self.int_type
+                    .wrap(&BigInt::from_i128(value >> self.shift_amount(b)?))
+            }
+            '>' => x >> self.shift_amount(b)?,
+            '&' => x & self.pattern(b)?,
+            '|' => x | self.pattern(b)?,
+            _ => x ^ self.pattern(b)?,
+        };
+        Ok(Number::Word(pattern, self.int_type))
+    }
+
+    fn bit_not(&self, a: &Number) -> Result<Number, CalcError> {
+        Ok(Number::Word(
+            !self.pattern(a)? & self.int_type.mask(),
+            self.int_type,
+        ))
+    }
+
+    fn caret_is_xor(&self) -> bool {
+        true
+    }
+}
+
+/// Remainder of truncating division, matching `%` on primitive numbers
+fn exact_rem(a: &Rational, b: &Rational) -> Rational {
+    let quotient = a.div(b);
+    let (truncated, _) = quotient.num.div_rem(&quotient.den);
+    a.sub(&b.mul(&Rational::from_bigint(truncated)))
+}
+
+/// Exact power for whole exponents; `None` means the exponent is fractional
+/// and the caller should fall back to floating point
+fn exact_pow(a: &Number, b: &Number) -> Result<Option<Rational>, CalcError> {
+    let exponent = b.to_rational()?;
+    if !exponent.is_integer() {
+        return Ok(None);
+    }
+    let base = a.to_rational()?;
+    if base.is_zero() && exponent.num.is_negative() {
+        return Err(CalcError::DivisionByZero);
+    }
+    base.pow(&exponent.num).map(Some)
+}
+
+/// Names accepted by `--mode` and `:mode`
+pub const MODES: &str = "float, rational, decimal, integer, i8..i128, u8..u128";
+
+/// Look up a backend by the name used with `--mode` and `:mode`. `scale` only
+/// matters in decimal mode and `overflow` only for fixed-width types.
+pub fn backend_for(mode: &str, scale: u32, overflow: OverflowPolicy) -> Option<Box<dyn Backend>> {
+    match mode {
+        "float" => Some(Box::new(FloatBackend)),
+        "rational" => Some(Box::new(RationalBackend)),
+        "decimal" => Some(Box::new(DecimalBackend { scale })),
+        "integer" | "bigint" => Some(Box::new(IntegerBackend)),
+        _ => IntType::parse(mode)
+            .map(|int_type| Box::new(FixedBackend { int_type, overflow }) as Box<dyn Backend>),
+    }
+}
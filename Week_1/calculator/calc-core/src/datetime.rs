@@ -0,0 +1,541 @@
+//! Dates, times and durations: calendar literals such as `2026-10-18` or
+//! `2026-10-18T09:30+02:00`, the clock names `now` and `today`, and
+//! compound durations such as `3h 20m`
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::backend::Backend;
+use crate::error::CalcError;
+use crate::eval::binary_values;
+use crate::number::{BigInt, Number, Rational};
+use crate::parser::{operator_symbol, Expr};
+use crate::units::{rescale, Unit, Value};
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+const SECONDS_PER_DAY: i128 = 86_400;
+const NANOS_PER_DAY: i128 = SECONDS_PER_DAY * NANOS_PER_SECOND;
+
+/// Names that read the system clock
+pub(crate) const CLOCK_NAMES: &[&str] = &["now", "today"];
+
+/// Suffixes allowed in a compound duration, largest first, with their
+/// length in seconds
+const DURATION_SUFFIXES: &[(&str, &str)] = &[
+    ("w", "604800"),
+    ("d", "86400"),
+    ("h", "3600"),
+    ("m", "60"),
+    ("min", "60"),
+    ("s", "1"),
+    ("ms", "1/1000"),
+];
+
+/// A point in time, kept as nanoseconds since 1970-01-01T00:00:00Z
+/// together with the UTC offset it is shown in. Times written without an
+/// offset are taken as UTC and shown without one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateTime {
+    nanos: i128,
+    /// Seconds east of UTC
+    offset: Option<i32>,
+    /// Written as a plain date, so it prints as one while it falls on midnight
+    date_only: bool,
+}
+
+impl DateTime {
+    /// Nanoseconds since 1970-01-01T00:00:00Z
+    pub fn unix_nanos(&self) -> i128 {
+        self.nanos
+    }
+
+    /// The offset from UTC in seconds, or `None` when none was given
+    pub fn offset(&self) -> Option<i32> {
+        self.offset
+    }
+
+    /// The current time in UTC, to the second
+    fn now() -> DateTime {
+        let seconds = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as i128,
+            Err(e) => -(e.duration().as_secs() as i128),
+        };
+        DateTime {
+            nanos: seconds * NANOS_PER_SECOND,
+            offset: Some(0),
+            date_only: false,
+        }
+    }
+
+    /// Today's date in UTC
+    fn today() -> DateTime {
+        let now = DateTime::now().nanos;
+        DateTime {
+            nanos: now - now.rem_euclid(NANOS_PER_DAY),
+            offset: None,
+            date_only: true,
+        }
+    }
+
+    fn shifted(&self, nanos: i128) -> Result<DateTime, CalcError> {
+        let nanos = self
+            .nanos
+            .checked_add(nanos)
+            .ok_or_else(|| CalcError::Overflow("The date is out of range".to_string()))?;
+        Ok(DateTime {
+            nanos,
+            ..self.clone()
+        })
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// ISO 8601, so the text reads back as the same date
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let local = self.nanos + self.offset.unwrap_or(0) as i128 * NANOS_PER_SECOND;
+        let time = local.rem_euclid(NANOS_PER_DAY);
+        let (year, month, day) = civil_from_days(local.div_euclid(NANOS_PER_DAY) as i64);
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)?;
+        if self.date_only && time == 0 {
+            return Ok(());
+        }
+
+        let (seconds, fraction) = (time / NANOS_PER_SECOND, time % NANOS_PER_SECOND);
+        write!(f, "T{:02}:{:02}", seconds / 3600, seconds / 60 % 60)?;
+        if seconds % 60 != 0 || fraction != 0 {
+            write!(f, ":{:02}{}", seconds % 60, fraction_text(fraction))?;
+        }
+        match self.offset {
+            None => Ok(()),
+            Some(0) => write!(f, "Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.abs();
+                write!(f, "{}{:02}:{:02}", sign, offset / 3600, offset / 60 % 60)
+            }
+        }
+    }
+}
+
+/// `.25` for a quarter of a second, or nothing for a whole second
+fn fraction_text(nanos: i128) -> String {
+    if nanos == 0 {
+        return String::new();
+    }
+    format!(".{:09}", nanos).trim_end_matches('0').to_string()
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Count years from March so the leap day falls at the end of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date `days` after 1970-01-01, as year, month and day
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+fn digits_at(chars: &[char], at: usize, count: usize) -> bool {
+    chars.len() >= at + count && chars[at..at + count].iter().all(char::is_ascii_digit)
+}
+
+/// `HH:MM` starting at `at`
+fn clock_at(chars: &[char], at: usize) -> bool {
+    digits_at(chars, at, 2) && chars.get(at + 2) == Some(&':') && digits_at(chars, at + 3, 2)
+}
+
+/// Length of the date literal at the start of `chars`, if there is one:
+/// `YYYY-MM-DD`, then optionally a time `HH:MM[:SS[.fff]]` after `T` or a
+/// space, then optionally `Z` or an offset `±HH:MM`
+pub(crate) fn scan_datetime(chars: &[char]) -> Option<usize> {
+    let dash = |at: usize| chars.get(at) == Some(&'-');
+    let is_date = digits_at(chars, 0, 4)
+        && dash(4)
+        && digits_at(chars, 5, 2)
+        && dash(7)
+        && digits_at(chars, 8, 2)
+        && !chars.get(10).is_some_and(char::is_ascii_digit);
+    if !is_date {
+        return None;
+    }
+
+    let mut end = 10;
+    if !(matches!(chars.get(end), Some('T' | ' ')) && clock_at(chars, end + 1)) {
+        return Some(end);
+    }
+    end += 6;
+    if chars.get(end) == Some(&':') && digits_at(chars, end + 1, 2) {
+        end += 3;
+        if chars.get(end) == Some(&'.') && digits_at(chars, end + 1, 1) {
+            end += 1;
+            while chars.get(end).is_some_and(char::is_ascii_digit) {
+                end += 1;
+            }
+        }
+    }
+    match chars.get(end) {
+        Some('Z') => end += 1,
+        Some('+' | '-') if clock_at(chars, end + 1) => end += 6,
+        _ => {}
+    }
+    Some(end)
+}
+
+/// Read a literal found by `scan_datetime`, checking that the date and
+/// time exist
+pub(crate) fn parse_datetime(text: &str) -> Result<DateTime, String> {
+    let field = |from: usize, to: usize| text[from..to].parse::<i64>().unwrap_or(0);
+    let invalid = |reason: String| format!("invalid date '{}': {}", text, reason);
+
+    let (year, month, day) = (field(0, 4), field(5, 7), field(8, 10));
+    if !(1..=12).contains(&month) {
+        return Err(invalid("the month must be from 01 to 12".to_string()));
+    }
+    let last_day = days_in_month(year, month);
+    if !(1..=last_day).contains(&day) {
+        return Err(invalid(format!(
+            "{} {} has {} days",
+            MONTH_NAMES[month as usize - 1],
+            year,
+            last_day
+        )));
+    }
+    let mut nanos = days_from_civil(year, month, day) as i128 * NANOS_PER_DAY;
+    if text.len() == 10 {
+        return Ok(DateTime {
+            nanos,
+            offset: None,
+            date_only: true,
+        });
+    }
+
+    let (hour, minute) = (field(11, 13), field(14, 16));
+    let mut rest = &text[16..];
+    let mut second = 0;
+    if let Some(tail) = rest.strip_prefix(':') {
+        second = field(17, 19);
+        rest = &tail[2..];
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid(
+            "the time must be from 00:00 to 23:59:59".to_string(),
+        ));
+    }
+    nanos += ((hour * 60 + minute) * 60 + second) as i128 * NANOS_PER_SECOND;
+    if let Some(tail) = rest.strip_prefix('.') {
+        let digits = tail
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        // Keep nanoseconds; finer digits are dropped
+        let padded = format!("{:0<9}", &tail[..digits.min(9)]);
+        nanos += padded.parse::<i128>().unwrap_or(0);
+        rest = &tail[digits..];
+    }
+
+    let offset = match rest {
+        "" => None,
+        "Z" => Some(0),
+        zone => {
+            let (hours, minutes) = (
+                field(text.len() - 5, text.len() - 3),
+                field(text.len() - 2, text.len()),
+            );
+            if hours > 23 || minutes > 59 {
+                return Err(invalid(format!("'{}' is not a UTC offset", zone)));
+            }
+            let seconds = (hours * 60 + minutes) * 60;
+            Some(if zone.starts_with('-') {
+                -seconds
+            } else {
+                seconds
+            } as i32)
+        }
+    };
+    nanos -= offset.unwrap_or(0) as i128 * NANOS_PER_SECOND;
+    Ok(DateTime {
+        nanos,
+        offset,
+        date_only: false,
+    })
+}
+
+/// `now` and `today`, unless the name means something else
+pub(crate) fn clock(name: &str) -> Option<Value> {
+    match name {
+        "now" => Some(Value::Date(DateTime::now())),
+        "today" => Some(Value::Date(DateTime::today())),
+        _ => None,
+    }
+}
+
+/// The offset named by the target of `date in UTC+2`: `UTC` itself, or
+/// `UTC` plus or minus a number of hours
+pub(crate) fn zone_offset(target: &Expr) -> Option<i32> {
+    let is_utc =
+        |expr: &Expr| matches!(expr, Expr::Variable(name) if name == "UTC" || name == "utc");
+    let (sign, hours) = match target {
+        utc if is_utc(utc) => return Some(0),
+        Expr::Binary(utc, op @ ('+' | '-'), hours) if is_utc(utc) => (*op, hours),
+        _ => return None,
+    };
+    let Expr::Number(hours) = hours.as_ref() else {
+        return None;
+    };
+    let hours = hours.parse::<f64>().ok().filter(|h| *h <= 23.75)?;
+    let seconds = (hours * 3600.0).round() as i32;
+    Some(if sign == '-' { -seconds } else { seconds })
+}
+
+/// Show a date in another UTC offset. The moment it names stays the same.
+pub(crate) fn in_zone(date: &DateTime, offset: i32) -> DateTime {
+    DateTime {
+        nanos: date.nanos,
+        offset: Some(offset),
+        date_only: false,
+    }
+}
+
+/// Length of the compound duration at the start of `chars`, if there is
+/// one: two or more groups such as `3h 20m` or `1d 4h 30m 15s`, largest
+/// first. A single group such as `3h` is left to be read as a quantity.
+pub(crate) fn scan_duration(chars: &[char]) -> Option<usize> {
+    let mut groups = 0;
+    let mut last_rank = None;
+    let (mut at, mut end) = (0, 0);
+    loop {
+        let start = at;
+        while chars
+            .get(at)
+            .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+        {
+            at += 1;
+        }
+        let suffix_start = at;
+        while chars
+            .get(at)
+            .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+        {
+            at += 1;
+        }
+        let suffix: String = chars[suffix_start..at].iter().collect();
+        let rank = DURATION_SUFFIXES
+            .iter()
+            .position(|(name, _)| *name == suffix)
+            .map(|rank| if suffix == "min" { rank - 1 } else { rank });
+        let in_order = match (rank, last_rank) {
+            (Some(rank), Some(last)) => rank > last,
+            (rank, None) => rank.is_some(),
+            _ => false,
+        };
+        if suffix_start == start || !in_order {
+            break;
+        }
+        groups += 1;
+        last_rank = rank;
+        end = at;
+        while chars.get(at) == Some(&' ') {
+            at += 1;
+        }
+        if !chars.get(at).is_some_and(char::is_ascii_digit) {
+            break;
+        }
+    }
+    (groups >= 2).then_some(end)
+}
+
+/// The length in seconds of a duration literal found by `scan_duration`
+pub(crate) fn duration_seconds(backend: &dyn Backend, text: &str) -> Result<Number, CalcError> {
+    let mut total = backend.literal("0")?;
+    for group in text.split_whitespace() {
+        let split = group
+            .find(|c: char| c.is_alphabetic())
+            .expect("the tokenizer checked every group has a suffix");
+        let (amount, suffix) = group.split_at(split);
+        let (_, factor) = DURATION_SUFFIXES
+            .iter()
+            .find(|(name, _)| *name == suffix)
+            .expect("the tokenizer checked the suffix");
+        let factor = match factor.split_once('/') {
+            Some((num, den)) => Rational::from_bigint(BigInt::from_i64(num.parse().unwrap_or(1)))
+                .div(&Rational::from_bigint(BigInt::from_i64(
+                    den.parse().unwrap_or(1),
+                ))),
+            None => Rational::from_bigint(BigInt::from_i64(factor.parse().unwrap_or(1))),
+        };
+        let part = backend.mul(
+            &backend.literal(amount)?,
+            &backend.convert(&Number::Rational(factor))?,
+        )?;
+        total = backend.add(&total, &part)?;
+    }
+    Ok(total)
+}
+
+/// A number of seconds as whole nanoseconds, rounded to the nearest
+fn to_nanos(seconds: &Number) -> Result<i128, CalcError> {
+    let one = |n: i64| Rational::from_bigint(BigInt::from_i64(n));
+    seconds
+        .to_rational()?
+        .mul(&one(NANOS_PER_SECOND as i64))
+        .add(&one(1).div(&one(2)))
+        .floor()
+        .to_i128()
+        .ok_or_else(|| CalcError::Overflow(format!("{} s is too long a duration", seconds)))
+}
+
+/// `3h 20m`, `45d` or `1m 30.5s`, the way durations are printed
+pub(crate) fn format_duration(seconds: &Number) -> String {
+    let Ok(nanos) = to_nanos(seconds) else {
+        return format!("{} s", seconds);
+    };
+    if nanos == 0 {
+        return "0s".to_string();
+    }
+    let sign = if nanos < 0 { "-" } else { "" };
+    let (whole, fraction) = (
+        nanos.abs() / NANOS_PER_SECOND,
+        nanos.abs() % NANOS_PER_SECOND,
+    );
+    let mut parts = Vec::new();
+    for (amount, suffix) in [
+        (whole / SECONDS_PER_DAY, "d"),
+        (whole / 3600 % 24, "h"),
+        (whole / 60 % 60, "m"),
+    ] {
+        if amount != 0 {
+            parts.push(format!("{}{}", amount, suffix));
+        }
+    }
+    if whole % 60 != 0 || fraction != 0 {
+        parts.push(format!("{}{}s", whole % 60, fraction_text(fraction)));
+    }
+    format!("{}{}", sign, parts.join(" "))
+}
+
+/// A duration written so it reads back as one. A single group such as
+/// `45d` would read as a product, so it gets a zero group: `45d 0s`.
+pub(crate) fn duration_source(seconds: &Number) -> String {
+    let text = format_duration(seconds);
+    match text.trim_start_matches('-').split(' ').count() {
+        1 if text.ends_with("ms") || !text.ends_with('s') => format!("{} 0s", text),
+        1 => format!("{} 0ms", text),
+        _ => text,
+    }
+}
+
+fn second() -> Unit {
+    Unit::lookup("s").expect("seconds are in the unit table")
+}
+
+/// Durations take part in arithmetic and functions as quantities of seconds
+pub(crate) fn duration_operand(value: &Value) -> Value {
+    match value {
+        Value::Duration(seconds) => Value::Quantity(seconds.clone(), second()),
+        other => other.clone(),
+    }
+}
+
+/// Turn a result back into a duration when it is a length of time
+pub(crate) fn duration_result(backend: &dyn Backend, value: Value) -> Result<Value, CalcError> {
+    match &value {
+        Value::Quantity(number, unit) if unit.dimension() == second().dimension() => {
+            Ok(Value::Duration(rescale(backend, number, unit, &second())?))
+        }
+        _ => Ok(value),
+    }
+}
+
+/// How far to move a date for `date + shift`; only lengths of time count
+fn shift_nanos(backend: &dyn Backend, shift: &Value) -> Result<i128, CalcError> {
+    let quantity = duration_operand(shift);
+    match &quantity {
+        Value::Quantity(number, unit) if unit.dimension() == second().dimension() => {
+            to_nanos(&rescale(backend, number, unit, &second())?)
+        }
+        _ => Err(CalcError::Dimension(format!(
+            "Only a length of time can move a date, found {}; try e.g. '+ 3 days'",
+            shift
+        ))),
+    }
+}
+
+/// Arithmetic with a date or a duration on either side. A date moves by a
+/// length of time and two dates subtract to the duration between them;
+/// durations otherwise act as quantities of seconds.
+pub(crate) fn temporal_binary(
+    backend: &dyn Backend,
+    a: &Value,
+    op: char,
+    b: &Value,
+) -> Result<Value, CalcError> {
+    match (a, op, b) {
+        (Value::Date(later), '-', Value::Date(earlier)) => {
+            let one = |n: i128| Rational::from_bigint(BigInt::from_i128(n));
+            let seconds = one(later.nanos - earlier.nanos).div(&one(NANOS_PER_SECOND));
+            Ok(Value::Duration(
+                backend.convert(&Number::Rational(seconds))?,
+            ))
+        }
+        (Value::Date(_), '+', Value::Date(_)) => Err(CalcError::Domain(
+            "Cannot add two dates; subtract them to get the time between".to_string(),
+        )),
+        (Value::Date(date), '+', shift) | (shift, '+', Value::Date(date)) => {
+            Ok(Value::Date(date.shifted(shift_nanos(backend, shift)?)?))
+        }
+        (Value::Date(date), '-', shift) if !matches!(shift, Value::Date(_)) => {
+            Ok(Value::Date(date.shifted(-shift_nanos(backend, shift)?)?))
+        }
+        (Value::Date(_), _, _) | (_, _, Value::Date(_)) => Err(CalcError::Domain(format!(
+            "Cannot apply '{}' to a date",
+            operator_symbol(op)
+        ))),
+        _ => {
+            let result = binary_values(backend, &duration_operand(a), op, &duration_operand(b))?;
+            duration_result(backend, result)
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Errors reported while parsing and evaluating a line
+
+use std::fmt;
+
+use crate::eval::MAX_CALL_DEPTH;
+
+/// Error raised while tokenizing or parsing, pointing at the offending column
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+/// Everything that can go wrong while running a line. Evaluation never
+/// panics and never hands back `inf` or `NaN`; it returns one of these.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcError {
+    /// The line is not a valid expression
+    Parse(ParseError),
+    DivisionByZero,
+    /// An operand outside what the operation accepts, e.g. `sqrt(-1)`
+    Domain(String),
+    /// A result too large to represent in the current mode
+    Overflow(String),
+    /// Units that do not fit together, e.g. adding metres to seconds
+    Dimension(String),
+    /// Matrices whose sizes do not fit the operation, e.g. `det` of a 2x3 matrix
+    Shape(String),
+    UnknownVariable(String),
+    UnknownFunction(String),
+    /// A function called with the wrong number of arguments; `expected` is
+    /// `None` for functions such as `min` that take one or more
+    Arity {
+        function: String,
+        expected: Option<usize>,
+        found: usize,
+    },
+    /// `ans` or `$n` used before that result exists
+    MissingResult(String),
+    /// An attempt to redefine a built-in name such as `pi`
+    Reserved(String),
+    /// User functions calling each other more than `MAX_CALL_DEPTH` deep
+    RecursionLimit(String),
+    /// `solve()` proved there is no solution or could not find one
+    NoSolution(String),
+    /// `solve()` on equations that leave these unknowns free
+    Underdetermined(Vec<String>),
+    /// `solve()` found more roots of one unknown than it lists, as a
+    /// periodic function such as `sin(x) = 0` has
+    TooManyRoots(String),
+}
+
+impl CalcError {
+    /// Stable, machine-readable name of the error, used in JSON and CSV output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CalcError::Parse(_) => "parse",
+            CalcError::DivisionByZero => "division_by_zero",
+            CalcError::Domain(_) => "domain",
+            CalcError::Overflow(_) => "overflow",
+            CalcError::Dimension(_) => "dimension",
+            CalcError::Shape(_) => "shape",
+            CalcError::UnknownVariable(_) => "unknown_variable",
+            CalcError::UnknownFunction(_) => "unknown_function",
+            CalcError::Arity { .. } => "arity",
+            CalcError::MissingResult(_) => "missing_result",
+            CalcError::Reserved(_) => "reserved",
+            CalcError::RecursionLimit(_) => "recursion_limit",
+            CalcError::NoSolution(_) => "no_solution",
+            CalcError::Underdetermined(_) => "underdetermined",
+            CalcError::TooManyRoots(_) => "too_many_roots",
+        }
+    }
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalcError::Parse(e) => write!(f, "Syntax error at column {}: {}", e.column, e.message),
+            CalcError::DivisionByZero => write!(f, "Division by zero is not allowed"),
+            CalcError::Domain(message) => write!(f, "{}", message),
+            CalcError::Overflow(message) => write!(f, "{}", message),
+            CalcError::Dimension(message) => write!(f, "{}", message),
+            CalcError::Shape(message) => write!(f, "{}", message),
+            CalcError::UnknownVariable(name) => write!(f, "Unknown variable '{}'", name),
+            CalcError::UnknownFunction(name) => write!(f, "Unknown function '{}'", name),
+            CalcError::Arity {
+                function,
+                expected: Some(expected),
+                found,
+            } => write!(
+                f,
+                "{}() takes {} argument{}, found {}",
+                function,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found
+            ),
+            CalcError::Arity { function, .. } => {
+                write!(f, "{}() needs at least one argument", function)
+            }
+            CalcError::MissingResult(reference) => write!(f, "No result for '{}' yet", reference),
+            CalcError::Reserved(name) => {
+                write!(f, "'{}' is built in and cannot be redefined", name)
+            }
+            CalcError::RecursionLimit(name) => write!(
+                f,
+                "{}() recursed more than {} levels deep",
+                name, MAX_CALL_DEPTH
+            ),
+            CalcError::NoSolution(reason) => write!(f, "No solution: {}", reason),
+            CalcError::Underdetermined(free) => write!(
+                f,
+                "The equations are underdetermined: {} can take many values",
+                free.join(", ")
+            ),
+            CalcError::TooManyRoots(reason) => write!(f, "Too many roots: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CalcError {}
+
+impl From<ParseError> for CalcError {
+    fn from(e: ParseError) -> CalcError {
+        CalcError::Parse(e)
+    }
+}
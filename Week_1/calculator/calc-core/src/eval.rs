@@ -0,0 +1,813 @@
+//! Session state and evaluation of parsed lines
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::backend::{Backend, FloatBackend};
+use crate::datetime::{
+    clock, duration_operand, duration_result, duration_seconds, in_zone, parse_datetime,
+    temporal_binary, zone_offset, CLOCK_NAMES,
+};
+use crate::error::{CalcError, ParseError};
+use crate::finance::{call_finance, FINANCE_FUNCTIONS};
+use crate::matrix::{call_matrix, matrix_binary, Matrix, MATRIX_FUNCTIONS};
+use crate::number::{BigInt, Number};
+use crate::parser::{operator_symbol, parse, radix_literal, Expr, Statement};
+use crate::plot::plot;
+use crate::solve::solve;
+use crate::stats::{call_stat, STAT_FUNCTIONS};
+use crate::symbolic::{differentiate, simplify};
+use crate::units::{
+    add_values, convert_to, describe_dimension, mul_values, pow_values, rescale, Unit, Value,
+};
+
+/// Built-in constants, looked up after the session's own variables
+pub(crate) const CONSTANTS: &[(&str, f64)] =
+    &[("pi", std::f64::consts::PI), ("e", std::f64::consts::E)];
+
+/// Built-in functions with the number of arguments they take (`None` for any number)
+const FUNCTIONS: &[(&str, Option<usize>)] = &[
+    ("sqrt", Some(1)),
+    ("pow", Some(2)),
+    ("abs", Some(1)),
+    ("floor", Some(1)),
+    ("ceil", Some(1)),
+    ("round", Some(1)),
+    ("min", None),
+    ("max", None),
+    ("ln", Some(1)),
+    ("log10", Some(1)),
+    ("sin", Some(1)),
+    ("cos", Some(1)),
+    ("tan", Some(1)),
+    ("asin", Some(1)),
+    ("acos", Some(1)),
+    ("atan", Some(1)),
+    ("factorial", Some(1)),
+    ("diff", Some(2)),
+    ("simplify", Some(1)),
+    ("solve", None),
+    ("det", Some(1)),
+    ("inv", Some(1)),
+    ("transpose", Some(1)),
+    ("dot", Some(2)),
+    ("sum", None),
+    ("product", None),
+    ("mean", None),
+    ("median", None),
+    ("mode", None),
+    ("variance", None),
+    ("stdev", None),
+    ("percentile", Some(2)),
+    ("npv", None),
+    ("irr", None),
+    ("pmt", None),
+    ("fv", None),
+    ("pv", None),
+    ("compound", Some(3)),
+    ("effective", Some(2)),
+    ("amortize", Some(3)),
+    ("plot", None),
+];
+
+/// Deepest chain of user-function calls before we assume runaway recursion.
+/// Kept low enough to stay within the 2 MiB stack of a spawned thread, since
+/// embedders may not evaluate on the main thread.
+pub(crate) const MAX_CALL_DEPTH: usize = 100;
+
+/// How close `solve` gets to a root before it stops, unless the session
+/// sets its own tolerance
+pub const DEFAULT_TOLERANCE: f64 = 1e-12;
+
+/// Largest argument accepted by `factorial`
+const MAX_FACTORIAL: u64 = 5_000;
+
+/// Whether the trig functions read and return degrees or radians
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AngleUnit {
+    Radians,
+    Degrees,
+}
+
+impl fmt::Display for AngleUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AngleUnit::Radians => write!(f, "radians"),
+            AngleUnit::Degrees => write!(f, "degrees"),
+        }
+    }
+}
+
+/// A function defined in the session, e.g. `f(x) = x^2 + 1`
+#[derive(Debug, Clone)]
+pub struct UserFunction {
+    params: Vec<String>,
+    body: Expr,
+}
+
+impl fmt::Display for UserFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}) = {}", self.params.join(", "), self.body)
+    }
+}
+
+/// Session state kept between lines of the REPL
+pub struct Env {
+    pub variables: BTreeMap<String, Value>,
+    pub functions: BTreeMap<String, UserFunction>,
+    pub history: Vec<Value>,
+    pub backend: Box<dyn Backend>,
+    pub angle: AngleUnit,
+    /// Accuracy `solve` aims for when it has to search for roots numerically
+    pub tolerance: f64,
+}
+
+impl Env {
+    pub fn new(backend: Box<dyn Backend>) -> Env {
+        Env {
+            variables: BTreeMap::new(),
+            functions: BTreeMap::new(),
+            history: Vec::new(),
+            backend,
+            angle: AngleUnit::Radians,
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// The most recent result, available as `ans`
+    pub fn ans(&self) -> Option<&Value> {
+        self.history.last()
+    }
+
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.functions.clear();
+        self.history.clear();
+    }
+}
+
+/// Names that cannot be assigned to or redefined. Unit names are among
+/// them, since a variable called `s` would quietly change what `10 m / s`
+/// means.
+pub(crate) fn is_reserved(name: &str) -> bool {
+    matches!(name, "ans" | "in" | "to" | "xor")
+        || CLOCK_NAMES.contains(&name)
+        || Unit::is_unit_name(name)
+        || CONSTANTS.iter().any(|(constant, _)| *constant == name)
+        || FUNCTIONS.iter().any(|(function, _)| *function == name)
+}
+
+/// Parameters bound while evaluating the body of a user function
+pub(crate) struct Scope<'a> {
+    pub(crate) locals: BTreeMap<&'a str, Value>,
+    pub(crate) depth: usize,
+}
+
+/// Walk the tree and compute its value with the session's backend
+pub(crate) fn evaluate_expr(expr: &Expr, env: &Env) -> Result<Value, CalcError> {
+    let scope = Scope {
+        locals: BTreeMap::new(),
+        depth: 0,
+    };
+    evaluate_in(expr, env, &scope)
+}
+
+pub(crate) fn evaluate_in(expr: &Expr, env: &Env, scope: &Scope) -> Result<Value, CalcError> {
+    let backend = env.backend.as_ref();
+    match expr {
+        Expr::Number(text) => match radix_literal(text) {
+            Some(pattern) => Ok(Value::Number(backend.radix_literal(pattern)?)),
+            None => Ok(Value::Number(backend.literal(text)?)),
+        },
+        Expr::Date(text) => parse_datetime(text)
+            .map(Value::Date)
+            .map_err(CalcError::Domain),
+        Expr::Duration(text) => Ok(Value::Duration(duration_seconds(backend, text)?)),
+        Expr::Variable(name) => {
+            if let Some(value) = scope.locals.get(name.as_str()) {
+                return Ok(value.clone());
+            }
+            if name == "ans" {
+                return match env.ans() {
+                    Some(value) => value.convert(backend),
+                    None => Err(CalcError::MissingResult("ans".to_string())),
+                };
+            }
+            if let Some(value) = env.variables.get(name) {
+                return value.convert(backend);
+            }
+            if let Some((_, value)) = CONSTANTS.iter().find(|(constant, _)| constant == name) {
+                return Ok(Value::Number(backend.convert(&Number::Float(*value))?));
+            }
+            if let Some(value) = clock(name) {
+                return Ok(value);
+            }
+            // A unit on its own stands for one of that unit, so `m/s` works
+            match Unit::lookup(name) {
+                Some(unit) => Ok(Value::Quantity(backend.literal("1")?, unit)),
+                None => Err(CalcError::UnknownVariable(name.clone())),
+            }
+        }
+        Expr::History(index) => match index.checked_sub(1).and_then(|i| env.history.get(i)) {
+            Some(value) => value.convert(backend),
+            None => Err(CalcError::MissingResult(format!("${}", index))),
+        },
+        Expr::Negate(inner) => {
+            // Read `-128` as one literal so it fits a checked `i8`
+            if let Expr::Number(text) = inner.as_ref() {
+                if radix_literal(text).is_none() {
+                    return Ok(Value::Number(backend.literal(&format!("-{}", text))?));
+                }
+            }
+            let value = evaluate_in(inner, env, scope)?.operand()?;
+            if value.is_symbolic() {
+                return symbolic_result(Expr::Negate(Box::new(value.to_expr()?)));
+            }
+            match &value {
+                Value::Matrix(matrix) => return Ok(Value::Matrix(matrix.map(|n| backend.neg(n))?)),
+                Value::Duration(seconds) => return Ok(Value::Duration(backend.neg(seconds)?)),
+                Value::Date(date) => {
+                    return Err(CalcError::Domain(format!(
+                        "Cannot negate the date {}",
+                        date
+                    )))
+                }
+                _ => {}
+            }
+            Ok(Value::from_parts(
+                backend.neg(value.number())?,
+                value.unit(),
+            ))
+        }
+        Expr::BitNot(inner) => {
+            let value = evaluate_in(inner, env, scope)?.operand()?;
+            if value.is_symbolic() {
+                return symbolic_result(Expr::BitNot(Box::new(value.to_expr()?)));
+            }
+            Ok(Value::Number(backend.bit_not(value.plain("'~'")?)?))
+        }
+        Expr::Factorial(inner) => {
+            let value = evaluate_in(inner, env, scope)?.operand()?;
+            if value.is_symbolic() {
+                return symbolic_result(Expr::Factorial(Box::new(value.to_expr()?)));
+            }
+            Ok(Value::Number(factorial(value.plain("factorial")?, env)?))
+        }
+        Expr::Binary(_, '=', _) => Err(CalcError::Domain(
+            "'=' is only allowed in the equations given to solve()".to_string(),
+        )),
+        Expr::Binary(left, op, right) => {
+            let value_1 = evaluate_in(left, env, scope)?.operand()?;
+            let value_2 = evaluate_in(right, env, scope)?.operand()?;
+            if value_1.is_symbolic() || value_2.is_symbolic() {
+                return symbolic_result(Expr::Binary(
+                    Box::new(value_1.to_expr()?),
+                    *op,
+                    Box::new(value_2.to_expr()?),
+                ));
+            }
+            binary_values(backend, &value_1, *op, &value_2)
+        }
+        Expr::Convert(value, unit) => {
+            let value = evaluate_in(value, env, scope)?.operand()?;
+            if value.is_symbolic() {
+                return symbolic_result(Expr::Convert(Box::new(value.to_expr()?), unit.clone()));
+            }
+            if let Value::Matrix(matrix) = &value {
+                return Err(CalcError::Domain(format!(
+                    "Cannot convert the matrix {} to a unit",
+                    matrix
+                )));
+            }
+            if let Value::Date(date) = &value {
+                // `in UTC+2` shows the same moment at another offset
+                return match zone_offset(unit) {
+                    Some(offset) => Ok(Value::Date(in_zone(date, offset))),
+                    None => Err(CalcError::Domain(format!(
+                        "A date converts to a UTC offset such as 'in UTC+2', found {}",
+                        unit
+                    ))),
+                };
+            }
+            convert_to(backend, &value, &evaluate_in(unit, env, scope)?)
+        }
+        Expr::Call(name, args) if name == "diff" || name == "simplify" => {
+            let result = symbolic_call(name, args, env, scope, scope.depth)?;
+            // Inside a user function the free variables are its parameters,
+            // so `slope(x) = diff(x^3, x)` gives a number
+            if scope.depth > 0 || !has_variables(&result) {
+                evaluate_in(&result, env, scope)
+            } else {
+                Ok(Value::Symbolic(result))
+            }
+        }
+        Expr::Call(name, args) if name == "solve" => solve(args, env, scope),
+        Expr::Call(name, args) if name == "plot" => plot(args, env, scope),
+        Expr::Matrix(rows) => {
+            let values = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|entry| evaluate_in(entry, env, scope)?.operand())
+                        .collect::<Result<Vec<Value>, CalcError>>()
+                })
+                .collect::<Result<Vec<_>, CalcError>>()?;
+            if values.iter().flatten().any(Value::is_symbolic) {
+                let rows = values
+                    .iter()
+                    .map(|row| row.iter().map(Value::to_expr).collect())
+                    .collect::<Result<_, CalcError>>()?;
+                return symbolic_result(Expr::Matrix(rows));
+            }
+            let rows = values
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|value| match value {
+                            Value::Number(n) => Ok(n),
+                            other => Err(CalcError::Domain(format!(
+                                "Matrix entries must be plain numbers, found {}",
+                                other
+                            ))),
+                        })
+                        .collect()
+                })
+                .collect::<Result<_, CalcError>>()?;
+            Ok(Value::Matrix(Matrix::from_rows(rows)))
+        }
+        Expr::Call(name, args) => {
+            let values = args
+                .iter()
+                .map(|arg| evaluate_in(arg, env, scope)?.operand())
+                .collect::<Result<Vec<Value>, CalcError>>()?;
+            if let Some((_, arity)) = FUNCTIONS.iter().find(|(function, _)| function == name) {
+                check_arity(name, *arity, values.len())?;
+                if values.iter().any(Value::is_symbolic) {
+                    let args = values
+                        .iter()
+                        .map(Value::to_expr)
+                        .collect::<Result<Vec<Expr>, CalcError>>()?;
+                    return symbolic_result(Expr::Call(name.clone(), args));
+                }
+                return call_builtin(name, &values, env);
+            }
+            match env.functions.get(name) {
+                Some(function) => call_user_function(name, function, values, env, scope),
+                None => Err(CalcError::UnknownFunction(name.clone())),
+            }
+        }
+    }
+}
+
+/// Arithmetic on two operands that are not symbolic
+pub(crate) fn binary_values(
+    backend: &dyn Backend,
+    a: &Value,
+    op: char,
+    b: &Value,
+) -> Result<Value, CalcError> {
+    if [a, b]
+        .iter()
+        .any(|value| matches!(value, Value::Date(_) | Value::Duration(_)))
+    {
+        return temporal_binary(backend, a, op, b);
+    }
+    if matches!(a, Value::Matrix(_)) || matches!(b, Value::Matrix(_)) {
+        return matrix_binary(backend, a, op, b);
+    }
+    match op {
+        '+' | '-' | '%' => add_values(backend, a, op, b),
+        '*' | '/' => mul_values(backend, a, op, b),
+        // On plain numbers the element-wise operators are the usual ones
+        'o' => mul_values(backend, a, '*', b),
+        'd' => mul_values(backend, a, '/', b),
+        '^' => pow_values(backend, a, b),
+        _ => {
+            let name = format!("'{}'", operator_symbol(op));
+            let result = backend.bitwise(a.plain(&name)?, op, b.plain(&name)?)?;
+            Ok(Value::Number(result))
+        }
+    }
+}
+
+/// A node with a symbolic operand stays symbolic, so `diff(x^2, x) + 1`
+/// is `2 * x + 1`
+fn symbolic_result(expr: Expr) -> Result<Value, CalcError> {
+    Ok(Value::Symbolic(simplify(&expr)))
+}
+
+fn has_variables(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::Date(_) | Expr::Duration(_) | Expr::History(_) => false,
+        Expr::Variable(_) => true,
+        Expr::Negate(inner) | Expr::BitNot(inner) | Expr::Factorial(inner) => has_variables(inner),
+        Expr::Binary(left, _, right) | Expr::Convert(left, right) => {
+            has_variables(left) || has_variables(right)
+        }
+        Expr::Call(_, args) => args.iter().any(has_variables),
+        Expr::Matrix(rows) => rows.iter().flatten().any(has_variables),
+    }
+}
+
+/// `diff(expr, x)` or `simplify(expr)`, worked on the expression itself
+/// rather than its value
+fn symbolic_call(
+    name: &str,
+    args: &[Expr],
+    env: &Env,
+    scope: &Scope,
+    depth: usize,
+) -> Result<Expr, CalcError> {
+    let arity = if name == "diff" { 2 } else { 1 };
+    check_arity(name, Some(arity), args.len())?;
+    let target = expand(&args[0], env, scope, depth)?;
+    if name == "simplify" {
+        return Ok(simplify(&target));
+    }
+    let Expr::Variable(variable) = &args[1] else {
+        return Err(CalcError::Domain(format!(
+            "diff() needs a variable name as its second argument, found {}",
+            args[1]
+        )));
+    };
+    Ok(simplify(&differentiate(&target, variable, env.angle)?))
+}
+
+/// Prepare an argument of `diff` or `simplify`: earlier results and names
+/// bound to symbolic values are written out, calls to user functions are
+/// inlined, and every other name is left as a symbol
+pub(crate) fn expand(
+    expr: &Expr,
+    env: &Env,
+    scope: &Scope,
+    depth: usize,
+) -> Result<Expr, CalcError> {
+    let each = |exprs: &[Expr]| {
+        exprs
+            .iter()
+            .map(|e| expand(e, env, scope, depth))
+            .collect::<Result<Vec<Expr>, CalcError>>()
+    };
+    let boxed = |e: &Expr| expand(e, env, scope, depth).map(Box::new);
+    Ok(match expr {
+        Expr::Number(_) | Expr::Date(_) | Expr::Duration(_) => expr.clone(),
+        Expr::Variable(name) => {
+            let bound = match scope.locals.get(name.as_str()) {
+                Some(value) => Some(value),
+                None if name == "ans" => Some(
+                    env.ans()
+                        .ok_or_else(|| CalcError::MissingResult("ans".to_string()))?,
+                ),
+                None => env.variables.get(name),
+            };
+            match bound {
+                Some(value @ Value::Symbolic(_)) => value.to_expr()?,
+                Some(value) if name == "ans" => value.to_expr()?,
+                _ => expr.clone(),
+            }
+        }
+        Expr::History(index) => match index.checked_sub(1).and_then(|i| env.history.get(i)) {
+            Some(value) => value.to_expr()?,
+            None => return Err(CalcError::MissingResult(format!("${}", index))),
+        },
+        Expr::Negate(inner) => Expr::Negate(boxed(inner)?),
+        Expr::BitNot(inner) => Expr::BitNot(boxed(inner)?),
+        Expr::Factorial(inner) => Expr::Factorial(boxed(inner)?),
+        Expr::Binary(left, op, right) => Expr::Binary(boxed(left)?, *op, boxed(right)?),
+        Expr::Convert(value, unit) => Expr::Convert(boxed(value)?, unit.clone()),
+        Expr::Matrix(rows) => Expr::Matrix(
+            rows.iter()
+                .map(|row| each(row))
+                .collect::<Result<_, CalcError>>()?,
+        ),
+        Expr::Call(name, args) if name == "diff" || name == "simplify" => {
+            symbolic_call(name, args, env, scope, depth)?
+        }
+        Expr::Call(name, args) => match env.functions.get(name) {
+            Some(function) if !FUNCTIONS.iter().any(|(builtin, _)| builtin == name) => {
+                check_arity(name, Some(function.params.len()), args.len())?;
+                if depth >= MAX_CALL_DEPTH {
+                    return Err(CalcError::RecursionLimit(name.to_string()));
+                }
+                let body = substitute(&function.body, &function.params, &each(args)?);
+                expand(&body, env, scope, depth + 1)?
+            }
+            _ => Expr::Call(name.clone(), each(args)?),
+        },
+    })
+}
+
+/// Replace each parameter in a function body with its argument
+pub(crate) fn substitute(expr: &Expr, params: &[String], args: &[Expr]) -> Expr {
+    let sub = |e: &Expr| Box::new(substitute(e, params, args));
+    match expr {
+        Expr::Variable(name) => match params.iter().position(|param| param == name) {
+            Some(i) => args[i].clone(),
+            None => expr.clone(),
+        },
+        Expr::Number(_) | Expr::Date(_) | Expr::Duration(_) | Expr::History(_) => expr.clone(),
+        Expr::Negate(inner) => Expr::Negate(sub(inner)),
+        Expr::BitNot(inner) => Expr::BitNot(sub(inner)),
+        Expr::Factorial(inner) => Expr::Factorial(sub(inner)),
+        Expr::Binary(left, op, right) => Expr::Binary(sub(left), *op, sub(right)),
+        Expr::Convert(value, unit) => Expr::Convert(sub(value), unit.clone()),
+        Expr::Matrix(rows) => Expr::Matrix(
+            rows.iter()
+                .map(|row| row.iter().map(|e| substitute(e, params, args)).collect())
+                .collect(),
+        ),
+        Expr::Call(name, call_args) => Expr::Call(
+            name.clone(),
+            call_args
+                .iter()
+                .map(|arg| substitute(arg, params, args))
+                .collect(),
+        ),
+    }
+}
+
+pub(crate) fn check_arity(
+    name: &str,
+    expected: Option<usize>,
+    found: usize,
+) -> Result<(), CalcError> {
+    let matches = match expected {
+        Some(expected) => expected == found,
+        None => found > 0,
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(CalcError::Arity {
+            function: name.to_string(),
+            expected,
+            found,
+        })
+    }
+}
+
+fn call_user_function(
+    name: &str,
+    function: &UserFunction,
+    values: Vec<Value>,
+    env: &Env,
+    scope: &Scope,
+) -> Result<Value, CalcError> {
+    check_arity(name, Some(function.params.len()), values.len())?;
+    if scope.depth >= MAX_CALL_DEPTH {
+        return Err(CalcError::RecursionLimit(name.to_string()));
+    }
+    let inner = Scope {
+        locals: function
+            .params
+            .iter()
+            .map(String::as_str)
+            .zip(values)
+            .collect(),
+        depth: scope.depth + 1,
+    };
+    evaluate_in(&function.body, env, &inner)
+}
+
+/// Built-ins that understand units; everything else wants plain numbers
+fn call_builtin(name: &str, args: &[Value], env: &Env) -> Result<Value, CalcError> {
+    let backend = env.backend.as_ref();
+    if MATRIX_FUNCTIONS.contains(&name) {
+        return call_matrix(name, args, backend);
+    }
+    if let Some(Value::Date(date)) = args.iter().find(|arg| matches!(arg, Value::Date(_))) {
+        return Err(CalcError::Domain(format!(
+            "{}() does not take dates, found {}",
+            name, date
+        )));
+    }
+    if args.iter().any(|arg| matches!(arg, Value::Duration(_))) {
+        // `max(3h 20m, 200 min)` works on the lengths in seconds
+        let args: Vec<Value> = args.iter().map(duration_operand).collect();
+        return duration_result(backend, call_builtin(name, &args, env)?);
+    }
+    if STAT_FUNCTIONS.contains(&name) {
+        return call_stat(name, args, backend);
+    }
+    if FINANCE_FUNCTIONS.contains(&name) {
+        return call_finance(name, args, backend, env.tolerance);
+    }
+    if let Some(Value::Matrix(_)) = args.iter().find(|arg| matches!(arg, Value::Matrix(_))) {
+        return match args {
+            _ if name == "pow" => matrix_binary(backend, &args[0], '^', &args[1]),
+            // `max([3, 9, 4])` picks from the entries of a list
+            [Value::Matrix(matrix)] if matches!(name, "min" | "max") => {
+                let entries: Vec<Value> = matrix
+                    .row_list()
+                    .into_iter()
+                    .flatten()
+                    .map(|n| Value::Number(n.clone()))
+                    .collect();
+                call_builtin(name, &entries, env)
+            }
+            // One-argument functions apply to every entry, so `abs(A)` works
+            [Value::Matrix(matrix)] => Ok(Value::Matrix(matrix.map(|n| {
+                call_builtin(name, &[Value::Number(n.clone())], env)?
+                    .plain(name)
+                    .cloned()
+            })?)),
+            _ => Err(CalcError::Shape(format!(
+                "{}() works on numbers, not matrices",
+                name
+            ))),
+        };
+    }
+    let x = &args[0];
+    match name {
+        "pow" => pow_values(backend, x, &args[1]),
+        "abs" => Ok(Value::from_parts(x.number().abs(), x.unit())),
+        "floor" => Ok(Value::from_parts(x.number().floor(), x.unit())),
+        "ceil" => Ok(Value::from_parts(x.number().ceil(), x.unit())),
+        "round" => Ok(Value::from_parts(x.number().round(), x.unit())),
+        "min" => extreme(backend, args, Ordering::Less),
+        "max" => extreme(backend, args, Ordering::Greater),
+        "sqrt" => {
+            let unit = x.unit().sqrt().ok_or_else(|| {
+                CalcError::Dimension(format!("Cannot take the square root of {}", x.unit()))
+            })?;
+            let root = call_numeric(name, x.number(), env)?;
+            Ok(Value::from_parts(root, unit))
+        }
+        _ => Ok(Value::Number(call_numeric(name, x.plain(name)?, env)?)),
+    }
+}
+
+/// One-argument functions on plain numbers
+fn call_numeric(name: &str, x: &Number, env: &Env) -> Result<Number, CalcError> {
+    match name {
+        "factorial" => factorial(x, env),
+        "sqrt" if x.to_f64() < 0.0 => Err(undefined_for(name, x)),
+        "ln" | "log10" if x.to_f64() <= 0.0 => Err(undefined_for(name, x)),
+        "asin" | "acos" if x.to_f64().abs() > 1.0 => Err(undefined_for(name, x)),
+        _ => {
+            // Everything else is transcendental, so it is computed in f64
+            // and brought back into the current mode
+            let value = x.to_f64();
+            let to_radians = |v: f64| match env.angle {
+                AngleUnit::Radians => v,
+                AngleUnit::Degrees => v.to_radians(),
+            };
+            let from_radians = |v: f64| match env.angle {
+                AngleUnit::Radians => v,
+                AngleUnit::Degrees => v.to_degrees(),
+            };
+            let result = match name {
+                "sqrt" => value.sqrt(),
+                "ln" => value.ln(),
+                "log10" => value.log10(),
+                "sin" => to_radians(value).sin(),
+                "cos" => to_radians(value).cos(),
+                "tan" => to_radians(value).tan(),
+                "asin" => from_radians(value.asin()),
+                "acos" => from_radians(value.acos()),
+                "atan" => from_radians(value.atan()),
+                _ => return Err(CalcError::UnknownFunction(name.to_string())),
+            };
+            env.backend.convert(&FloatBackend::finite(result, name)?)
+        }
+    }
+}
+
+fn undefined_for(name: &str, x: &Number) -> CalcError {
+    CalcError::Domain(format!("{} is undefined for {}", name, x))
+}
+
+/// The smallest (`Ordering::Less`) or largest (`Ordering::Greater`)
+/// argument. Quantities are compared in the first argument's unit.
+fn extreme(backend: &dyn Backend, args: &[Value], wanted: Ordering) -> Result<Value, CalcError> {
+    let unit = args[0].unit();
+    let mut best = &args[0];
+    let mut best_number = args[0].number().clone();
+    for candidate in &args[1..] {
+        if candidate.unit().dimension() != unit.dimension() {
+            return Err(CalcError::Dimension(format!(
+                "Cannot compare {} with {}",
+                describe_dimension(&candidate.unit().dimension()),
+                describe_dimension(&unit.dimension())
+            )));
+        }
+        let number = rescale(backend, candidate.number(), &candidate.unit(), &unit)?;
+        if number.compare(&best_number) == wanted {
+            best = candidate;
+            best_number = number;
+        }
+    }
+    Ok(best.clone())
+}
+
+/// `n!` for a whole, non-negative `n`, computed exactly and then brought
+/// into the current mode
+fn factorial(n: &Number, env: &Env) -> Result<Number, CalcError> {
+    let whole = n.to_integer()?;
+    if whole.is_negative() {
+        return Err(undefined_for("factorial", n));
+    }
+    let limit = whole.to_string().parse::<u64>().unwrap_or(u64::MAX);
+    if limit > MAX_FACTORIAL {
+        return Err(CalcError::Overflow(format!(
+            "factorial is limited to {}!",
+            MAX_FACTORIAL
+        )));
+    }
+
+    let mut result = BigInt::from_i64(1);
+    for i in 2..=limit {
+        result = result.mul(&BigInt::from_i64(i as i64));
+    }
+    env.backend.convert(&Number::Integer(result))
+}
+
+/// What a successfully executed line produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// A value, saved as result `$index` and, for `let`, under `name`
+    Value {
+        index: usize,
+        name: Option<String>,
+        value: Value,
+    },
+    /// A user function definition, printed back in normalised form
+    Defined(String),
+}
+
+impl Outcome {
+    /// Just the result, without the `$n =` prefix used in the REPL
+    pub fn value_text(&self) -> String {
+        match self {
+            Outcome::Value { value, .. } => value.to_string(),
+            Outcome::Defined(definition) => definition.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Value {
+                index,
+                name: Some(name),
+                value,
+            } => write!(f, "${} = {} = {}", index, name, value),
+            Outcome::Value { index, value, .. } => write!(f, "${} = {}", index, value),
+            Outcome::Defined(definition) => write!(f, "{}", definition),
+        }
+    }
+}
+
+/// Run one line of input against the session: an expression, a `let`
+/// assignment or a function definition
+pub fn execute(line: &str, env: &mut Env) -> Result<Outcome, CalcError> {
+    let statement = parse(line, env.backend.caret_is_xor())?;
+    run(statement, env)
+}
+
+/// Evaluate an expression or `let` assignment and return its value. The
+/// result goes into the history like any other line, so later calls can use
+/// `ans` and `$n`. Function definitions have no value; pass them to
+/// `execute` instead.
+pub fn evaluate(input: &str, env: &mut Env) -> Result<Value, CalcError> {
+    let statement = parse(input, env.backend.caret_is_xor())?;
+    if let Statement::Function(..) = statement {
+        return Err(CalcError::Parse(ParseError {
+            column: 1,
+            message: "expected an expression, found a function definition".to_string(),
+        }));
+    }
+    match run(statement, env)? {
+        Outcome::Value { value, .. } => Ok(value),
+        Outcome::Defined(_) => unreachable!("definitions are rejected above"),
+    }
+}
+
+fn run(statement: Statement, env: &mut Env) -> Result<Outcome, CalcError> {
+    let (name, expr) = match statement {
+        Statement::Let(name, _) | Statement::Function(name, _, _) if is_reserved(&name) => {
+            return Err(CalcError::Reserved(name));
+        }
+        Statement::Function(name, params, body) => {
+            let function = UserFunction { params, body };
+            let definition = format!("{}{}", name, function);
+            env.functions.insert(name, function);
+            return Ok(Outcome::Defined(definition));
+        }
+        Statement::Let(name, expr) => (Some(name), expr),
+        Statement::Expr(expr) => (None, expr),
+    };
+
+    let value = evaluate_expr(&expr, env)?;
+    if let Some(name) = &name {
+        env.variables.insert(name.clone(), value.clone());
+    }
+    env.history.push(value.clone());
+    Ok(Outcome::Value {
+        index: env.history.len(),
+        name,
+        value,
+    })
+}
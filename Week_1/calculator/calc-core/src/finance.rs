@@ -0,0 +1,328 @@
+//! Time-value-of-money functions. Rates are per period, as in a
+//! spreadsheet: a 6% yearly loan paid monthly has a rate of `0.06 / 12`.
+//! Signs follow the spreadsheet convention too, so money paid out is
+//! negative and `pmt` of a positive loan is a negative payment.
+//!
+//! Money must not pick up binary rounding, so the arithmetic always runs in
+//! decimal mode and the result is brought back into the session's mode
+//! afterwards.
+
+use crate::backend::{Backend, DecimalBackend, DEFAULT_SCALE};
+use crate::error::CalcError;
+use crate::matrix::Matrix;
+use crate::number::Number;
+use crate::stats::sample;
+use crate::units::Value;
+
+/// Built-in financial functions
+pub(crate) const FINANCE_FUNCTIONS: &[&str] = &[
+    "npv",
+    "irr",
+    "pmt",
+    "fv",
+    "pv",
+    "compound",
+    "effective",
+    "amortize",
+];
+
+/// The columns of an `amortize` table
+const AMORTIZE_COLUMNS: &[&str] = &["period", "payment", "interest", "principal", "balance"];
+
+/// Most periods `amortize` will lay out, which is a century of monthly payments
+const MAX_PERIODS: usize = 1_200;
+
+/// Most steps `irr` takes before giving up
+const MAX_IRR_STEPS: usize = 200;
+
+pub(crate) fn call_finance(
+    name: &str,
+    args: &[Value],
+    backend: &dyn Backend,
+    tolerance: f64,
+) -> Result<Value, CalcError> {
+    let money = &DecimalBackend::new(DEFAULT_SCALE);
+    let finance = Finance { money };
+    let result = match name {
+        "npv" => {
+            check_count(name, args, 2, usize::MAX)?;
+            let rate = finance.number(name, &args[0])?;
+            finance.npv(&rate, &finance.flows(name, &args[1..])?)?
+        }
+        "irr" => {
+            let flows = finance.flows(name, args)?;
+            // Only the places the tolerance vouches for are kept, so a 10%
+            // return comes out as 0.1 rather than 0.09999999999999985
+            let places = (-tolerance.log10()).ceil().max(0.0) as usize;
+            money.literal(&format!("{:.*}", places, irr(&flows, tolerance)?))?
+        }
+        "pmt" | "fv" | "pv" => {
+            check_count(name, args, 3, 4)?;
+            let [rate, periods, amount, rest @ ..] = &finance.numbers(name, args)?[..] else {
+                unreachable!("checked above");
+            };
+            let zero = money.literal("0")?;
+            let last = rest.first().unwrap_or(&zero);
+            match name {
+                "pmt" => finance.pmt(rate, periods, amount, last)?,
+                "fv" => finance.fv(rate, periods, amount, last)?,
+                _ => finance.pv(rate, periods, amount, last)?,
+            }
+        }
+        "compound" => {
+            let [principal, rate, periods] = &finance.numbers(name, args)?[..] else {
+                unreachable!("FUNCTIONS fixes the arity");
+            };
+            money.mul(principal, &finance.growth(rate, periods)?)?
+        }
+        "effective" => {
+            let [nominal, times] = &finance.numbers(name, args)?[..] else {
+                unreachable!("FUNCTIONS fixes the arity");
+            };
+            let rate = money.div(nominal, times)?;
+            money.sub(&finance.growth(&rate, times)?, &money.literal("1")?)?
+        }
+        "amortize" => {
+            let [rate, periods, principal] = &finance.numbers(name, args)?[..] else {
+                unreachable!("FUNCTIONS fixes the arity");
+            };
+            let rows = finance
+                .amortize(rate, periods, principal)?
+                .into_iter()
+                .map(|row| row.iter().map(|n| backend.convert(n)).collect())
+                .collect::<Result<_, CalcError>>()?;
+            return Ok(Value::Matrix(
+                Matrix::from_rows(rows).with_columns(AMORTIZE_COLUMNS),
+            ));
+        }
+        _ => return Err(CalcError::UnknownFunction(name.to_string())),
+    };
+    Ok(Value::Number(backend.convert(&result)?))
+}
+
+/// `pmt` takes three or four arguments, `npv` two or more
+fn check_count(name: &str, args: &[Value], least: usize, most: usize) -> Result<(), CalcError> {
+    if args.len() < least || args.len() > most {
+        return Err(CalcError::Arity {
+            function: name.to_string(),
+            expected: Some(if args.len() < least { least } else { most }),
+            found: args.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Arithmetic in decimal mode
+struct Finance<'a> {
+    money: &'a dyn Backend,
+}
+
+impl Finance<'_> {
+    /// Bring an argument into decimal mode. Floats go through their
+    /// shortest printed form, so `0.05` arrives as exactly five hundredths
+    /// rather than the binary fraction closest to it.
+    fn number(&self, name: &str, value: &Value) -> Result<Number, CalcError> {
+        match value.plain(name)? {
+            Number::Float(x) => self.money.literal(&x.to_string()),
+            other => self.money.convert(other),
+        }
+    }
+
+    fn numbers(&self, name: &str, args: &[Value]) -> Result<Vec<Number>, CalcError> {
+        args.iter().map(|arg| self.number(name, arg)).collect()
+    }
+
+    /// Cash flows given as a list, as separate arguments, or both
+    fn flows(&self, name: &str, args: &[Value]) -> Result<Vec<Number>, CalcError> {
+        sample(name, args)?
+            .into_iter()
+            .map(|flow| self.number(name, &Value::Number(flow)))
+            .collect()
+    }
+
+    fn one(&self) -> Result<Number, CalcError> {
+        self.money.literal("1")
+    }
+
+    /// `(1 + rate) ^ periods`
+    fn growth(&self, rate: &Number, periods: &Number) -> Result<Number, CalcError> {
+        self.money
+            .pow(&self.money.add(&self.one()?, rate)?, periods)
+    }
+
+    /// Present value of flows at the end of periods 1, 2, ... like the
+    /// spreadsheet `NPV`. Add a flow made today separately.
+    fn npv(&self, rate: &Number, flows: &[Number]) -> Result<Number, CalcError> {
+        let money = self.money;
+        let base = money.add(&self.one()?, rate)?;
+        let mut total = money.literal("0")?;
+        let mut discount = self.one()?;
+        for flow in flows {
+            discount = money.mul(&discount, &base)?;
+            total = money.add(&total, &money.div(flow, &discount)?)?;
+        }
+        Ok(total)
+    }
+
+    /// `((1 + rate) ^ periods - 1) / rate`, the value a payment of 1 per
+    /// period grows to, which is `periods` when the rate is zero
+    fn annuity(&self, rate: &Number, periods: &Number) -> Result<Number, CalcError> {
+        if rate.to_f64() == 0.0 {
+            return Ok(periods.clone());
+        }
+        let grown = self.money.sub(&self.growth(rate, periods)?, &self.one()?)?;
+        self.money.div(&grown, rate)
+    }
+
+    /// The payment each period that takes `present` to `future`
+    fn pmt(
+        &self,
+        rate: &Number,
+        periods: &Number,
+        present: &Number,
+        future: &Number,
+    ) -> Result<Number, CalcError> {
+        let money = self.money;
+        let grown = money.mul(present, &self.growth(rate, periods)?)?;
+        let owed = money.add(&grown, future)?;
+        money.neg(&money.div(&owed, &self.annuity(rate, periods)?)?)
+    }
+
+    /// What `present` and a payment each period come to after `periods`
+    fn fv(
+        &self,
+        rate: &Number,
+        periods: &Number,
+        payment: &Number,
+        present: &Number,
+    ) -> Result<Number, CalcError> {
+        let money = self.money;
+        let grown = money.mul(present, &self.growth(rate, periods)?)?;
+        let paid = money.mul(payment, &self.annuity(rate, periods)?)?;
+        money.neg(&money.add(&grown, &paid)?)
+    }
+
+    /// What a payment each period and `future` at the end are worth today
+    fn pv(
+        &self,
+        rate: &Number,
+        periods: &Number,
+        payment: &Number,
+        future: &Number,
+    ) -> Result<Number, CalcError> {
+        let money = self.money;
+        let paid = money.mul(payment, &self.annuity(rate, periods)?)?;
+        let total = money.add(future, &paid)?;
+        money.neg(&money.div(&total, &self.growth(rate, periods)?)?)
+    }
+
+    /// One row per period with the payment, the interest and principal it
+    /// covers, and the balance left. The last payment clears whatever
+    /// rounding left over, so the balance ends at exactly zero.
+    fn amortize(
+        &self,
+        rate: &Number,
+        periods: &Number,
+        principal: &Number,
+    ) -> Result<Vec<Vec<Number>>, CalcError> {
+        let money = self.money;
+        let count = periods
+            .to_integer()
+            .ok()
+            .and_then(|n| n.to_i128())
+            .filter(|n| (1..=MAX_PERIODS as i128).contains(n))
+            .ok_or_else(|| {
+                CalcError::Domain(format!(
+                    "amortize() needs a whole number of periods from 1 to {}, found {}",
+                    MAX_PERIODS, periods
+                ))
+            })? as usize;
+        let zero = money.literal("0")?;
+        let payment = money.neg(&self.pmt(rate, periods, principal, &zero)?)?;
+        let mut balance = principal.clone();
+        let mut rows = Vec::with_capacity(count);
+        for period in 1..=count {
+            let interest = money.mul(&balance, rate)?;
+            let paid = if period == count {
+                money.add(&balance, &interest)?
+            } else {
+                payment.clone()
+            };
+            let repaid = money.sub(&paid, &interest)?;
+            balance = money.sub(&balance, &repaid)?;
+            rows.push(vec![
+                money.literal(&period.to_string())?,
+                paid,
+                interest,
+                repaid,
+                balance.clone(),
+            ]);
+        }
+        Ok(rows)
+    }
+}
+
+/// The rate at which the flows have a net present value of zero. There is
+/// no formula for it, so it is found numerically in `f64` like the roots
+/// of `solve`: Newton's method from 10%, then bisection if that wanders off.
+fn irr(flows: &[Number], tolerance: f64) -> Result<f64, CalcError> {
+    let flows: Vec<f64> = flows.iter().map(Number::to_f64).collect();
+    let has = |positive: bool| flows.iter().any(|&f| (f > 0.0) == positive && f != 0.0);
+    if !has(true) || !has(false) {
+        return Err(CalcError::NoSolution(
+            "irr() needs cash flows both in and out".to_string(),
+        ));
+    }
+    // Flows start today, so the first one is not discounted
+    let npv = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .enumerate()
+            .map(|(t, flow)| flow / (1.0 + rate).powi(t as i32))
+            .sum()
+    };
+    let slope = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .enumerate()
+            .map(|(t, flow)| -(t as f64) * flow / (1.0 + rate).powi(t as i32 + 1))
+            .sum()
+    };
+
+    let mut rate = 0.1;
+    for _ in 0..MAX_IRR_STEPS {
+        let step = npv(rate) / slope(rate);
+        if !step.is_finite() || rate - step <= -1.0 {
+            break;
+        }
+        rate -= step;
+        if step.abs() < tolerance {
+            return Ok(rate);
+        }
+    }
+
+    // Look for a sign change between -99% and 1000% and bisect it
+    let points: Vec<f64> = (0..=1_100).map(|i| -0.99 + f64::from(i) * 0.01).collect();
+    for pair in points.windows(2) {
+        let (mut low, mut high) = (pair[0], pair[1]);
+        if npv(low).signum() == npv(high).signum() {
+            continue;
+        }
+        while high - low > tolerance {
+            let middle = (low + high) / 2.0;
+            // A tolerance finer than f64 can tell apart would never be reached
+            if middle == low || middle == high {
+                break;
+            }
+            if npv(low).signum() == npv(middle).signum() {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        return Ok((low + high) / 2.0);
+    }
+    Err(CalcError::NoSolution(
+        "irr() found no rate between -99% and 1000%".to_string(),
+    ))
+}
@@ -0,0 +1,39 @@
+//! The Day 6 calculator as a library: parsing, the numeric backends, units
+//! and evaluation, with no terminal I/O. The `calc` binary in `calc-cli` is
+//! a thin front end over this crate.
+//!
+//! ```
+//! use calc_core::{backend_for, evaluate, Env, OverflowPolicy, DEFAULT_SCALE};
+//!
+//! let backend = backend_for("rational", DEFAULT_SCALE, OverflowPolicy::Wrapping).unwrap();
+//! let mut env = Env::new(backend);
+//! evaluate("let x = 1/3", &mut env).unwrap();
+//! assert_eq!(evaluate("x * 3", &mut env).unwrap().to_string(), "1");
+//! ```
+
+mod backend;
+mod datetime;
+mod error;
+mod eval;
+mod finance;
+mod matrix;
+mod number;
+mod parser;
+mod plot;
+mod solve;
+mod stats;
+mod symbolic;
+mod units;
+mod workspace;
+
+pub use backend::{backend_for, Backend, OverflowPolicy, DEFAULT_SCALE, MODES};
+pub use datetime::DateTime;
+pub use error::{CalcError, ParseError};
+pub use eval::{evaluate, execute, AngleUnit, Env, Outcome, UserFunction, DEFAULT_TOLERANCE};
+pub use matrix::Matrix;
+pub use number::{BigInt, Decimal, IntType, Number, Rational};
+pub use parser::Expr;
+pub use plot::Plot;
+pub use stats::summarize;
+pub use units::{Unit, Value};
+pub use workspace::{load_workspace, save_workspace, WorkspaceError};
@@ -0,0 +1,394 @@
+//! Matrix and vector values and the linear algebra on them
+
+use std::fmt;
+
+use crate::backend::Backend;
+use crate::error::CalcError;
+use crate::number::{BigInt, Number, Rational};
+use crate::parser::operator_symbol;
+use crate::units::Value;
+
+/// A rectangular grid of numbers, stored row by row. Vectors are matrices
+/// with a single row (`[1, 2, 3]`) or a single column (`[1; 2; 3]`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    cells: Vec<Number>,
+    /// Names for the columns of a table such as `amortize`'s, written as
+    /// the header when it is exported. Arithmetic on the table drops them.
+    columns: Option<&'static [&'static str]>,
+}
+
+impl Matrix {
+    /// Build a matrix from its rows, which the caller has checked are all
+    /// the same, non-zero length
+    pub(crate) fn from_rows(rows: Vec<Vec<Number>>) -> Matrix {
+        Matrix {
+            rows: rows.len(),
+            cols: rows[0].len(),
+            cells: rows.into_iter().flatten().collect(),
+            columns: None,
+        }
+    }
+
+    /// Name the columns, one name per column
+    pub(crate) fn with_columns(self, names: &'static [&'static str]) -> Matrix {
+        debug_assert_eq!(names.len(), self.cols);
+        Matrix {
+            columns: Some(names),
+            ..self
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The names of the columns, for tables that have them
+    pub fn columns(&self) -> Option<&[&str]> {
+        self.columns
+    }
+
+    /// The entry at `row`, `col`, counting from zero
+    pub fn get(&self, row: usize, col: usize) -> &Number {
+        &self.cells[row * self.cols + col]
+    }
+
+    /// Each row as a list of entries
+    pub(crate) fn row_list(&self) -> Vec<&[Number]> {
+        self.cells.chunks(self.cols).collect()
+    }
+
+    /// Apply `f` to every entry
+    pub(crate) fn map(
+        &self,
+        mut f: impl FnMut(&Number) -> Result<Number, CalcError>,
+    ) -> Result<Matrix, CalcError> {
+        Ok(Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            cells: self.cells.iter().map(&mut f).collect::<Result<_, _>>()?,
+            columns: None,
+        })
+    }
+
+    fn is_vector(&self) -> bool {
+        self.rows == 1 || self.cols == 1
+    }
+
+    /// The entries as exact fractions, row by row, for `det` and `inv`
+    fn to_rationals(&self) -> Result<Vec<Vec<Rational>>, CalcError> {
+        self.row_list()
+            .into_iter()
+            .map(|row| row.iter().map(Number::to_rational).collect())
+            .collect()
+    }
+
+    fn identity(backend: &dyn Backend, size: usize) -> Result<Matrix, CalcError> {
+        let (zero, one) = (backend.literal("0")?, backend.literal("1")?);
+        let rows = (0..size)
+            .map(|r| {
+                (0..size)
+                    .map(|c| if r == c { one.clone() } else { zero.clone() })
+                    .collect()
+            })
+            .collect();
+        Ok(Matrix::from_rows(rows))
+    }
+}
+
+impl fmt::Display for Matrix {
+    /// Printed the way it is typed, so `[1, 2; 3, 4]` reads back the same
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<String> = self
+            .row_list()
+            .into_iter()
+            .map(|row| {
+                let entries: Vec<String> = row.iter().map(|n| n.to_string()).collect();
+                entries.join(", ")
+            })
+            .collect();
+        write!(f, "[{}]", rows.join("; "))
+    }
+}
+
+/// `2x3 matrix`, for messages
+fn shape(matrix: &Matrix) -> String {
+    format!("{}x{} matrix", matrix.rows, matrix.cols)
+}
+
+/// Built-in functions that take matrices
+pub(crate) const MATRIX_FUNCTIONS: &[&str] = &["det", "inv", "transpose", "dot"];
+
+/// `+`, `-`, `*`, `/`, `^` and the element-wise operators when at least
+/// one side is a matrix. A plain number on either side applies to every
+/// entry, so `2 * A` and `A - 1` work.
+pub(crate) fn matrix_binary(
+    backend: &dyn Backend,
+    a: &Value,
+    op: char,
+    b: &Value,
+) -> Result<Value, CalcError> {
+    let apply = |x: &Number, y: &Number| match op {
+        '+' => backend.add(x, y),
+        '-' => backend.sub(x, y),
+        '*' | 'o' => backend.mul(x, y),
+        '/' | 'd' => backend.div(x, y),
+        '%' => backend.rem(x, y),
+        '^' => backend.pow(x, y),
+        _ => backend.bitwise(x, op, y),
+    };
+    let result = match (a, b) {
+        (Value::Matrix(x), Value::Matrix(y)) => match op {
+            '*' => multiply(backend, x, y)?,
+            '/' => {
+                return Err(CalcError::Shape(
+                    "Cannot divide by a matrix; use './' for element-wise division, or * inv()"
+                        .to_string(),
+                ))
+            }
+            '^' => {
+                return Err(CalcError::Shape(format!(
+                    "Cannot raise a {} to the power of a {}",
+                    shape(x),
+                    shape(y)
+                )))
+            }
+            _ => {
+                if (x.rows, x.cols) != (y.rows, y.cols) {
+                    return Err(CalcError::Shape(format!(
+                        "Cannot apply '{}' to a {} and a {}; the shapes must match",
+                        operator_symbol(op),
+                        shape(x),
+                        shape(y)
+                    )));
+                }
+                let cells = x
+                    .cells
+                    .iter()
+                    .zip(&y.cells)
+                    .map(|(p, q)| apply(p, q))
+                    .collect::<Result<_, _>>()?;
+                Matrix {
+                    cells,
+                    columns: None,
+                    ..x.clone()
+                }
+            }
+        },
+        (Value::Matrix(x), scalar) if op == '^' => power(backend, x, scalar.plain("'^'")?)?,
+        (Value::Matrix(x), scalar) => {
+            let y = scalar.plain(&format!("'{}' with a matrix", operator_symbol(op)))?;
+            x.map(|p| apply(p, y))?
+        }
+        (scalar, Value::Matrix(y)) if op == '^' => {
+            return Err(CalcError::Shape(format!(
+                "Cannot raise {} to the power of a {}",
+                scalar,
+                shape(y)
+            )))
+        }
+        (scalar, Value::Matrix(y)) => {
+            let x = scalar.plain(&format!("'{}' with a matrix", operator_symbol(op)))?;
+            y.map(|q| apply(x, q))?
+        }
+        _ => unreachable!("one side is a matrix"),
+    };
+    Ok(Value::Matrix(result))
+}
+
+/// The matrix product `a * b`
+fn multiply(backend: &dyn Backend, a: &Matrix, b: &Matrix) -> Result<Matrix, CalcError> {
+    if a.cols != b.rows {
+        return Err(CalcError::Shape(format!(
+            "Cannot multiply a {} by a {}: the left has {} column{} but the right has {} row{}",
+            shape(a),
+            shape(b),
+            a.cols,
+            if a.cols == 1 { "" } else { "s" },
+            b.rows,
+            if b.rows == 1 { "" } else { "s" }
+        )));
+    }
+    let mut rows = Vec::with_capacity(a.rows);
+    for r in 0..a.rows {
+        let mut row = Vec::with_capacity(b.cols);
+        for c in 0..b.cols {
+            let mut sum = backend.mul(a.get(r, 0), b.get(0, c))?;
+            for k in 1..a.cols {
+                sum = backend.add(&sum, &backend.mul(a.get(r, k), b.get(k, c))?)?;
+            }
+            row.push(sum);
+        }
+        rows.push(row);
+    }
+    Ok(Matrix::from_rows(rows))
+}
+
+/// `A^n` for a square matrix and whole `n`; negative powers invert first
+fn power(backend: &dyn Backend, a: &Matrix, exponent: &Number) -> Result<Matrix, CalcError> {
+    require_square("'^'", a)?;
+    let n = exponent.to_integer().map_err(|_| {
+        CalcError::Domain(format!(
+            "Matrices can only be raised to whole powers, found {}",
+            exponent
+        ))
+    })?;
+    let mut base = if n.is_negative() {
+        inverse(backend, a)?
+    } else {
+        a.clone()
+    };
+    // Square-and-multiply over the bits of |n|
+    let mut remaining = n.abs();
+    let two = BigInt::from_i64(2);
+    let mut result = Matrix::identity(backend, a.rows)?;
+    while !remaining.is_zero() {
+        let (half, bit) = remaining.div_rem(&two);
+        if !bit.is_zero() {
+            result = multiply(backend, &result, &base)?;
+        }
+        remaining = half;
+        if !remaining.is_zero() {
+            base = multiply(backend, &base, &base)?;
+        }
+    }
+    Ok(result)
+}
+
+fn require_square(function: &str, a: &Matrix) -> Result<(), CalcError> {
+    if a.rows == a.cols {
+        Ok(())
+    } else {
+        Err(CalcError::Shape(format!(
+            "{} needs a square matrix, found a {}",
+            function,
+            shape(a)
+        )))
+    }
+}
+
+/// The determinant, found exactly by elimination over fractions
+fn determinant(backend: &dyn Backend, a: &Matrix) -> Result<Number, CalcError> {
+    require_square("det()", a)?;
+    let mut rows = a.to_rationals()?;
+    let mut det = Rational::from_bigint(BigInt::from_i64(1));
+    for column in 0..a.cols {
+        let Some(found) = (column..a.rows).find(|&r| !rows[r][column].is_zero()) else {
+            return backend.literal("0");
+        };
+        if found != column {
+            rows.swap(found, column);
+            det = det.neg();
+        }
+        let pivot_row = rows[column].clone();
+        det = det.mul(&pivot_row[column]);
+        for row in rows.iter_mut().skip(column + 1) {
+            let factor = row[column].div(&pivot_row[column]);
+            for (cell, pivot_cell) in row.iter_mut().zip(&pivot_row).skip(column) {
+                *cell = cell.sub(&factor.mul(pivot_cell));
+            }
+        }
+    }
+    backend.convert(&Number::Rational(det))
+}
+
+/// The inverse, found exactly by Gauss-Jordan elimination over fractions
+fn inverse(backend: &dyn Backend, a: &Matrix) -> Result<Matrix, CalcError> {
+    require_square("inv()", a)?;
+    let n = a.rows;
+    let zero = Rational::from_bigint(BigInt::from_i64(0));
+    let one = Rational::from_bigint(BigInt::from_i64(1));
+    // Work on `[A | I]` until the left half is the identity
+    let mut rows: Vec<Vec<Rational>> = a
+        .to_rationals()?
+        .into_iter()
+        .enumerate()
+        .map(|(r, mut row)| {
+            row.extend((0..n).map(|c| if r == c { one.clone() } else { zero.clone() }));
+            row
+        })
+        .collect();
+    for column in 0..n {
+        let Some(found) = (column..n).find(|&r| !rows[r][column].is_zero()) else {
+            return Err(CalcError::Domain(
+                "The matrix is singular and has no inverse".to_string(),
+            ));
+        };
+        rows.swap(found, column);
+        let pivot = rows[column][column].clone();
+        rows[column] = rows[column].iter().map(|cell| cell.div(&pivot)).collect();
+        let pivot_row = rows[column].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            let factor = row[column].clone();
+            if r == column || factor.is_zero() {
+                continue;
+            }
+            for (cell, pivot_cell) in row.iter_mut().zip(&pivot_row) {
+                *cell = cell.sub(&factor.mul(pivot_cell));
+            }
+        }
+    }
+    let rows = rows
+        .into_iter()
+        .map(|row| {
+            row[n..]
+                .iter()
+                .map(|cell| backend.convert(&Number::Rational(cell.clone())))
+                .collect()
+        })
+        .collect::<Result<Vec<Vec<Number>>, CalcError>>()?;
+    Ok(Matrix::from_rows(rows))
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    let rows = (0..a.cols)
+        .map(|c| (0..a.rows).map(|r| a.get(r, c).clone()).collect())
+        .collect();
+    Matrix::from_rows(rows)
+}
+
+/// The dot product of two vectors of the same length, row or column
+fn dot(backend: &dyn Backend, a: &Matrix, b: &Matrix) -> Result<Number, CalcError> {
+    if !a.is_vector() || !b.is_vector() || a.cells.len() != b.cells.len() {
+        return Err(CalcError::Shape(format!(
+            "dot() needs two vectors of the same length, found a {} and a {}",
+            shape(a),
+            shape(b)
+        )));
+    }
+    let mut sum = backend.mul(&a.cells[0], &b.cells[0])?;
+    for (x, y) in a.cells.iter().zip(&b.cells).skip(1) {
+        sum = backend.add(&sum, &backend.mul(x, y)?)?;
+    }
+    Ok(sum)
+}
+
+/// `det`, `inv`, `transpose` and `dot`
+pub(crate) fn call_matrix(
+    name: &str,
+    args: &[Value],
+    backend: &dyn Backend,
+) -> Result<Value, CalcError> {
+    let matrices = args
+        .iter()
+        .map(|arg| match arg {
+            Value::Matrix(m) => Ok(m),
+            other => Err(CalcError::Shape(format!(
+                "{}() needs a matrix, found {}",
+                name, other
+            ))),
+        })
+        .collect::<Result<Vec<&Matrix>, CalcError>>()?;
+    Ok(match name {
+        "det" => Value::Number(determinant(backend, matrices[0])?),
+        "inv" => Value::Matrix(inverse(backend, matrices[0])?),
+        "transpose" => Value::Matrix(transpose(matrices[0])),
+        _ => Value::Number(dot(backend, matrices[0], matrices[1])?),
+    })
+}
@@ -0,0 +1,748 @@
+//! Exact number types: big integers, fractions, fixed-point decimals and
+//! fixed-width machine integers
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::error::CalcError;
+
+/// Each limb holds nine decimal digits, which keeps parsing and printing simple
+const LIMB_BASE: u64 = 1_000_000_000;
+const LIMB_DIGITS: usize = 9;
+
+/// Arbitrary-precision signed integer, stored as base 10^9 limbs with the
+/// least significant limb first. Zero has no limbs and is never negative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    pub(crate) fn from_i64(n: i64) -> BigInt {
+        let mut magnitude = n.unsigned_abs();
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push((magnitude % LIMB_BASE) as u32);
+            magnitude /= LIMB_BASE;
+        }
+        BigInt {
+            negative: n < 0,
+            limbs,
+        }
+    }
+
+    pub(crate) fn from_u128(mut magnitude: u128) -> BigInt {
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push((magnitude % LIMB_BASE as u128) as u32);
+            magnitude /= LIMB_BASE as u128;
+        }
+        BigInt {
+            negative: false,
+            limbs,
+        }
+    }
+
+    pub(crate) fn from_i128(n: i128) -> BigInt {
+        let magnitude = BigInt::from_u128(n.unsigned_abs());
+        if n < 0 {
+            magnitude.neg()
+        } else {
+            magnitude
+        }
+    }
+
+    fn from_limbs(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        trim_limbs(&mut limbs);
+        let negative = negative && !limbs.is_empty();
+        BigInt { negative, limbs }
+    }
+
+    /// Parse an optionally negative run of decimal digits
+    pub(crate) fn parse(text: &str) -> Option<BigInt> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut limbs = Vec::new();
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(LIMB_DIGITS);
+            limbs.push(digits[start..end].parse().ok()?);
+            end = start;
+        }
+        Some(BigInt::from_limbs(negative, limbs))
+    }
+
+    /// 10 raised to `exponent`
+    fn pow10(exponent: u32) -> BigInt {
+        let mut limbs = vec![0; exponent as usize / LIMB_DIGITS];
+        limbs.push(10u32.pow(exponent % LIMB_DIGITS as u32));
+        BigInt::from_limbs(false, limbs)
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub(crate) fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub(crate) fn neg(&self) -> BigInt {
+        BigInt::from_limbs(!self.negative, self.limbs.clone())
+    }
+
+    pub(crate) fn abs(&self) -> BigInt {
+        BigInt::from_limbs(false, self.limbs.clone())
+    }
+
+    pub(crate) fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_limbs(self.negative, add_limbs(&self.limbs, &other.limbs));
+        }
+        match cmp_limbs(&self.limbs, &other.limbs) {
+            Ordering::Less => {
+                BigInt::from_limbs(other.negative, sub_limbs(&other.limbs, &self.limbs))
+            }
+            _ => BigInt::from_limbs(self.negative, sub_limbs(&self.limbs, &other.limbs)),
+        }
+    }
+
+    pub(crate) fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    pub(crate) fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::from_limbs(
+            self.negative != other.negative,
+            mul_limbs(&self.limbs, &other.limbs),
+        )
+    }
+
+    /// Truncating division. The remainder takes the sign of the dividend, the
+    /// same as Rust's `/` and `%` on primitive integers. The caller must make
+    /// sure `other` is not zero.
+    pub(crate) fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+        let (quotient, remainder) = div_rem_limbs(&self.limbs, &other.limbs);
+        (
+            BigInt::from_limbs(self.negative != other.negative, quotient),
+            BigInt::from_limbs(self.negative, remainder),
+        )
+    }
+
+    fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let remainder = a.div_rem(&b).1;
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub(crate) fn to_i128(&self) -> Option<i128> {
+        self.to_string().parse().ok()
+    }
+
+    pub(crate) fn to_u128(&self) -> Option<u128> {
+        self.to_string().parse().ok()
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_limbs(&self.limbs, &other.limbs),
+            (true, true) => cmp_limbs(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some((most_significant, rest)) = self.limbs.split_last() else {
+            return write!(f, "0");
+        };
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", most_significant)?;
+        for limb in rest.iter().rev() {
+            write!(f, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim_limbs(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn cmp_limbs(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = carry + *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64;
+        result.push((sum % LIMB_BASE) as u32);
+        carry = sum / LIMB_BASE;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Subtract magnitudes, `a` must be at least as large as `b`
+fn sub_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut diff = limb as i64 - borrow - *b.get(i).unwrap_or(&0) as i64;
+        borrow = 0;
+        if diff < 0 {
+            diff += LIMB_BASE as i64;
+            borrow = 1;
+        }
+        result.push(diff as u32);
+    }
+    trim_limbs(&mut result);
+    result
+}
+
+fn mul_limbs(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut result = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let current = result[i + j] + x as u64 * y as u64 + carry;
+            result[i + j] = current % LIMB_BASE;
+            carry = current / LIMB_BASE;
+        }
+        let mut k = i + b.len();
+        while carry > 0 {
+            let current = result[k] + carry;
+            result[k] = current % LIMB_BASE;
+            carry = current / LIMB_BASE;
+            k += 1;
+        }
+    }
+    let mut limbs: Vec<u32> = result.into_iter().map(|limb| limb as u32).collect();
+    trim_limbs(&mut limbs);
+    limbs
+}
+
+/// Schoolbook long division, one limb of quotient at a time. Each quotient
+/// limb is found by binary search, which is slow for huge numbers but easy
+/// to get right.
+fn div_rem_limbs(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+
+    for i in (0..a.len()).rev() {
+        remainder.insert(0, a[i]);
+        trim_limbs(&mut remainder);
+
+        let (mut low, mut high) = (0, LIMB_BASE as u32 - 1);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if cmp_limbs(&mul_limbs(b, &[mid]), &remainder) == Ordering::Greater {
+                high = mid - 1;
+            } else {
+                low = mid;
+            }
+        }
+
+        if low > 0 {
+            remainder = sub_limbs(&remainder, &mul_limbs(b, &[low]));
+        }
+        quotient[i] = low;
+    }
+
+    trim_limbs(&mut quotient);
+    (quotient, remainder)
+}
+
+/// Exact fraction kept in lowest terms with a positive denominator
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rational {
+    pub(crate) num: BigInt,
+    pub(crate) den: BigInt,
+}
+
+impl Rational {
+    /// Build a reduced fraction. The caller must make sure `den` is not zero.
+    fn new(num: BigInt, den: BigInt) -> Rational {
+        let divisor = num.gcd(&den);
+        let (mut num, mut den) = if divisor.is_zero() {
+            (num, den)
+        } else {
+            (num.div_rem(&divisor).0, den.div_rem(&divisor).0)
+        };
+        if den.is_negative() {
+            num = num.neg();
+            den = den.neg();
+        }
+        Rational { num, den }
+    }
+
+    pub(crate) fn from_bigint(n: BigInt) -> Rational {
+        Rational {
+            num: n,
+            den: BigInt::from_i64(1),
+        }
+    }
+
+    /// Read a decimal literal such as `-12.50` or `6.02e23` exactly, so
+    /// `0.1` is `1/10`. Exponents past `MAX_EXACT_EXPONENT` are refused.
+    pub(crate) fn parse_decimal(text: &str) -> Option<Rational> {
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+            None => (unsigned, 0),
+        };
+        if exponent.unsigned_abs() > MAX_EXACT_EXPONENT {
+            return None;
+        }
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{}{}", whole, fraction);
+        let mut num = BigInt::parse(&digits)?;
+        if negative {
+            num = num.neg();
+        }
+        let shift = exponent - fraction.len() as i64;
+        if shift >= 0 {
+            Some(Rational::new(
+                num.mul(&BigInt::pow10(shift as u32)),
+                BigInt::from_i64(1),
+            ))
+        } else {
+            Some(Rational::new(
+                num,
+                BigInt::pow10(shift.unsigned_abs() as u32),
+            ))
+        }
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+
+    pub(crate) fn is_integer(&self) -> bool {
+        self.den == BigInt::from_i64(1)
+    }
+
+    pub(crate) fn add(&self, other: &Rational) -> Rational {
+        Rational::new(
+            self.num.mul(&other.den).add(&other.num.mul(&self.den)),
+            self.den.mul(&other.den),
+        )
+    }
+
+    pub(crate) fn sub(&self, other: &Rational) -> Rational {
+        self.add(&other.neg())
+    }
+
+    pub(crate) fn mul(&self, other: &Rational) -> Rational {
+        Rational::new(self.num.mul(&other.num), self.den.mul(&other.den))
+    }
+
+    /// The caller must make sure `other` is not zero
+    pub(crate) fn div(&self, other: &Rational) -> Rational {
+        Rational::new(self.num.mul(&other.den), self.den.mul(&other.num))
+    }
+
+    pub(crate) fn neg(&self) -> Rational {
+        Rational {
+            num: self.num.neg(),
+            den: self.den.clone(),
+        }
+    }
+
+    pub(crate) fn abs(&self) -> Rational {
+        Rational {
+            num: self.num.abs(),
+            den: self.den.clone(),
+        }
+    }
+
+    /// Largest whole number not above this value
+    pub(crate) fn floor(&self) -> BigInt {
+        let (quotient, remainder) = self.num.div_rem(&self.den);
+        if remainder.is_negative() {
+            quotient.sub(&BigInt::from_i64(1))
+        } else {
+            quotient
+        }
+    }
+
+    fn ceil(&self) -> BigInt {
+        self.neg().floor().neg()
+    }
+
+    /// Nearest whole number, with halves rounded away from zero
+    fn round(&self) -> BigInt {
+        let half = Rational::new(BigInt::from_i64(1), BigInt::from_i64(2));
+        if self.num.is_negative() {
+            self.neg().add(&half).floor().neg()
+        } else {
+            self.add(&half).floor()
+        }
+    }
+
+    /// Raise to a whole power by repeated squaring. The caller must make
+    /// sure a zero base is not raised to a negative power.
+    pub(crate) fn pow(&self, exponent: &BigInt) -> Result<Rational, CalcError> {
+        let mut remaining = exact_exponent(exponent)?;
+
+        let mut result = Rational::from_bigint(BigInt::from_i64(1));
+        let mut base = self.clone();
+        while remaining > 0 {
+            if remaining % 2 == 1 {
+                result = result.mul(&base);
+            }
+            base = base.mul(&base);
+            remaining /= 2;
+        }
+
+        if exponent.is_negative() {
+            result = Rational::from_bigint(BigInt::from_i64(1)).div(&result);
+        }
+        Ok(result)
+    }
+
+    fn to_f64(&self) -> f64 {
+        let value = self.num.to_f64() / self.den.to_f64();
+        if value.is_finite() {
+            return value;
+        }
+        // Numerator and denominator are both too large for f64 on their own
+        Decimal::from_rational(self, 40)
+            .to_string()
+            .parse()
+            .unwrap_or(f64::NAN)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+/// Largest power computed exactly; beyond this the numbers get too long to be useful
+const MAX_EXACT_EXPONENT: u64 = 10_000;
+
+/// The size of a whole exponent, refused past `MAX_EXACT_EXPONENT`
+pub(crate) fn exact_exponent(exponent: &BigInt) -> Result<u64, CalcError> {
+    let size = exponent
+        .abs()
+        .to_string()
+        .parse::<u64>()
+        .unwrap_or(u64::MAX);
+    if size > MAX_EXACT_EXPONENT {
+        return Err(CalcError::Overflow(format!(
+            "Exponent {} is too large for exact arithmetic (limit {})",
+            exponent, MAX_EXACT_EXPONENT
+        )));
+    }
+    Ok(size)
+}
+
+/// Fixed-point decimal: the value is `unscaled / 10^scale`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decimal {
+    unscaled: BigInt,
+    scale: u32,
+}
+
+impl Decimal {
+    /// Round an exact fraction to `scale` digits after the point, with halves
+    /// rounded away from zero as is usual for money
+    pub(crate) fn from_rational(value: &Rational, scale: u32) -> Decimal {
+        let scaled = value.num.mul(&BigInt::pow10(scale));
+        let (quotient, remainder) = scaled.div_rem(&value.den);
+        let twice_remainder = remainder.abs().mul(&BigInt::from_i64(2));
+
+        let unscaled = if twice_remainder < value.den {
+            quotient
+        } else if scaled.is_negative() {
+            quotient.sub(&BigInt::from_i64(1))
+        } else {
+            quotient.add(&BigInt::from_i64(1))
+        };
+        Decimal { unscaled, scale }
+    }
+
+    pub(crate) fn to_rational(&self) -> Rational {
+        Rational::new(self.unscaled.clone(), BigInt::pow10(self.scale))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scale = self.scale as usize;
+        let mut digits = self.unscaled.abs().to_string();
+        if digits.len() <= scale {
+            digits = format!("{}{}", "0".repeat(scale + 1 - digits.len()), digits);
+        }
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        let fraction = fraction.trim_end_matches('0');
+
+        if self.unscaled.is_negative() {
+            write!(f, "-")?;
+        }
+        if fraction.is_empty() {
+            write!(f, "{}", whole)
+        } else {
+            write!(f, "{}.{}", whole, fraction)
+        }
+    }
+}
+
+/// A fixed-width machine integer type such as `u8` or `i64`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl IntType {
+    /// Read a type name: `i8` to `i128` or `u8` to `u128`
+    pub fn parse(name: &str) -> Option<IntType> {
+        let signed = match name.get(..1)? {
+            "i" => true,
+            "u" => false,
+            _ => return None,
+        };
+        let bits = match &name[1..] {
+            "8" => 8,
+            "16" => 16,
+            "32" => 32,
+            "64" => 64,
+            "128" => 128,
+            _ => return None,
+        };
+        Some(IntType { signed, bits })
+    }
+
+    /// All bits of the type set
+    pub(crate) fn mask(&self) -> u128 {
+        u128::MAX >> (128 - self.bits)
+    }
+
+    /// 2^bits, the number of distinct values
+    fn modulus(&self) -> BigInt {
+        BigInt::from_u128(1 << (self.bits - 1)).mul(&BigInt::from_i64(2))
+    }
+
+    fn min(&self) -> BigInt {
+        if self.signed {
+            self.modulus().div_rem(&BigInt::from_i64(2)).0.neg()
+        } else {
+            BigInt::from_i64(0)
+        }
+    }
+
+    fn max(&self) -> BigInt {
+        let max = if self.signed {
+            self.mask() >> 1
+        } else {
+            self.mask()
+        };
+        BigInt::from_u128(max)
+    }
+
+    pub(crate) fn fits(&self, value: &BigInt) -> bool {
+        *value >= self.min() && *value <= self.max()
+    }
+
+    /// The value a bit pattern stands for, reading the top bit as the sign
+    /// for signed types
+    pub(crate) fn value(&self, pattern: u128) -> BigInt {
+        let value = BigInt::from_u128(pattern);
+        if self.signed && pattern >> (self.bits - 1) & 1 == 1 {
+            value.sub(&self.modulus())
+        } else {
+            value
+        }
+    }
+
+    /// Two's complement bit pattern of `value`, dropping any bits that do not
+    /// fit, the same as an `as` cast
+    pub(crate) fn wrap(&self, value: &BigInt) -> u128 {
+        let modulus = self.modulus();
+        let mut remainder = value.div_rem(&modulus).1;
+        if remainder.is_negative() {
+            remainder = remainder.add(&modulus);
+        }
+        remainder.to_u128().expect("reduced below 2^128")
+    }
+}
+
+impl fmt::Display for IntType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+/// Binary digits in groups of four, so `0b1010_0101` stays readable
+fn grouped_binary(pattern: u128) -> String {
+    let digits = format!("{:b}", pattern);
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 4 == 0 {
+            grouped.push('_');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// A value produced by one of the numeric backends
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Float(f64),
+    Integer(BigInt),
+    Rational(Rational),
+    Decimal(Decimal),
+    /// A fixed-width integer, stored as its bit pattern
+    Word(u128, IntType),
+}
+
+impl Number {
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Float(n) => *n,
+            Number::Integer(n) => n.to_f64(),
+            Number::Rational(n) => n.to_f64(),
+            Number::Decimal(n) => n.to_rational().to_f64(),
+            Number::Word(pattern, int_type) => int_type.value(*pattern).to_f64(),
+        }
+    }
+
+    /// The exact value as a fraction. Floats go through their shortest
+    /// decimal form, so a float `0.1` becomes `1/10` rather than the binary
+    /// approximation.
+    pub(crate) fn to_rational(&self) -> Result<Rational, CalcError> {
+        match self {
+            Number::Float(n) => Rational::parse_decimal(&n.to_string())
+                .ok_or_else(|| CalcError::Domain(format!("{} has no exact value", n))),
+            Number::Integer(n) => Ok(Rational::from_bigint(n.clone())),
+            Number::Rational(n) => Ok(n.clone()),
+            Number::Decimal(n) => Ok(n.to_rational()),
+            Number::Word(pattern, int_type) => Ok(Rational::from_bigint(int_type.value(*pattern))),
+        }
+    }
+
+    pub(crate) fn to_integer(&self) -> Result<BigInt, CalcError> {
+        let exact = self.to_rational()?;
+        if !exact.is_integer() {
+            return Err(CalcError::Domain(format!("{} is not a whole number", self)));
+        }
+        Ok(exact.num)
+    }
+
+    /// Apply an exact operation, keeping the same kind of number. Floats use
+    /// the matching `f64` operation instead and fixed-width results wrap.
+    fn map_exact(&self, float: fn(f64) -> f64, exact: fn(&Rational) -> Rational) -> Number {
+        match self {
+            Number::Float(n) => Number::Float(float(*n)),
+            Number::Integer(n) => Number::Integer(exact(&Rational::from_bigint(n.clone())).num),
+            Number::Rational(n) => Number::Rational(exact(n)),
+            Number::Decimal(n) => {
+                Number::Decimal(Decimal::from_rational(&exact(&n.to_rational()), n.scale))
+            }
+            Number::Word(pattern, int_type) => {
+                let result = exact(&Rational::from_bigint(int_type.value(*pattern)));
+                Number::Word(int_type.wrap(&result.num), *int_type)
+            }
+        }
+    }
+
+    pub(crate) fn abs(&self) -> Number {
+        self.map_exact(f64::abs, Rational::abs)
+    }
+
+    pub(crate) fn floor(&self) -> Number {
+        self.map_exact(f64::floor, |n| Rational::from_bigint(n.floor()))
+    }
+
+    pub(crate) fn ceil(&self) -> Number {
+        self.map_exact(f64::ceil, |n| Rational::from_bigint(n.ceil()))
+    }
+
+    pub(crate) fn round(&self) -> Number {
+        self.map_exact(f64::round, |n| Rational::from_bigint(n.round()))
+    }
+
+    /// Compare two numbers, exactly unless either side is a float
+    pub(crate) fn compare(&self, other: &Number) -> Ordering {
+        match (self, other) {
+            (Number::Float(_), _) | (_, Number::Float(_)) => self
+                .to_f64()
+                .partial_cmp(&other.to_f64())
+                .unwrap_or(Ordering::Equal),
+            _ => match (self.to_rational(), other.to_rational()) {
+                (Ok(a), Ok(b)) => a.sub(&b).num.cmp(&BigInt::from_i64(0)),
+                _ => Ordering::Equal,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Float(n) => write!(f, "{}", n),
+            Number::Integer(n) => write!(f, "{}", n),
+            Number::Rational(n) => write!(f, "{}", n),
+            Number::Decimal(n) => write!(f, "{}", n),
+            // Programmer mode shows every base at once
+            Number::Word(pattern, int_type) => write!(
+                f,
+                "{} (0x{:x}, 0o{:o}, 0b{})",
+                int_type.value(*pattern),
+                pattern,
+                pattern,
+                grouped_binary(*pattern)
+            ),
+        }
+    }
+}
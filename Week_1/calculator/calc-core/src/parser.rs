@@ -0,0 +1,855 @@
+//! Tokenizer, syntax tree and recursive-descent parser
+
+use std::fmt;
+
+use crate::datetime::{parse_datetime, scan_datetime, scan_duration};
+use crate::error::ParseError;
+use crate::units::Unit;
+
+/// A single lexical token together with the column (1-based) it starts at
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    /// `2026-10-18`, with an optional time and UTC offset
+    Date(String),
+    /// `3h 20m`
+    Duration(String),
+    Ident(String),
+    History(usize),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    /// `**`, power in every mode
+    StarStar,
+    Amp,
+    Pipe,
+    Tilde,
+    Shl,
+    Shr,
+    Bang,
+    LParen,
+    RParen,
+    /// `[` and `]` around a matrix literal
+    LBracket,
+    RBracket,
+    /// Separates the rows of a matrix literal
+    Semicolon,
+    /// `.*` and `./`, element-wise on matrices
+    DotStar,
+    DotSlash,
+    Comma,
+    Equals,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Date(text) => write!(f, "date {}", text),
+            Token::Duration(text) => write!(f, "duration {}", text),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::History(index) => write!(f, "'${}'", index),
+            Token::Plus => write!(f, "'+'"),
+            Token::Minus => write!(f, "'-'"),
+            Token::Star => write!(f, "'*'"),
+            Token::Slash => write!(f, "'/'"),
+            Token::Percent => write!(f, "'%'"),
+            Token::Caret => write!(f, "'^'"),
+            Token::StarStar => write!(f, "'**'"),
+            Token::Amp => write!(f, "'&'"),
+            Token::Pipe => write!(f, "'|'"),
+            Token::Tilde => write!(f, "'~'"),
+            Token::Shl => write!(f, "'<<'"),
+            Token::Shr => write!(f, "'>>'"),
+            Token::Bang => write!(f, "'!'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::LBracket => write!(f, "'['"),
+            Token::RBracket => write!(f, "']'"),
+            Token::Semicolon => write!(f, "';'"),
+            Token::DotStar => write!(f, "'.*'"),
+            Token::DotSlash => write!(f, "'./'"),
+            Token::Comma => write!(f, "','"),
+            Token::Equals => write!(f, "'='"),
+            Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Syntax tree produced by the parser. Number literals keep their source
+/// text so each numeric backend can read them at its own precision.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(String),
+    /// A date literal as written, such as `2026-10-18T09:30Z`
+    Date(String),
+    /// A compound duration as written, such as `3h 20m`
+    Duration(String),
+    Variable(String),
+    History(usize),
+    Negate(Box<Expr>),
+    /// `~x`, bitwise complement
+    BitNot(Box<Expr>),
+    Factorial(Box<Expr>),
+    /// The operator is its source character, except `x` for xor, `<` for
+    /// `<<`, `>` for `>>`, `o` for `.*` and `d` for `./` (see
+    /// `operator_symbol`). `=` only appears in
+    /// call arguments, as in `solve(x^2 = 4, x)`.
+    Binary(Box<Expr>, char, Box<Expr>),
+    Call(String, Vec<Expr>),
+    /// `value in unit`
+    Convert(Box<Expr>, Box<Expr>),
+    /// `[1, 2; 3, 4]`, row by row; every row has the same length
+    Matrix(Vec<Vec<Expr>>),
+}
+
+impl Expr {
+    /// Binding strength, used to print only the parentheses that are needed
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Convert(..) | Expr::Binary(_, '=', _) => 0,
+            Expr::Binary(_, '|', _) => 1,
+            Expr::Binary(_, 'x', _) => 2,
+            Expr::Binary(_, '&', _) => 3,
+            Expr::Binary(_, '<' | '>', _) => 4,
+            Expr::Binary(_, '+' | '-', _) => 5,
+            Expr::Binary(_, '^', _) => 8,
+            Expr::Binary(..) => 6,
+            Expr::Negate(_) | Expr::BitNot(_) => UNARY_PRECEDENCE,
+            _ => 9,
+        }
+    }
+}
+
+const UNARY_PRECEDENCE: u8 = 7;
+
+/// How a binary operator is written. Xor prints as the `xor` keyword so the
+/// text reads back the same way whichever mode is active.
+pub(crate) fn operator_symbol(op: char) -> &'static str {
+    match op {
+        'x' => "xor",
+        '<' => "<<",
+        '>' => ">>",
+        '+' => "+",
+        '-' => "-",
+        '*' => "*",
+        '/' => "/",
+        '%' => "%",
+        '^' => "^",
+        '&' => "&",
+        '|' => "|",
+        '=' => "=",
+        'o' => ".*",
+        'd' => "./",
+        _ => unreachable!("the parser only produces known operators"),
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Wrap a child in parentheses when it binds looser than its parent needs
+        fn child(f: &mut fmt::Formatter, expr: &Expr, wrap: bool) -> fmt::Result {
+            if wrap {
+                write!(f, "({})", expr)
+            } else {
+                write!(f, "{}", expr)
+            }
+        }
+
+        match self {
+            Expr::Number(text) | Expr::Date(text) | Expr::Duration(text) => write!(f, "{}", text),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::History(index) => write!(f, "${}", index),
+            Expr::Negate(inner) => {
+                write!(f, "-")?;
+                child(f, inner, inner.precedence() <= UNARY_PRECEDENCE)
+            }
+            Expr::BitNot(inner) => {
+                write!(f, "~")?;
+                child(f, inner, inner.precedence() < UNARY_PRECEDENCE)
+            }
+            Expr::Factorial(inner) => {
+                child(f, inner, inner.precedence() < 9)?;
+                write!(f, "!")
+            }
+            Expr::Binary(left, '^', right) => {
+                child(f, left, left.precedence() <= 8)?;
+                write!(f, "^")?;
+                child(f, right, right.precedence() < UNARY_PRECEDENCE)
+            }
+            Expr::Binary(left, op, right) => {
+                let precedence = self.precedence();
+                child(f, left, left.precedence() < precedence)?;
+                write!(f, " {} ", operator_symbol(*op))?;
+                child(f, right, right.precedence() <= precedence)
+            }
+            Expr::Convert(value, unit) => write!(f, "{} in {}", value, unit),
+            Expr::Matrix(rows) => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| {
+                        let entries: Vec<String> = row.iter().map(|e| e.to_string()).collect();
+                        entries.join(", ")
+                    })
+                    .collect();
+                write!(f, "[{}]", rows.join("; "))
+            }
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// A full input line: a bare expression, a `let` assignment or a function
+/// definition such as `f(x) = x^2 + 1`
+#[derive(Debug)]
+pub(crate) enum Statement {
+    Expr(Expr),
+    Let(String, Expr),
+    Function(String, Vec<String>, Expr),
+}
+
+/// Split a line into tokens, remembering the column of each one
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // `0x`, `0o` and `0b` literals, with `_` allowed between digits
+        if c == '0' && matches!(chars.get(i + 1), Some('x' | 'o' | 'b')) {
+            let start = i;
+            i += 2;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            if radix_literal(&text).is_none() {
+                return Err(ParseError {
+                    column,
+                    message: format!("invalid number '{}'", text),
+                });
+            }
+            tokens.push((Token::Number(text), column));
+            continue;
+        }
+
+        // Dates and compound durations are read whole, before plain numbers
+        if let Some(length) = scan_datetime(&chars[i..]) {
+            let text: String = chars[i..i + length].iter().collect();
+            parse_datetime(&text).map_err(|message| ParseError { column, message })?;
+            tokens.push((Token::Date(text), column));
+            i += length;
+            continue;
+        }
+        if let Some(length) = scan_duration(&chars[i..]) {
+            tokens.push((
+                Token::Duration(chars[i..i + length].iter().collect()),
+                column,
+            ));
+            i += length;
+            continue;
+        }
+
+        let element_wise = c == '.' && matches!(chars.get(i + 1), Some('*' | '/'));
+        if c.is_ascii_digit() || (c == '.' && !element_wise) {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || (chars[i] == '.' && !matches!(chars.get(i + 1), Some('*' | '/'))))
+            {
+                i += 1;
+            }
+            // An exponent belongs to the number, so `1e-3` is not `1*e - 3`.
+            // `e` with no digits after it is still Euler's number, as in `2e`.
+            if matches!(chars.get(i), Some('e' | 'E')) {
+                let sign = usize::from(matches!(chars.get(i + 1), Some('+' | '-')));
+                if chars.get(i + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            if text.parse::<f64>().is_err() {
+                return Err(ParseError {
+                    column,
+                    message: format!("invalid number '{}'", text),
+                });
+            }
+            tokens.push((Token::Number(text), column));
+            continue;
+        }
+
+        // `°` may start a name so that `°C` and `°F` read as units
+        if c.is_alphabetic() || c == '_' || c == '°' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            tokens.push((Token::Ident(name), column));
+            continue;
+        }
+
+        // `$1`, `$2`, ... recall earlier results by their position in the history
+        if c == '$' {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            let index: usize = digits.parse().map_err(|_| ParseError {
+                column,
+                message: "expected a result number after '$'".to_string(),
+            })?;
+            tokens.push((Token::History(index), column));
+            continue;
+        }
+
+        // Two-character operators
+        let pair = match (c, chars.get(i + 1)) {
+            ('.', Some('*')) => Some(Token::DotStar),
+            ('.', Some('/')) => Some(Token::DotSlash),
+            ('*', Some('*')) => Some(Token::StarStar),
+            ('<', Some('<')) => Some(Token::Shl),
+            ('>', Some('>')) => Some(Token::Shr),
+            _ => None,
+        };
+        if let Some(token) = pair {
+            tokens.push((token, column));
+            i += 2;
+            continue;
+        }
+
+        let token = match c {
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '%' => Token::Percent,
+            '^' => Token::Caret,
+            '&' => Token::Amp,
+            '|' => Token::Pipe,
+            '~' => Token::Tilde,
+            '!' => Token::Bang,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            _ => {
+                return Err(ParseError {
+                    column,
+                    message: format!("unexpected character '{}'", c),
+                })
+            }
+        };
+        tokens.push((token, column));
+        i += 1;
+    }
+
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+/// Words the grammar gives a meaning of their own
+fn is_keyword(name: &str) -> bool {
+    matches!(name, "let" | "in" | "to" | "xor")
+}
+
+/// The bit pattern of a `0x`, `0o` or `0b` literal, or `None` for anything
+/// else (including plain decimal literals and patterns wider than 128 bits)
+pub(crate) fn radix_literal(text: &str) -> Option<u128> {
+    let radix = match text.get(..2)? {
+        "0x" => 16,
+        "0o" => 8,
+        "0b" => 2,
+        _ => return None,
+    };
+    let digits = &text[2..];
+    if digits.starts_with('_') || digits.ends_with('_') {
+        return None;
+    }
+    u128::from_str_radix(&digits.replace('_', ""), radix).ok()
+}
+
+/// Recursive-descent parser over the token stream.
+///
+/// Grammar, from lowest to highest precedence:
+///   line    := 'let' ident '=' expr | ident '(' params ')' '=' expr | expr
+///   expr    := bit_or (('in' | 'to') bit_or)*
+///   bit_or  := bit_xor ('|' bit_xor)*
+///   bit_xor := bit_and ('xor' bit_and)*
+///   bit_and := shift ('&' shift)*
+///   shift   := sum (('<<' | '>>') sum)*
+///   sum     := term (('+' | '-') term)*
+///   term    := unary (('*' | '/' | '.*' | './' | '%') unary)*
+///   unary   := '-' unary | '~' unary | power
+///   power   := postfix (('^' | '**') unary)?
+///   postfix := primary '!'*
+///   primary := number [unit ('^' unary)?] [power] | ident | ident '(' args ')'
+///            | '$' digits | '(' expr ')' | '[' rows ']' | date | duration
+///   rows    := expr (',' expr)* (';' expr (',' expr)*)*
+///   args    := arg (',' arg)*
+///   arg     := expr ('=' expr)?
+///
+/// `^` is right-associative and binds tighter than unary minus, so `-2^2`
+/// is `-4` and `2^3^2` is `2^9`. A unit written straight after a number
+/// binds to it, so `5 km^2` is five square kilometres. Any other name
+/// written with no space after a number multiplies it, so `2x^2` is
+/// `2 * x^2`.
+///
+/// A number may carry an exponent, as in `6.02e23` or `1e-3`.
+///
+/// A date is `YYYY-MM-DD` with an optional `THH:MM[:SS]` (or a space
+/// before the time) and `Z` or `±HH:MM`; a duration is two or more groups
+/// such as `3h 20m`. Both are single tokens, so `2026-10-18` is a date and
+/// not a subtraction.
+///
+/// In programmer mode `caret_xor` is set and `^` is xor, as in C, leaving
+/// `**` for powers.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    caret_xor: bool,
+    /// How many `unary` calls are open, see `MAX_NESTING`
+    depth: usize,
+}
+
+/// Deepest nesting of brackets, signs and exponents in one line. Parsing
+/// and evaluating each level takes several stack frames, so like
+/// `MAX_CALL_DEPTH` this stays well within the 2 MiB stack of a spawned
+/// thread.
+const MAX_NESTING: usize = 64;
+
+impl Parser {
+    fn new(tokens: Vec<(Token, usize)>, caret_xor: bool) -> Parser {
+        Parser {
+            tokens,
+            pos: 0,
+            caret_xor,
+            depth: 0,
+        }
+    }
+
+    /// Whether the next token raises to a power
+    fn at_power(&self) -> bool {
+        match self.peek() {
+            Token::StarStar => true,
+            Token::Caret => !self.caret_xor,
+            _ => false,
+        }
+    }
+
+    /// Parse a left-associative chain of operators that all bind equally tightly
+    fn chain(
+        &mut self,
+        operand: fn(&mut Parser) -> Result<Expr, ParseError>,
+        operator: fn(&Parser) -> Option<char>,
+    ) -> Result<Expr, ParseError> {
+        let mut left = operand(self)?;
+        while let Some(op) = operator(self) {
+            self.advance();
+            let right = operand(self)?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError {
+            column: self.column(),
+            message,
+        }
+    }
+
+    /// Check for `name(a, b, ...) =` at the start of the line without consuming anything
+    fn at_function_definition(&self) -> bool {
+        let tokens: Vec<&Token> = self.tokens.iter().map(|(token, _)| token).collect();
+        if !matches!(tokens.as_slice(), [Token::Ident(_), Token::LParen, ..]) {
+            return false;
+        }
+        let mut i = 2;
+        if tokens[i] != &Token::RParen {
+            loop {
+                if !matches!(tokens[i], Token::Ident(_)) {
+                    return false;
+                }
+                i += 1;
+                match tokens[i] {
+                    Token::Comma => i += 1,
+                    Token::RParen => break,
+                    _ => return false,
+                }
+            }
+        }
+        tokens.get(i + 1) == Some(&&Token::Equals)
+    }
+
+    fn function_definition(&mut self) -> Result<Statement, ParseError> {
+        let Token::Ident(name) = self.advance() else {
+            unreachable!("checked by at_function_definition");
+        };
+        self.advance();
+
+        let mut params = Vec::new();
+        while let Token::Ident(param) = self.advance() {
+            if params.contains(&param) {
+                let column = self.tokens[self.pos - 1].1;
+                return Err(ParseError {
+                    column,
+                    message: format!("parameter '{}' is listed twice", param),
+                });
+            }
+            params.push(param);
+            if *self.peek() == Token::Comma {
+                self.advance();
+            }
+        }
+        // The closing ')' was consumed by the loop above, skip the '='
+        self.advance();
+        Ok(Statement::Function(name, params, self.expr()?))
+    }
+
+    /// Parse a complete line and make sure nothing is left over
+    fn parse(&mut self) -> Result<Statement, ParseError> {
+        let statement = if self.at_function_definition() {
+            self.function_definition()?
+        } else if *self.peek() == Token::Ident("let".to_string()) {
+            self.advance();
+            let name = match self.advance() {
+                Token::Ident(name) if name != "ans" && !is_keyword(&name) => name,
+                other => {
+                    let column = self.tokens[self.pos - 1].1;
+                    return Err(ParseError {
+                        column,
+                        message: format!("expected a variable name, found {}", other),
+                    });
+                }
+            };
+            if *self.peek() != Token::Equals {
+                return Err(self.error(format!("expected '=', found {}", self.peek())));
+            }
+            self.advance();
+            Statement::Let(name, self.expr()?)
+        } else {
+            Statement::Expr(self.expr()?)
+        };
+
+        if *self.peek() != Token::End {
+            return Err(self.error(format!("unexpected {}", self.peek())));
+        }
+        Ok(statement)
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut value = self.bit_or()?;
+        while matches!(self.peek(), Token::Ident(word) if word == "in" || word == "to") {
+            self.advance();
+            let unit = self.bit_or()?;
+            value = Expr::Convert(Box::new(value), Box::new(unit));
+        }
+        Ok(value)
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        self.chain(Parser::bit_xor, |parser| {
+            (*parser.peek() == Token::Pipe).then_some('|')
+        })
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, ParseError> {
+        self.chain(Parser::bit_and, |parser| match parser.peek() {
+            Token::Ident(word) if word == "xor" => Some('x'),
+            Token::Caret if parser.caret_xor => Some('x'),
+            _ => None,
+        })
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        self.chain(Parser::shift, |parser| {
+            (*parser.peek() == Token::Amp).then_some('&')
+        })
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        self.chain(Parser::sum, |parser| match parser.peek() {
+            Token::Shl => Some('<'),
+            Token::Shr => Some('>'),
+            _ => None,
+        })
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Token::Plus => '+',
+                Token::Minus => '-',
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.term()?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Star => '*',
+                Token::Slash => '/',
+                Token::DotStar => 'o',
+                Token::DotSlash => 'd',
+                Token::Percent => '%',
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.unary()?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+    }
+
+    /// Every nested bracket, sign or exponent passes through here, so this
+    /// is where the depth is counted
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error(format!(
+                "expression is nested more than {} levels deep",
+                MAX_NESTING
+            )));
+        }
+        self.depth += 1;
+        let result = self.signed();
+        self.depth -= 1;
+        result
+    }
+
+    fn signed(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Token::Minus => {
+                self.advance();
+                let operand = self.unary()?;
+                Ok(Expr::Negate(Box::new(operand)))
+            }
+            Token::Tilde => {
+                self.advance();
+                let operand = self.unary()?;
+                Ok(Expr::BitNot(Box::new(operand)))
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.postfix()?;
+        if !self.at_power() {
+            return Ok(base);
+        }
+        self.advance();
+        // The exponent goes back through `unary` so `2^-1` and `2^3^2` work
+        let exponent = self.unary()?;
+        Ok(Expr::Binary(Box::new(base), '^', Box::new(exponent)))
+    }
+
+    fn postfix(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        while *self.peek() == Token::Bang {
+            self.advance();
+            expr = Expr::Factorial(Box::new(expr));
+        }
+        Ok(expr)
+    }
+
+    /// Comma-separated call arguments, after the opening '(' has been consumed
+    fn arguments(&mut self, open_column: usize) -> Result<Vec<Expr>, ParseError> {
+        let mut args = Vec::new();
+        if *self.peek() != Token::RParen {
+            loop {
+                let arg = self.expr()?;
+                if *self.peek() == Token::Equals {
+                    self.advance();
+                    let right = self.expr()?;
+                    args.push(Expr::Binary(Box::new(arg), '=', Box::new(right)));
+                } else {
+                    args.push(arg);
+                }
+                if *self.peek() != Token::Comma {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        if *self.peek() != Token::RParen {
+            return Err(self.error(format!(
+                "expected ',' or ')' to close '(' at column {}",
+                open_column
+            )));
+        }
+        self.advance();
+        Ok(args)
+    }
+
+    /// A unit written right after a number literal, as in `5 km` or `3 m^2`
+    fn attached_unit(&mut self, number: Expr) -> Result<Expr, ParseError> {
+        let is_unit = match (self.peek(), self.tokens.get(self.pos + 1)) {
+            (Token::Ident(name), Some((next, _))) => {
+                Unit::is_unit_name(name) && *next != Token::LParen
+            }
+            _ => false,
+        };
+        if !is_unit {
+            return Ok(number);
+        }
+
+        let Token::Ident(name) = self.advance() else {
+            unreachable!("checked above");
+        };
+        let mut unit = Expr::Variable(name);
+        if self.at_power() {
+            self.advance();
+            unit = Expr::Binary(Box::new(unit), '^', Box::new(self.unary()?));
+        }
+        Ok(Expr::Binary(Box::new(number), '*', Box::new(unit)))
+    }
+
+    /// A name written straight after a number, with no space, multiplies it:
+    /// `2x` is `2 * x` and `3sin(x)` is `3 * sin(x)`. Units come first, so
+    /// `2m` is still two metres. The tokenizer has already read any
+    /// exponent into the number, so `1e-3` never gets here as `1 * e`.
+    fn implicit_product(&mut self, number: Expr, end: usize) -> Result<Expr, ParseError> {
+        let adjacent = match self.peek() {
+            Token::Ident(name) => !is_keyword(name) && self.column() == end,
+            _ => false,
+        };
+        if !adjacent {
+            return Ok(number);
+        }
+        let factor = self.power()?;
+        Ok(Expr::Binary(Box::new(number), '*', Box::new(factor)))
+    }
+
+    /// The rows of a matrix literal, after the opening '[' has been consumed
+    fn matrix(&mut self) -> Result<Expr, ParseError> {
+        let open_column = self.tokens[self.pos - 1].1;
+        let mut rows: Vec<Vec<Expr>> = vec![Vec::new()];
+        loop {
+            let entry = self.expr()?;
+            rows.last_mut().expect("at least one row").push(entry);
+            match self.peek() {
+                Token::Comma => {}
+                Token::Semicolon => rows.push(Vec::new()),
+                Token::RBracket => break,
+                other => {
+                    return Err(self.error(format!(
+                        "expected ',', ';' or ']' to close '[' at column {}, found {}",
+                        open_column, other
+                    )))
+                }
+            }
+            self.advance();
+        }
+        let width = rows[0].len();
+        if let Some(index) = rows.iter().position(|row| row.len() != width) {
+            let entries = |n: usize| format!("{} {}", n, if n == 1 { "entry" } else { "entries" });
+            return Err(self.error(format!(
+                "row {} has {} but the first row has {}",
+                index + 1,
+                entries(rows[index].len()),
+                entries(width)
+            )));
+        }
+        self.advance();
+        Ok(Expr::Matrix(rows))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        match self.advance() {
+            Token::Number(n) => {
+                let end = self.tokens[self.pos - 1].1 + n.chars().count();
+                let number = self.attached_unit(Expr::Number(n))?;
+                self.implicit_product(number, end)
+            }
+            Token::Ident(name) if *self.peek() == Token::LParen => {
+                let open_column = self.column();
+                self.advance();
+                let args = self.arguments(open_column)?;
+                Ok(Expr::Call(name, args))
+            }
+            Token::Ident(name) => Ok(Expr::Variable(name)),
+            Token::Date(text) => Ok(Expr::Date(text)),
+            Token::Duration(text) => Ok(Expr::Duration(text)),
+            Token::History(index) => Ok(Expr::History(index)),
+            Token::LBracket => self.matrix(),
+            Token::LParen => {
+                let open_column = self.tokens[self.pos - 1].1;
+                let inner = self.expr()?;
+                if *self.peek() != Token::RParen {
+                    return Err(self.error(format!(
+                        "expected ')' to close '(' at column {}",
+                        open_column
+                    )));
+                }
+                self.advance();
+                Ok(inner)
+            }
+            Token::End => Err(self.error("unexpected end of input".to_string())),
+            other => {
+                let column = self.tokens[self.pos - 1].1;
+                Err(ParseError {
+                    column,
+                    message: format!("unexpected {}", other),
+                })
+            }
+        }
+    }
+}
+
+/// Parse a whole line into a statement; `caret_xor` makes `^` mean xor
+pub(crate) fn parse(input: &str, caret_xor: bool) -> Result<Statement, ParseError> {
+    let tokens = tokenize(input)?;
+    Parser::new(tokens, caret_xor).parse()
+}
@@ -0,0 +1,825 @@
+//! `plot()`: sampling functions of one variable and drawing them in the
+//! terminal, as SVG or as PNG. Everything is drawn by hand so the crate
+//! keeps its no-dependency promise; the PNG encoder writes uncompressed
+//! deflate blocks, which every viewer reads.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::backend::FloatBackend;
+use crate::error::CalcError;
+use crate::eval::{evaluate_in, Env, Scope};
+use crate::number::Number;
+use crate::parser::Expr;
+use crate::solve::free_names;
+use crate::units::Value;
+
+/// Evenly spaced samples taken before refining
+const BASE_SAMPLES: usize = 200;
+
+/// How many times an interval may be halved while refining
+const MAX_DEPTH: usize = 10;
+
+/// A midpoint this far off the straight line between its neighbours,
+/// as a fraction of the height of the plot, is worth refining
+const FLATNESS: f64 = 0.002;
+
+/// A jump of this fraction of the height across the narrowest interval is
+/// a discontinuity, such as the pole of `tan` or a step of `floor`
+const JUMP: f64 = 0.05;
+
+/// Size of the chart printed in the terminal, in character cells
+const COLUMNS: usize = 60;
+const ROWS: usize = 15;
+
+/// Markers that tell functions apart when several share a text chart
+const MARKERS: [char; 5] = ['*', '+', 'o', 'x', '#'];
+
+/// Line colours for SVG and PNG, in the order the functions were given
+const COLOURS: [(u8, u8, u8); 5] = [
+    (31, 119, 180),
+    (214, 39, 40),
+    (44, 160, 44),
+    (255, 127, 14),
+    (148, 103, 189),
+];
+
+/// Ticks along each axis of an image
+const TICKS: usize = 5;
+
+/// A point on a curve, or a position in pixels
+type Point = (f64, f64);
+
+/// Functions sampled over a range, ready to be drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plot {
+    /// The arguments of the `plot()` call, so it can be written back out
+    call: Vec<Expr>,
+    variable: String,
+    x_range: (f64, f64),
+    y_range: (f64, f64),
+    /// For each function, the unbroken pieces of its curve
+    curves: Vec<Vec<Vec<Point>>>,
+}
+
+/// `plot(f, g, ..., start, end)`. The functions share one free variable,
+/// `x` unless they name another.
+pub(crate) fn plot(args: &[Expr], env: &Env, scope: &Scope) -> Result<Value, CalcError> {
+    if args.len() < 3 {
+        return Err(CalcError::Domain(
+            "plot() takes functions followed by the start and end of the range, as in plot(sin(x), -pi, pi)"
+                .to_string(),
+        ));
+    }
+    let (functions, bounds) = args.split_at(args.len() - 2);
+    let bound = |expr: &Expr| -> Result<f64, CalcError> {
+        let value = evaluate_in(expr, env, scope)?.operand()?;
+        Ok(value.plain("plot()")?.to_f64())
+    };
+    let (start, end) = (bound(&bounds[0])?, bound(&bounds[1])?);
+    if start >= end {
+        return Err(CalcError::Domain(format!(
+            "plot() needs a range that starts below where it ends, found {} to {}",
+            start, end
+        )));
+    }
+    let variable = match free_names(functions, env, scope).as_slice() {
+        [] => "x".to_string(),
+        [name] => name.clone(),
+        names => {
+            return Err(CalcError::Domain(format!(
+                "plot() draws functions of one variable, found {}",
+                names.join(", ")
+            )))
+        }
+    };
+
+    let mut float = Env::new(Box::new(FloatBackend));
+    float.variables = env.variables.clone();
+    float.functions = env.functions.clone();
+    float.angle = env.angle;
+    let samplers: Vec<Sampler> = functions
+        .iter()
+        .map(|expr| Sampler {
+            expr,
+            variable: &variable,
+            outer: &scope.locals,
+            env: &float,
+            depth: scope.depth,
+        })
+        .collect();
+
+    let step = (end - start) / BASE_SAMPLES as f64;
+    let xs: Vec<f64> = (0..=BASE_SAMPLES)
+        .map(|i| start + step * i as f64)
+        .collect();
+    let base = samplers
+        .iter()
+        .map(|sampler| {
+            xs.iter()
+                .map(|&x| Ok((x, sampler.at(x)?)))
+                .collect::<Result<Vec<_>, CalcError>>()
+        })
+        .collect::<Result<Vec<_>, CalcError>>()?;
+    let y_range = y_range(base.iter().flatten().filter_map(|&(_, y)| y)).ok_or_else(|| {
+        CalcError::Domain(format!(
+            "plot() found no point between {} and {} where the functions are defined",
+            start, end
+        ))
+    })?;
+
+    let height = y_range.1 - y_range.0;
+    let curves = samplers
+        .iter()
+        .zip(&base)
+        .map(|(sampler, samples)| {
+            let mut points = vec![samples[0]];
+            for pair in samples.windows(2) {
+                sampler.refine(pair[0], pair[1], 0, height, &mut points)?;
+            }
+            Ok(pieces(points))
+        })
+        .collect::<Result<Vec<_>, CalcError>>()?;
+
+    Ok(Value::Plot(Box::new(Plot {
+        call: args.to_vec(),
+        variable,
+        x_range: (start, end),
+        y_range,
+        curves,
+    })))
+}
+
+/// The height of the plot. Functions that shoot off to infinity, like `tan`
+/// near its poles, would flatten everything else, so when the extremes are
+/// far outside where most samples lie the plot shows the middle 90%.
+fn y_range(ys: impl Iterator<Item = f64>) -> Option<(f64, f64)> {
+    let mut ys: Vec<f64> = ys.collect();
+    if ys.is_empty() {
+        return None;
+    }
+    ys.sort_by(f64::total_cmp);
+    let (low, high) = (ys[0], ys[ys.len() - 1]);
+    let at = |fraction: f64| ys[((ys.len() - 1) as f64 * fraction).round() as usize];
+    let (typical_low, typical_high) = (at(0.05), at(0.95));
+    let (low, high) = if high - low > 10.0 * (typical_high - typical_low) {
+        let margin = (typical_high - typical_low) * 0.25;
+        (typical_low - margin, typical_high + margin)
+    } else {
+        (low, high)
+    };
+    if high - low <= f64::EPSILON * low.abs().max(1.0) {
+        return Some((low - 1.0, high + 1.0));
+    }
+    Some((low, high))
+}
+
+/// Split a run of samples into pieces wherever the function is undefined
+fn pieces(points: Vec<(f64, Option<f64>)>) -> Vec<Vec<Point>> {
+    let mut pieces = vec![Vec::new()];
+    for (x, y) in points {
+        match y {
+            Some(y) => pieces.last_mut().expect("never empty").push((x, y)),
+            None if pieces.last().is_some_and(|piece| !piece.is_empty()) => pieces.push(Vec::new()),
+            None => {}
+        }
+    }
+    pieces.retain(|piece| !piece.is_empty());
+    pieces
+}
+
+/// One function being sampled
+struct Sampler<'a> {
+    expr: &'a Expr,
+    variable: &'a str,
+    /// Parameters of the enclosing user function, if any
+    outer: &'a BTreeMap<&'a str, Value>,
+    env: &'a Env,
+    depth: usize,
+}
+
+impl Sampler<'_> {
+    /// The function at `x`, or `None` where it is undefined
+    fn at(&self, x: f64) -> Result<Option<f64>, CalcError> {
+        let mut locals = self.outer.clone();
+        locals.insert(self.variable, Value::Number(Number::Float(x)));
+        let scope = Scope {
+            locals,
+            depth: self.depth,
+        };
+        match evaluate_in(self.expr, self.env, &scope).and_then(Value::operand) {
+            Ok(value) => Ok(Some(value.plain("plot()")?.to_f64())),
+            Err(CalcError::Domain(_) | CalcError::DivisionByZero | CalcError::Overflow(_)) => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Add the samples after `left` up to and including `right`, halving
+    /// the interval while the curve bends or crosses the edge of where it is
+    /// defined. A jump that survives every halving breaks the line there.
+    fn refine(
+        &self,
+        left: (f64, Option<f64>),
+        right: (f64, Option<f64>),
+        depth: usize,
+        height: f64,
+        points: &mut Vec<(f64, Option<f64>)>,
+    ) -> Result<(), CalcError> {
+        if depth < MAX_DEPTH {
+            let x = (left.0 + right.0) / 2.0;
+            let middle = (x, self.at(x)?);
+            let smooth = match (left.1, middle.1, right.1) {
+                (Some(a), Some(m), Some(b)) => (m - (a + b) / 2.0).abs() <= FLATNESS * height,
+                (None, None, None) => true,
+                _ => false,
+            };
+            if !smooth {
+                self.refine(left, middle, depth + 1, height, points)?;
+                return self.refine(middle, right, depth + 1, height, points);
+            }
+        } else if let (Some(a), Some(b)) = (left.1, right.1) {
+            if (b - a).abs() > JUMP * height {
+                points.push(((left.0 + right.0) / 2.0, None));
+            }
+        }
+        points.push(right);
+        Ok(())
+    }
+}
+
+impl Plot {
+    /// The `plot(...)` call that draws this plot again
+    pub(crate) fn to_expr(&self) -> Expr {
+        Expr::Call("plot".to_string(), self.call.clone())
+    }
+
+    /// `plot of sin(x) for x from -3.14 to 3.14`
+    pub fn title(&self) -> String {
+        format!(
+            "plot of {} for {} from {} to {}",
+            self.labels().join(", "),
+            self.variable,
+            label(self.x_range.0),
+            label(self.x_range.1)
+        )
+    }
+
+    /// The functions as they were typed
+    fn labels(&self) -> Vec<String> {
+        self.call[..self.call.len() - 2]
+            .iter()
+            .map(|expr| expr.to_string())
+            .collect()
+    }
+
+    /// The curves cut to the height of the plot, as runs of points to be
+    /// joined by straight lines, for each function in turn
+    fn runs(&self) -> Vec<Vec<Vec<Point>>> {
+        let (low, high) = self.y_range;
+        self.curves
+            .iter()
+            .map(|curve| {
+                let mut runs: Vec<Vec<Point>> = Vec::new();
+                for piece in curve {
+                    if let [(x, y)] = piece[..] {
+                        if (low..=high).contains(&y) {
+                            runs.push(vec![(x, y)]);
+                        }
+                    }
+                    let mut open = false;
+                    for pair in piece.windows(2) {
+                        let Some((from, to, cut_end)) = clip(pair[0], pair[1], low, high) else {
+                            open = false;
+                            continue;
+                        };
+                        if !open || runs.last().and_then(|run| run.last()) != Some(&from) {
+                            runs.push(vec![from]);
+                        }
+                        runs.last_mut().expect("just pushed").push(to);
+                        open = !cut_end;
+                    }
+                }
+                runs
+            })
+            .collect()
+    }
+
+    /// Map a point to `width` by `height` pixels, `y` growing downwards
+    fn place(&self, (x, y): Point, width: f64, height: f64) -> Point {
+        let (start, end) = self.x_range;
+        let (low, high) = self.y_range;
+        (
+            (x - start) / (end - start) * width,
+            (high - y) / (high - low) * height,
+        )
+    }
+
+    /// Visit the dots along every curve on a `width` by `height` grid,
+    /// with the index of the function each belongs to
+    fn trace(&self, width: usize, height: usize, mut visit: impl FnMut(usize, usize, usize)) {
+        let (w, h) = ((width - 1) as f64, (height - 1) as f64);
+        for (index, runs) in self.runs().iter().enumerate() {
+            for run in runs {
+                let mut previous: Option<Point> = None;
+                for &point in run {
+                    let (x1, y1) = self.place(point, w, h);
+                    let (x0, y0) = previous.unwrap_or((x1, y1));
+                    let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+                    for step in 0..=steps {
+                        let t = step as f64 / steps as f64;
+                        let (x, y) = (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+                        visit(
+                            (x.round().max(0.0) as usize).min(width - 1),
+                            (y.round().max(0.0) as usize).min(height - 1),
+                            index,
+                        );
+                    }
+                    previous = Some((x1, y1));
+                }
+            }
+        }
+    }
+
+    /// The chart as text `columns` wide and `rows` high, plus labels. One
+    /// function is drawn with Braille dots, eight to a character; several
+    /// are drawn with a different marker each so they can be told apart.
+    pub fn to_text(&self, columns: usize, rows: usize) -> String {
+        let labels = self.labels();
+        let mut cells = vec![vec![' '; columns]; rows];
+        if labels.len() == 1 {
+            let mut dots = vec![vec![0u8; columns]; rows];
+            self.trace(columns * 2, rows * 4, |x, y, _| {
+                const BITS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                dots[y / 4][x / 2] |= BITS[y % 4][x % 2];
+            });
+            for (row, bits) in cells.iter_mut().zip(&dots) {
+                for (cell, &bit) in row.iter_mut().zip(bits) {
+                    *cell = char::from_u32(0x2800 + u32::from(bit)).expect("Braille block");
+                }
+            }
+        } else {
+            self.trace(columns, rows, |x, y, index| {
+                cells[y][x] = MARKERS[index % MARKERS.len()];
+            });
+        }
+
+        let (low, high) = self.y_range;
+        let mut side = vec![String::new(); rows];
+        side[0] = label(high);
+        side[rows - 1] = label(low);
+        if low < 0.0 && high > 0.0 {
+            let zero = ((high / (high - low)) * (rows - 1) as f64).round() as usize;
+            if zero > 0 && zero < rows - 1 {
+                side[zero] = "0".to_string();
+            }
+        }
+        let margin = side
+            .iter()
+            .map(|text| text.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut lines: Vec<String> = side
+            .iter()
+            .zip(&cells)
+            .map(|(text, row)| {
+                format!("{:>margin$} │{}", text, row.iter().collect::<String>())
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        lines.push(format!("{:>margin$} └{}", "", "─".repeat(columns)));
+
+        let (start, end) = (label(self.x_range.0), label(self.x_range.1));
+        let gap = columns.saturating_sub(start.len() + end.len() + self.variable.len());
+        lines.push(format!(
+            "{:>margin$}  {}{:left$}{}{:right$}{}",
+            "",
+            start,
+            "",
+            self.variable,
+            "",
+            end,
+            left = gap / 2,
+            right = gap - gap / 2,
+        ));
+        let legend: Vec<String> = if labels.len() == 1 {
+            labels
+        } else {
+            labels
+                .iter()
+                .enumerate()
+                .map(|(i, text)| format!("{} {}", MARKERS[i % MARKERS.len()], text))
+                .collect()
+        };
+        lines.push(format!("{:>margin$}  {}", "", legend.join("   ")));
+        lines.join("\n")
+    }
+
+    /// The chart as an SVG document `width` by `height` pixels
+    pub fn to_svg(&self, width: u32, height: u32) -> String {
+        let frame = Frame::new(width, height);
+        let (left, top, w, h) = (frame.left, frame.top, frame.width, frame.height);
+        let mut svg = vec![
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="sans-serif" font-size="12">"#,
+                width, height, width, height
+            ),
+            r#"<rect width="100%" height="100%" fill="white"/>"#.to_string(),
+            format!(
+                r#"<clipPath id="area"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath>"#,
+                left, top, w, h
+            ),
+        ];
+        let (x_ticks, y_ticks) = self.ticks(&frame);
+        for (y, text) in y_ticks {
+            svg.push(format!(
+                r##"<line x1="{0}" y1="{1:.1}" x2="{2}" y2="{1:.1}" stroke="#eee"/><text x="{3}" y="{4:.1}" text-anchor="end">{5}</text>"##,
+                left,
+                y,
+                left + w,
+                left - 6.0,
+                y + 4.0,
+                text
+            ));
+        }
+        for (x, text) in x_ticks {
+            svg.push(format!(
+                r##"<line x1="{0:.1}" y1="{1}" x2="{0:.1}" y2="{2}" stroke="#eee"/><text x="{0:.1}" y="{3}" text-anchor="middle">{4}</text>"##,
+                x,
+                top,
+                top + h,
+                top + h + 16.0,
+                text
+            ));
+        }
+        for (x1, y1, x2, y2) in self.zero_axes(&frame) {
+            svg.push(format!(
+                r##"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="#999"/>"##,
+                x1, y1, x2, y2
+            ));
+        }
+        svg.push(format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="black"/>"#,
+            left, top, w, h
+        ));
+        svg.push(format!(
+            r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+            left + w / 2.0,
+            top + h + 34.0,
+            escape(&self.variable)
+        ));
+
+        for (index, runs) in self.runs().iter().enumerate() {
+            let (r, g, b) = COLOURS[index % COLOURS.len()];
+            for run in runs {
+                let points: Vec<String> = run
+                    .iter()
+                    .map(|&point| {
+                        let (x, y) = self.place(point, w, h);
+                        format!("{:.2},{:.2}", left + x, top + y)
+                    })
+                    .collect();
+                svg.push(format!(
+                    r#"<polyline fill="none" stroke="rgb({},{},{})" stroke-width="1.5" clip-path="url(#area)" points="{}"/>"#,
+                    r,
+                    g,
+                    b,
+                    points.join(" ")
+                ));
+            }
+        }
+        for (index, text) in self.labels().iter().enumerate() {
+            let (r, g, b) = COLOURS[index % COLOURS.len()];
+            let y = top + 16.0 + 16.0 * index as f64;
+            svg.push(format!(
+                r#"<line x1="{0:.1}" y1="{1:.1}" x2="{2:.1}" y2="{1:.1}" stroke="rgb({3},{4},{5})" stroke-width="2"/><text x="{6:.1}" y="{7:.1}">{8}</text>"#,
+                left + w - 150.0,
+                y - 4.0,
+                left + w - 130.0,
+                r,
+                g,
+                b,
+                left + w - 124.0,
+                y,
+                escape(text)
+            ));
+        }
+        svg.push("</svg>".to_string());
+        svg.join("\n") + "\n"
+    }
+
+    /// The chart as a PNG image `width` by `height` pixels. Axis numbers
+    /// are drawn with a small built-in font of digits; the legend is a
+    /// coloured swatch per function, in the order they were given.
+    pub fn to_png(&self, width: u32, height: u32) -> Vec<u8> {
+        let frame = Frame::new(width, height);
+        let mut canvas = Canvas::new(width as usize, height as usize);
+        let (left, top, w, h) = (frame.left, frame.top, frame.width, frame.height);
+        let (right, bottom) = (left + w, top + h);
+        let (x_ticks, y_ticks) = self.ticks(&frame);
+        for (y, text) in y_ticks {
+            canvas.line((left, y), (right, y), (238, 238, 238));
+            canvas.text(left - 6.0, y, &text, true);
+        }
+        for (x, text) in x_ticks {
+            canvas.line((x, top), (x, bottom), (238, 238, 238));
+            canvas.text(x, bottom + 12.0, &text, false);
+        }
+        for (x1, y1, x2, y2) in self.zero_axes(&frame) {
+            canvas.line((x1, y1), (x2, y2), (153, 153, 153));
+        }
+        for (index, runs) in self.runs().iter().enumerate() {
+            let colour = COLOURS[index % COLOURS.len()];
+            for run in runs {
+                for pair in run.windows(2) {
+                    let (x1, y1) = self.place(pair[0], w, h);
+                    let (x2, y2) = self.place(pair[1], w, h);
+                    canvas.thick_line((left + x1, top + y1), (left + x2, top + y2), colour);
+                }
+            }
+            let y = top + 12.0 + 10.0 * index as f64;
+            canvas.thick_line((right - 40.0, y), (right - 12.0, y), colour);
+        }
+        canvas.line((left, top), (right, top), (0, 0, 0));
+        canvas.line((left, bottom), (right, bottom), (0, 0, 0));
+        canvas.line((left, top), (left, bottom), (0, 0, 0));
+        canvas.line((right, top), (right, bottom), (0, 0, 0));
+        canvas.to_png()
+    }
+
+    /// Evenly spaced ticks with their labels, as pixel positions along
+    /// the `x` axis and then up the `y` axis
+    fn ticks(&self, frame: &Frame) -> (Ticks, Ticks) {
+        let (start, end) = self.x_range;
+        let (low, high) = self.y_range;
+        (0..=TICKS)
+            .map(|i| {
+                let fraction = i as f64 / TICKS as f64;
+                (
+                    (
+                        frame.left + frame.width * fraction,
+                        label(start + (end - start) * fraction),
+                    ),
+                    (
+                        frame.top + frame.height * (1.0 - fraction),
+                        label(low + (high - low) * fraction),
+                    ),
+                )
+            })
+            .unzip()
+    }
+
+    /// The lines `y = 0` and `x = 0`, where they cross the plot
+    fn zero_axes(&self, frame: &Frame) -> Vec<(f64, f64, f64, f64)> {
+        let (x0, y0) = self.place((0.0, 0.0), frame.width, frame.height);
+        let mut axes = Vec::new();
+        if (0.0..=frame.height).contains(&y0) {
+            let y = frame.top + y0;
+            axes.push((frame.left, y, frame.left + frame.width, y));
+        }
+        if (0.0..=frame.width).contains(&x0) {
+            let x = frame.left + x0;
+            axes.push((x, frame.top, x, frame.top + frame.height));
+        }
+        axes
+    }
+}
+
+/// The title line above the chart
+impl fmt::Display for Plot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.title())?;
+        write!(f, "{}", self.to_text(COLUMNS, ROWS))
+    }
+}
+
+/// Cut the line from `a` to `b` to the band `low..=high`. Returns the
+/// visible part and whether it stops short of `b`.
+fn clip(a: Point, b: Point, low: f64, high: f64) -> Option<(Point, Point, bool)> {
+    let rise = b.1 - a.1;
+    let (mut enter, mut leave) = (0.0f64, 1.0f64);
+    if rise == 0.0 {
+        if !(low..=high).contains(&a.1) {
+            return None;
+        }
+    } else {
+        let (t1, t2) = ((low - a.1) / rise, (high - a.1) / rise);
+        enter = enter.max(t1.min(t2));
+        leave = leave.min(t1.max(t2));
+        if enter > leave {
+            return None;
+        }
+    }
+    let at = |t: f64| (a.0 + (b.0 - a.0) * t, a.1 + rise * t);
+    let from = if enter == 0.0 { a } else { at(enter) };
+    let to = if leave == 1.0 { b } else { at(leave) };
+    Some((from, to, leave < 1.0))
+}
+
+/// An axis number with three significant digits: `3.14`, `-0.5`, `1.2e6`
+fn label(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude < 1e-9 {
+        return "0".to_string();
+    }
+    if !(1e-3..1e5).contains(&magnitude) {
+        return format!("{:.2e}", value);
+    }
+    let places = (2 - magnitude.log10().floor() as i32).max(0) as usize;
+    let text = format!("{:.*}", places, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Pixel positions along an axis with the number shown at each
+type Ticks = Vec<(f64, String)>;
+
+/// Where the plotting area sits inside an image, leaving room for labels
+struct Frame {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Frame {
+    fn new(width: u32, height: u32) -> Frame {
+        let (left, top) = (64.0, 16.0);
+        Frame {
+            left,
+            top,
+            width: (f64::from(width) - left - 16.0).max(1.0),
+            height: (f64::from(height) - top - 48.0).max(1.0),
+        }
+    }
+}
+
+/// A 3 by 5 font for axis numbers, one bit per pixel, left to right
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('0', [7, 5, 5, 5, 7]),
+    ('1', [2, 6, 2, 2, 7]),
+    ('2', [7, 1, 7, 4, 7]),
+    ('3', [7, 1, 7, 1, 7]),
+    ('4', [5, 5, 7, 1, 1]),
+    ('5', [7, 4, 7, 1, 7]),
+    ('6', [7, 4, 7, 5, 7]),
+    ('7', [7, 1, 1, 1, 1]),
+    ('8', [7, 5, 7, 5, 7]),
+    ('9', [7, 5, 7, 1, 7]),
+    ('-', [0, 0, 7, 0, 0]),
+    ('+', [0, 2, 7, 2, 0]),
+    ('.', [0, 0, 0, 0, 2]),
+    ('e', [0, 7, 7, 4, 7]),
+];
+
+/// Pixels the font is scaled up by
+const GLYPH_SCALE: usize = 2;
+
+/// An RGB image being drawn
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![255; width * height * 3],
+        }
+    }
+
+    fn set(&mut self, x: f64, y: f64, (r, g, b): (u8, u8, u8)) {
+        let (x, y) = (x.round(), y.round());
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return;
+        }
+        let at = (y as usize * self.width + x as usize) * 3;
+        self.pixels[at..at + 3].copy_from_slice(&[r, g, b]);
+    }
+
+    fn line(&mut self, (x0, y0): Point, (x1, y1): Point, colour: (u8, u8, u8)) {
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let t = step as f64 / steps as f64;
+            self.set(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t, colour);
+        }
+    }
+
+    /// A line two pixels wide, for curves
+    fn thick_line(&mut self, from: Point, to: Point, colour: (u8, u8, u8)) {
+        self.line(from, to, colour);
+        self.line((from.0, from.1 + 1.0), (to.0, to.1 + 1.0), colour);
+    }
+
+    /// Draw `text` centred on `x`, or ending at `x` when `right_aligned`,
+    /// with `y` as its middle
+    fn text(&mut self, x: f64, y: f64, text: &str, right_aligned: bool) {
+        let advance = (4 * GLYPH_SCALE) as f64;
+        let width = advance * text.chars().count() as f64 - GLYPH_SCALE as f64;
+        let mut left = if right_aligned {
+            x - width
+        } else {
+            x - width / 2.0
+        };
+        let top = y - (5 * GLYPH_SCALE / 2) as f64;
+        for c in text.chars() {
+            if let Some((_, rows)) = GLYPHS.iter().find(|(glyph, _)| *glyph == c) {
+                for (row, bits) in rows.iter().enumerate() {
+                    for col in 0..3 {
+                        if bits & (4 >> col) == 0 {
+                            continue;
+                        }
+                        for dy in 0..GLYPH_SCALE {
+                            for dx in 0..GLYPH_SCALE {
+                                self.set(
+                                    left + (col * GLYPH_SCALE + dx) as f64,
+                                    top + (row * GLYPH_SCALE + dy) as f64,
+                                    (0, 0, 0),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            left += advance;
+        }
+    }
+
+    /// Encode as PNG: 8-bit RGB, every row unfiltered, stored in
+    /// uncompressed deflate blocks
+    fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = raw.chunks(u16::MAX as usize).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            let length = block.len() as u16;
+            zlib.push(u8::from(i == blocks.len() - 1));
+            zlib.extend_from_slice(&length.to_le_bytes());
+            zlib.extend_from_slice(&(!length).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering and no interlace
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        for (kind, data) in [(b"IHDR", &header), (b"IDAT", &zlib), (b"IEND", &Vec::new())] {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let start = png.len();
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            let crc = crc32(&png[start..]);
+            png.extend_from_slice(&crc.to_be_bytes());
+        }
+        png
+    }
+}
+
+/// The checksum that ends every PNG chunk
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The checksum that ends a zlib stream
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + u32::from(byte)) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,599 @@
+//! `solve()`: exact answers for linear systems, numeric roots for the rest
+
+use std::collections::BTreeMap;
+
+use crate::backend::FloatBackend;
+use crate::datetime::CLOCK_NAMES;
+use crate::error::CalcError;
+use crate::eval::{check_arity, evaluate_in, expand, substitute, Env, Scope, CONSTANTS};
+use crate::number::{BigInt, Number, Rational};
+use crate::parser::Expr;
+use crate::symbolic::{constant_value, contains, differentiate, simplify};
+use crate::units::{Unit, Value};
+
+/// Points sampled on each side of zero when looking for sign changes
+const SCAN_STEPS: i32 = 2000;
+
+/// The scan reaches out to `±10^SCAN_DECADES`, with points packed closest near zero
+const SCAN_DECADES: f64 = 6.0;
+
+/// Newton steps tried from each starting point
+const MAX_ITERATIONS: usize = 100;
+
+/// Most roots listed for one unknown. Finding more, as periodic functions
+/// do, is reported rather than cut short, since a partial list looks whole.
+const MAX_ROOTS: usize = 10;
+
+/// Starting points for Newton's method on a system. Each unknown is offset
+/// a little from the next so symmetric systems do not start on a singular point.
+const STARTS: [f64; 7] = [1.0, -1.0, 0.5, 2.0, -2.0, 10.0, -10.0];
+
+/// `solve(equation, ..., unknown, ...)`. Equations are `left = right`, or a
+/// bare expression meaning `expr = 0`. Names listed after the equations are
+/// the unknowns; without them, every name that has no value is one.
+pub(crate) fn solve(args: &[Expr], env: &Env, scope: &Scope) -> Result<Value, CalcError> {
+    check_arity("solve", None, args.len())?;
+    // Trailing bare names are the unknowns, but the first argument is always an equation
+    let mut split = args.len();
+    while split > 1 && matches!(args[split - 1], Expr::Variable(_)) {
+        split -= 1;
+    }
+    let residuals = args[..split]
+        .iter()
+        .map(|equation| Ok(residual(expand(equation, env, scope, scope.depth)?)))
+        .collect::<Result<Vec<Expr>, CalcError>>()?;
+    let unknowns = if split < args.len() {
+        listed(&args[split..])?
+    } else {
+        free_names(&residuals, env, scope)
+    };
+    if unknowns.is_empty() {
+        return Err(CalcError::Domain(
+            "solve() found no unknowns; name them after the equations, as in solve(x^2 = 4, x)"
+                .to_string(),
+        ));
+    }
+
+    let solutions: Vec<Vec<Number>> = match linear_system(&residuals, &unknowns, env, scope)? {
+        Some(rows) => vec![eliminate(rows, &unknowns)?
+            .into_iter()
+            .map(Number::Rational)
+            .collect()],
+        None => {
+            let system = System::new(residuals, &unknowns, env, scope);
+            let found = if unknowns.len() == 1 {
+                system.roots()?.into_iter().map(|root| vec![root]).collect()
+            } else {
+                vec![system.solve()?]
+            };
+            found
+                .into_iter()
+                .map(|point| point.into_iter().map(Number::Float).collect())
+                .collect()
+        }
+    };
+
+    let backend = env.backend.as_ref();
+    let solutions = solutions
+        .into_iter()
+        .map(|point| {
+            unknowns
+                .iter()
+                .zip(point)
+                .map(|(name, n)| Ok((name.clone(), backend.convert(&n)?)))
+                .collect::<Result<Vec<_>, CalcError>>()
+        })
+        .collect::<Result<Vec<_>, CalcError>>()?;
+    Ok(Value::Solution(solutions))
+}
+
+/// Move everything to one side: `left = right` becomes `left - right`
+fn residual(equation: Expr) -> Expr {
+    match equation {
+        Expr::Binary(left, '=', right) => Expr::Binary(left, '-', right),
+        other => other,
+    }
+}
+
+/// The unknowns named at the end of the argument list
+fn listed(args: &[Expr]) -> Result<Vec<String>, CalcError> {
+    let mut unknowns: Vec<String> = Vec::new();
+    for arg in args {
+        let Expr::Variable(name) = arg else {
+            unreachable!("only bare names are split off as unknowns");
+        };
+        if unknowns.contains(name) {
+            return Err(CalcError::Domain(format!(
+                "solve() lists the unknown '{}' twice",
+                name
+            )));
+        }
+        unknowns.push(name.clone());
+    }
+    Ok(unknowns)
+}
+
+/// Names in the equations that have no value, in the order they appear.
+/// Unit names are never unknowns, so `2m = 4` is about metres.
+pub(crate) fn free_names(residuals: &[Expr], env: &Env, scope: &Scope) -> Vec<String> {
+    fn walk(expr: &Expr, names: &mut Vec<String>) {
+        match expr {
+            Expr::Variable(name) if !names.contains(name) => names.push(name.clone()),
+            Expr::Negate(inner) | Expr::BitNot(inner) | Expr::Factorial(inner) => {
+                walk(inner, names)
+            }
+            Expr::Binary(left, _, right) | Expr::Convert(left, right) => {
+                walk(left, names);
+                walk(right, names);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| walk(arg, names)),
+            Expr::Matrix(rows) => rows.iter().flatten().for_each(|entry| walk(entry, names)),
+            _ => {}
+        }
+    }
+
+    let mut names = Vec::new();
+    residuals.iter().for_each(|expr| walk(expr, &mut names));
+    names.retain(|name| {
+        !scope.locals.contains_key(name.as_str())
+            && !env.variables.contains_key(name)
+            && !CONSTANTS.iter().any(|(constant, _)| constant == name)
+            && !CLOCK_NAMES.contains(&name.as_str())
+            && !Unit::is_unit_name(name)
+            && name != "ans"
+    });
+    names
+}
+
+/// The coefficients of a linear system, one row per equation with the
+/// constant term last, or `None` if any equation is not linear in the unknowns
+fn linear_system(
+    residuals: &[Expr],
+    unknowns: &[String],
+    env: &Env,
+    scope: &Scope,
+) -> Result<Option<Vec<Vec<Rational>>>, CalcError> {
+    let zeros: Vec<Expr> = unknowns
+        .iter()
+        .map(|_| Expr::Number("0".to_string()))
+        .collect();
+    let mut rows = Vec::new();
+    for residual in residuals {
+        let residual = simplify(residual);
+        let mut row = Vec::new();
+        for unknown in unknowns {
+            let Ok(slope) = differentiate(&residual, unknown, env.angle) else {
+                return Ok(None);
+            };
+            let slope = simplify(&slope);
+            if unknowns.iter().any(|name| contains(&slope, name)) {
+                return Ok(None);
+            }
+            row.push(exact(&slope, env, scope)?);
+        }
+        row.push(exact(&substitute(&residual, unknowns, &zeros), env, scope)?);
+        rows.push(row);
+    }
+    Ok(Some(rows))
+}
+
+/// The exact value of an expression free of unknowns. Other names, such as
+/// `pi` or session variables, are evaluated in the current mode.
+fn exact(expr: &Expr, env: &Env, scope: &Scope) -> Result<Rational, CalcError> {
+    let expr = simplify(expr);
+    match constant_value(&expr) {
+        Some(value) => Ok(value),
+        None => evaluate_in(&expr, env, scope)?
+            .operand()?
+            .plain("solve()")?
+            .to_rational(),
+    }
+}
+
+/// Gauss-Jordan elimination over exact fractions. Each row reads
+/// `a1*x1 + ... + an*xn + c = 0`.
+fn eliminate(
+    mut rows: Vec<Vec<Rational>>,
+    unknowns: &[String],
+) -> Result<Vec<Rational>, CalcError> {
+    let n = unknowns.len();
+    let mut pivots = Vec::new();
+    for column in 0..n {
+        let row = pivots.len();
+        let Some(found) = (row..rows.len()).find(|&r| !rows[r][column].is_zero()) else {
+            continue;
+        };
+        rows.swap(row, found);
+        let pivot = rows[row][column].clone();
+        rows[row] = rows[row].iter().map(|a| a.div(&pivot)).collect();
+        let pivot_row = rows[row].clone();
+        for (other, cells) in rows.iter_mut().enumerate() {
+            let factor = cells[column].clone();
+            if other == row || factor.is_zero() {
+                continue;
+            }
+            for (cell, pivot_cell) in cells.iter_mut().zip(&pivot_row).skip(column) {
+                *cell = cell.sub(&factor.mul(pivot_cell));
+            }
+        }
+        pivots.push(column);
+    }
+
+    // Rows left without a pivot say `0 = c`, which only holds for `c = 0`
+    if rows[pivots.len()..].iter().any(|row| !row[n].is_zero()) {
+        return Err(CalcError::NoSolution(
+            "the equations contradict each other".to_string(),
+        ));
+    }
+    if pivots.len() < n {
+        let free = (0..n)
+            .filter(|column| !pivots.contains(column))
+            .map(|column| unknowns[column].clone())
+            .collect();
+        return Err(CalcError::Underdetermined(free));
+    }
+    let mut solution = vec![Rational::from_bigint(BigInt::from_i64(0)); n];
+    for (row, &column) in pivots.iter().enumerate() {
+        solution[column] = rows[row][n].neg();
+    }
+    Ok(solution)
+}
+
+/// Equations that are not linear, evaluated in float mode while searching
+struct System<'a> {
+    residuals: Vec<Expr>,
+    /// `partials[i][j]` is the derivative of equation `i` by unknown `j`,
+    /// or `None` when some derivative is not known symbolically
+    partials: Option<Vec<Vec<Expr>>>,
+    unknowns: &'a [String],
+    /// Parameters of the enclosing user function, if any
+    outer: Vec<(String, Value)>,
+    env: Env,
+    depth: usize,
+}
+
+impl<'a> System<'a> {
+    fn new(residuals: Vec<Expr>, unknowns: &'a [String], env: &Env, scope: &Scope) -> System<'a> {
+        let partials = residuals
+            .iter()
+            .map(|residual| {
+                unknowns
+                    .iter()
+                    .map(|unknown| {
+                        differentiate(residual, unknown, env.angle).map(|slope| simplify(&slope))
+                    })
+                    .collect::<Result<Vec<Expr>, CalcError>>()
+            })
+            .collect::<Result<Vec<_>, CalcError>>()
+            .ok();
+        let mut float = Env::new(Box::new(FloatBackend));
+        float.variables = env.variables.clone();
+        float.functions = env.functions.clone();
+        float.angle = env.angle;
+        float.tolerance = env.tolerance;
+        System {
+            residuals,
+            partials,
+            unknowns,
+            outer: scope
+                .locals
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            env: float,
+            depth: scope.depth,
+        }
+    }
+
+    /// Evaluate with the unknowns set to `point`. Points where the equations
+    /// are undefined, such as `ln(x)` for negative `x`, give `None`.
+    fn evaluate(&self, exprs: &[Expr], point: &[f64]) -> Result<Option<Vec<f64>>, CalcError> {
+        let mut locals: BTreeMap<&str, Value> = self
+            .outer
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        for (name, x) in self.unknowns.iter().zip(point) {
+            locals.insert(name.as_str(), Value::Number(Number::Float(*x)));
+        }
+        let scope = Scope {
+            locals,
+            depth: self.depth,
+        };
+        let mut values = Vec::new();
+        for expr in exprs {
+            match evaluate_in(expr, &self.env, &scope).and_then(Value::operand) {
+                Ok(value) => values.push(value.plain("solve()")?.to_f64()),
+                Err(CalcError::Domain(_) | CalcError::DivisionByZero | CalcError::Overflow(_)) => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Some(values))
+    }
+
+    /// The first equation at `x`, for scanning a single unknown
+    fn first_at(&self, x: f64) -> Result<Option<f64>, CalcError> {
+        Ok(self
+            .evaluate(&self.residuals[..1], &[x])?
+            .map(|values| values[0]))
+    }
+
+    /// Whether every equation holds at `point`, to within the tolerance
+    fn satisfied(&self, point: &[f64]) -> Result<bool, CalcError> {
+        let limit = self.env.tolerance.sqrt();
+        Ok(self
+            .evaluate(&self.residuals, point)?
+            .is_some_and(|values| values.iter().all(|value| value.abs() <= limit)))
+    }
+
+    fn close(&self, a: f64, b: f64) -> bool {
+        (a - b).abs() <= self.env.tolerance * a.abs().max(1.0)
+    }
+
+    /// Partial derivatives at `point`, symbolic when possible and central
+    /// differences otherwise
+    fn jacobian(
+        &self,
+        equations: usize,
+        point: &[f64],
+    ) -> Result<Option<Vec<Vec<f64>>>, CalcError> {
+        let mut rows = Vec::new();
+        for i in 0..equations {
+            let row = match &self.partials {
+                Some(partials) => self.evaluate(&partials[i], point)?,
+                None => {
+                    let mut row = Vec::new();
+                    for j in 0..point.len() {
+                        let h = 1e-7 * point[j].abs().max(1.0);
+                        let (mut above, mut below) = (point.to_vec(), point.to_vec());
+                        above[j] += h;
+                        below[j] -= h;
+                        let residual = &self.residuals[i..=i];
+                        match (
+                            self.evaluate(residual, &above)?,
+                            self.evaluate(residual, &below)?,
+                        ) {
+                            (Some(a), Some(b)) => row.push((a[0] - b[0]) / (2.0 * h)),
+                            _ => return Ok(None),
+                        }
+                    }
+                    Some(row)
+                }
+            };
+            match row {
+                Some(row) => rows.push(row),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(rows))
+    }
+
+    /// Newton's method on the first `equations` equations from `start`
+    fn newton(&self, equations: usize, start: Vec<f64>) -> Result<Option<Vec<f64>>, CalcError> {
+        let mut point = start;
+        for _ in 0..MAX_ITERATIONS {
+            let Some(values) = self.evaluate(&self.residuals[..equations], &point)? else {
+                return Ok(None);
+            };
+            let Some(jacobian) = self.jacobian(equations, &point)? else {
+                return Ok(None);
+            };
+            let Some(step) = solve_dense(jacobian, values) else {
+                return Ok(None);
+            };
+            let mut settled = true;
+            for (x, dx) in point.iter_mut().zip(step) {
+                settled &= dx.abs() <= self.env.tolerance * x.abs().max(1.0);
+                *x -= dx;
+            }
+            if settled {
+                return Ok(self.satisfied(&point)?.then_some(point));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every real root of a single unknown within the scanned range: sign
+    /// changes are narrowed down by bisection and polished with Newton's method,
+    /// and roots where the curve only touches zero are found with Newton's
+    /// method from the nearest sample.
+    fn roots(&self) -> Result<Vec<f64>, CalcError> {
+        let points: Vec<f64> = (-SCAN_STEPS..=SCAN_STEPS)
+            .map(|i| {
+                let t = i as f64 / SCAN_STEPS as f64;
+                t.signum() * (10f64.powf(SCAN_DECADES * t.abs()) - 1.0)
+            })
+            .collect();
+        let values = points
+            .iter()
+            .map(|&x| self.first_at(x))
+            .collect::<Result<Vec<Option<f64>>, CalcError>>()?;
+
+        let mut roots = Vec::new();
+        for i in 0..points.len() {
+            let Some(here) = values[i] else {
+                continue;
+            };
+            if here == 0.0 {
+                roots.push(points[i]);
+                continue;
+            }
+            if let Some(Some(next)) = values.get(i + 1) {
+                if here.signum() != next.signum() && *next != 0.0 {
+                    if let Some(root) = self.bisect(points[i], points[i + 1], here, *next)? {
+                        // A few Newton steps make up the last digits bisection leaves
+                        let polished = self.newton(1, vec![root])?;
+                        roots.push(polished.map_or(root, |point| point[0]));
+                    }
+                    continue;
+                }
+            }
+            let (Some(Some(before)), Some(Some(after))) =
+                (values.get(i.wrapping_sub(1)), values.get(i + 1))
+            else {
+                continue;
+            };
+            let dip = here.abs() < before.abs() && here.abs() <= after.abs();
+            if dip && before.signum() == here.signum() {
+                if let Some(root) = self.newton(1, vec![points[i]])? {
+                    roots.push(root[0]);
+                }
+            }
+        }
+
+        let mut found: Vec<f64> = Vec::new();
+        for root in roots {
+            let root = self.snap(root)?;
+            if self.satisfied(&[root])? && !found.iter().any(|&seen| self.near(seen, root)) {
+                found.push(root);
+            }
+        }
+        if found.is_empty() {
+            return Err(CalcError::NoSolution(format!(
+                "no real value of {} between -{} and {} satisfies the equation",
+                self.unknowns[0],
+                10f64.powf(SCAN_DECADES),
+                10f64.powf(SCAN_DECADES)
+            )));
+        }
+        if found.len() > MAX_ROOTS {
+            found.sort_by(|a, b| a.abs().total_cmp(&b.abs()));
+            found.truncate(MAX_ROOTS);
+            found.sort_by(f64::total_cmp);
+            let nearest: Vec<String> = found.iter().map(f64::to_string).collect();
+            return Err(CalcError::TooManyRoots(format!(
+                "{} has more than {} real values that satisfy the equation; \
+                 those nearest zero include {}",
+                self.unknowns[0],
+                MAX_ROOTS,
+                nearest.join(", ")
+            )));
+        }
+        found.sort_by(f64::total_cmp);
+        Ok(found)
+    }
+
+    /// Narrow a sign change down to a root. A pole such as the one in `1/x`
+    /// also changes sign, but the value there grows instead of shrinking.
+    fn bisect(
+        &self,
+        mut a: f64,
+        mut b: f64,
+        mut fa: f64,
+        fb: f64,
+    ) -> Result<Option<f64>, CalcError> {
+        let bound = fa.abs().min(fb.abs());
+        let mut mid = (a + b) / 2.0;
+        while !self.close(a, b) {
+            let Some(fm) = self.first_at(mid)? else {
+                return Ok(None);
+            };
+            if fm == 0.0 {
+                return Ok(Some(mid));
+            }
+            if fm.signum() == fa.signum() {
+                a = mid;
+                fa = fm;
+            } else {
+                b = mid;
+            }
+            let next = (a + b) / 2.0;
+            if next == mid {
+                break;
+            }
+            mid = next;
+        }
+        Ok(self
+            .first_at(mid)?
+            .filter(|fm| fm.abs() < bound)
+            .map(|_| mid))
+    }
+
+    /// Prefer a whole number when it fits the equation at least as well, so
+    /// `x^2 = 4` gives `2` rather than `1.9999999999999998`
+    fn snap(&self, root: f64) -> Result<f64, CalcError> {
+        let whole = root.round();
+        if whole == root || !self.near(whole, root) {
+            return Ok(root + 0.0);
+        }
+        match (self.first_at(whole)?, self.first_at(root)?) {
+            (Some(at_whole), Some(at_root)) if at_whole.abs() <= at_root.abs() => Ok(whole + 0.0),
+            _ => Ok(root + 0.0),
+        }
+    }
+
+    /// Whether two roots are the same one, found twice
+    fn near(&self, a: f64, b: f64) -> bool {
+        (a - b).abs() <= self.env.tolerance.sqrt() * a.abs().max(1.0)
+    }
+
+    /// One solution of a square system by Newton's method, trying a few
+    /// starting points
+    fn solve(&self) -> Result<Vec<f64>, CalcError> {
+        let n = self.unknowns.len();
+        if self.residuals.len() < n {
+            return Err(CalcError::Underdetermined(self.unknowns.to_vec()));
+        }
+        if self.residuals.len() > n {
+            return Err(CalcError::Domain(format!(
+                "solve() needs as many equations as unknowns unless they are linear, found {} for {}",
+                self.residuals.len(),
+                n
+            )));
+        }
+        for start in STARTS {
+            let point = (0..n).map(|j| start * (1.0 + 0.1 * j as f64)).collect();
+            if let Some(solution) = self.newton(n, point)? {
+                let snapped: Vec<f64> = solution
+                    .iter()
+                    .map(|&x| {
+                        if self.near(x.round(), x) {
+                            x.round() + 0.0
+                        } else {
+                            x
+                        }
+                    })
+                    .collect();
+                return Ok(if self.satisfied(&snapped)? {
+                    snapped
+                } else {
+                    solution
+                });
+            }
+        }
+        Err(CalcError::NoSolution(
+            "Newton's method did not converge from any starting point".to_string(),
+        ))
+    }
+}
+
+/// Solve `a * x = b` by Gaussian elimination with partial pivoting, or
+/// `None` if `a` is singular
+fn solve_dense(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for column in 0..n {
+        let pivot =
+            (column..n).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column] == 0.0 || !a[pivot][column].is_finite() {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let pivot_row = a[column].clone();
+        for row in column + 1..n {
+            let factor = a[row][column] / pivot_row[column];
+            for (cell, pivot_cell) in a[row].iter_mut().zip(&pivot_row).skip(column) {
+                *cell -= factor * pivot_cell;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|c| a[row][c] * x[c]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    x.iter().all(|v| v.is_finite()).then_some(x)
+}
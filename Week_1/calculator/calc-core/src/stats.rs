@@ -0,0 +1,204 @@
+//! Aggregate statistics over lists of numbers. A list is a vector literal
+//! such as `[4, 8, 15]`; a matrix counts every entry.
+
+use std::cmp::Ordering;
+
+use crate::backend::{Backend, FloatBackend};
+use crate::error::CalcError;
+use crate::number::Number;
+use crate::units::Value;
+
+/// Built-in functions that reduce a list to one number
+pub(crate) const STAT_FUNCTIONS: &[&str] = &[
+    "sum",
+    "product",
+    "mean",
+    "median",
+    "mode",
+    "variance",
+    "stdev",
+    "percentile",
+];
+
+/// `sum([1, 2, 3])` and `sum(1, 2, 3)` alike. `percentile` takes the list
+/// first and the percentage second.
+pub(crate) fn call_stat(
+    name: &str,
+    args: &[Value],
+    backend: &dyn Backend,
+) -> Result<Value, CalcError> {
+    if name == "percentile" {
+        let values = sample(name, &args[..1])?;
+        let wanted = args[1].plain(name)?;
+        return percentile(backend, values, wanted).map(Value::Number);
+    }
+    let values = sample(name, args)?;
+    let result = match name {
+        "sum" => sum(backend, &values)?,
+        "product" => product(backend, &values)?,
+        "mean" => mean(backend, &values)?,
+        "median" => median(backend, sorted(values))?,
+        "mode" => mode(sorted(values)),
+        "variance" => variance(backend, &values)?,
+        "stdev" => stdev(backend, &values)?,
+        _ => return Err(CalcError::UnknownFunction(name.to_string())),
+    };
+    Ok(Value::Number(result))
+}
+
+/// The numbers a function works on: the entries of a list or matrix, or
+/// the plain-number arguments themselves
+pub(crate) fn sample(name: &str, args: &[Value]) -> Result<Vec<Number>, CalcError> {
+    let mut values = Vec::new();
+    for arg in args {
+        match arg {
+            Value::Matrix(matrix) => {
+                for row in matrix.row_list() {
+                    values.extend(row.iter().cloned());
+                }
+            }
+            other => values.push(other.plain(name)?.clone()),
+        }
+    }
+    Ok(values)
+}
+
+fn sorted(mut values: Vec<Number>) -> Vec<Number> {
+    values.sort_by(Number::compare);
+    values
+}
+
+fn count(backend: &dyn Backend, values: &[Number]) -> Result<Number, CalcError> {
+    backend.literal(&values.len().to_string())
+}
+
+fn sum(backend: &dyn Backend, values: &[Number]) -> Result<Number, CalcError> {
+    values[1..]
+        .iter()
+        .try_fold(values[0].clone(), |total, x| backend.add(&total, x))
+}
+
+fn product(backend: &dyn Backend, values: &[Number]) -> Result<Number, CalcError> {
+    values[1..]
+        .iter()
+        .try_fold(values[0].clone(), |total, x| backend.mul(&total, x))
+}
+
+fn mean(backend: &dyn Backend, values: &[Number]) -> Result<Number, CalcError> {
+    backend.div(&sum(backend, values)?, &count(backend, values)?)
+}
+
+/// The middle value, or the mean of the two middle values for an even count
+fn median(backend: &dyn Backend, sorted: Vec<Number>) -> Result<Number, CalcError> {
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        Ok(sorted[middle].clone())
+    } else {
+        mean(backend, &sorted[middle - 1..=middle])
+    }
+}
+
+/// The most common value. Ties go to the smallest, so `mode` of a list
+/// with no repeats is its minimum.
+fn mode(sorted: Vec<Number>) -> Number {
+    let (mut best, mut best_run) = (0, 0);
+    let mut start = 0;
+    for end in 1..=sorted.len() {
+        if end == sorted.len() || sorted[end].compare(&sorted[start]) != Ordering::Equal {
+            if end - start > best_run {
+                (best, best_run) = (start, end - start);
+            }
+            start = end;
+        }
+    }
+    sorted[best].clone()
+}
+
+/// Sample variance, dividing by `n - 1`, since a list typed into a
+/// calculator is nearly always a sample of something larger
+fn variance(backend: &dyn Backend, values: &[Number]) -> Result<Number, CalcError> {
+    if values.len() < 2 {
+        return Err(CalcError::Domain(
+            "variance and stdev need at least two values".to_string(),
+        ));
+    }
+    let average = mean(backend, values)?;
+    let mut squares = backend.literal("0")?;
+    for x in values {
+        let deviation = backend.sub(x, &average)?;
+        squares = backend.add(&squares, &backend.mul(&deviation, &deviation)?)?;
+    }
+    let degrees = backend.literal(&(values.len() - 1).to_string())?;
+    backend.div(&squares, &degrees)
+}
+
+/// Sample standard deviation. The square root is taken in f64 and brought
+/// back into the current mode, like `sqrt`.
+fn stdev(backend: &dyn Backend, values: &[Number]) -> Result<Number, CalcError> {
+    let spread = variance(backend, values)?.to_f64().sqrt();
+    backend.convert(&FloatBackend::finite(spread, "stdev")?)
+}
+
+/// The value below which `wanted` percent of the list falls, interpolating
+/// linearly between neighbours: `percentile(x, 0)` is the minimum, `50` the
+/// median and `100` the maximum
+fn percentile(
+    backend: &dyn Backend,
+    values: Vec<Number>,
+    wanted: &Number,
+) -> Result<Number, CalcError> {
+    let percent = wanted.to_f64();
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(CalcError::Domain(format!(
+            "percentile expects a percentage from 0 to 100, found {}",
+            wanted
+        )));
+    }
+    let sorted = sorted(values);
+    let last = backend.literal(&(sorted.len() - 1).to_string())?;
+    let rank = backend.div(&backend.mul(wanted, &last)?, &backend.literal("100")?)?;
+    let below = rank.floor();
+    let index = below
+        .to_integer()?
+        .to_i128()
+        .map_or(0, |i| i as usize)
+        .min(sorted.len() - 1);
+    let Some(next) = sorted.get(index + 1) else {
+        return Ok(sorted[index].clone());
+    };
+    let fraction = backend.sub(&rank, &backend.convert(&below)?)?;
+    let step = backend.mul(&backend.sub(next, &sorted[index])?, &fraction)?;
+    backend.add(&sorted[index], &step)
+}
+
+/// A summary of a column of numbers, as printed by `calc --stats`: count,
+/// sum, mean, median, mode, min, quartiles and max, then the sample
+/// variance and standard deviation when there are at least two values
+pub fn summarize(
+    values: &[Number],
+    backend: &dyn Backend,
+) -> Result<Vec<(&'static str, Number)>, CalcError> {
+    if values.is_empty() {
+        return Err(CalcError::Domain(
+            "There are no numbers to summarize".to_string(),
+        ));
+    }
+    let ordered = sorted(values.to_vec());
+    let quartile = |percent: &str| percentile(backend, ordered.clone(), &backend.literal(percent)?);
+    let mut rows = vec![
+        ("count", count(backend, values)?),
+        ("sum", sum(backend, values)?),
+        ("mean", mean(backend, values)?),
+        ("median", median(backend, ordered.clone())?),
+        ("mode", mode(ordered.clone())),
+        ("min", ordered[0].clone()),
+        ("q1", quartile("25")?),
+        ("q3", quartile("75")?),
+        ("max", ordered[ordered.len() - 1].clone()),
+    ];
+    if values.len() > 1 {
+        rows.push(("variance", variance(backend, values)?));
+        rows.push(("stdev", stdev(backend, values)?));
+    }
+    Ok(rows)
+}
@@ -0,0 +1,553 @@
+//! Symbolic differentiation and simplification of expression trees
+
+use std::cmp::Ordering;
+
+use crate::error::CalcError;
+use crate::eval::AngleUnit;
+use crate::number::{BigInt, Decimal, Rational};
+use crate::parser::{radix_literal, Expr};
+
+/// Most decimal places used when printing a coefficient such as `0.125`;
+/// fractions that need more are printed as `a / b`
+const MAX_COEFFICIENT_PLACES: u32 = 20;
+
+/// A product `coefficient * base^power * ...`. Factors with the same base
+/// are merged, and bases are compared by their printed form.
+#[derive(Debug, Clone)]
+struct Term {
+    coefficient: Rational,
+    factors: Vec<(Expr, Rational)>,
+}
+
+impl Term {
+    fn constant(coefficient: Rational) -> Term {
+        Term {
+            coefficient,
+            factors: Vec::new(),
+        }
+    }
+
+    /// A factor that simplification treats as a whole, such as `sin(x)`
+    fn opaque(expr: Expr) -> Term {
+        if let Some(value) = literal_value(&expr) {
+            return Term::constant(value);
+        }
+        Term {
+            coefficient: one(),
+            factors: vec![(expr, one())],
+        }
+    }
+
+    fn negate(mut self) -> Term {
+        self.coefficient = self.coefficient.neg();
+        self
+    }
+
+    fn mul(mut self, other: Term) -> Term {
+        self.coefficient = self.coefficient.mul(&other.coefficient);
+        for (base, power) in other.factors {
+            let key = base.to_string();
+            match self.factors.iter_mut().find(|(b, _)| b.to_string() == key) {
+                Some((_, existing)) => *existing = existing.add(&power),
+                None => self.factors.push((base, power)),
+            }
+        }
+        self.factors.retain(|(_, power)| !power.is_zero());
+        self.factors.sort_by_key(|(base, _)| factor_order(base));
+        self
+    }
+
+    /// `1 / self`, or `None` when the coefficient is zero
+    fn reciprocal(self) -> Option<Term> {
+        if self.coefficient.is_zero() {
+            return None;
+        }
+        Some(Term {
+            coefficient: one().div(&self.coefficient),
+            factors: self
+                .factors
+                .into_iter()
+                .map(|(base, power)| (base, power.neg()))
+                .collect(),
+        })
+    }
+
+    /// Raise to a constant power, or `None` when that cannot be done exactly
+    fn pow(self, exponent: &Rational) -> Option<Term> {
+        let coefficient = if exponent.is_integer() {
+            if self.coefficient.is_zero() && exponent.num.is_negative() {
+                return None;
+            }
+            self.coefficient.pow(&exponent.num).ok()?
+        } else if self.coefficient == one() {
+            one()
+        } else {
+            return None;
+        };
+        Some(Term {
+            coefficient,
+            factors: self
+                .factors
+                .into_iter()
+                .map(|(base, power)| (base, power.mul(exponent)))
+                .collect(),
+        })
+    }
+
+    /// Printed form of the factors alone, used to spot like terms
+    fn key(&self) -> String {
+        self.factors
+            .iter()
+            .map(|(base, power)| format!("{}^{}", base, power))
+            .collect::<Vec<_>>()
+            .join("*")
+    }
+}
+
+fn one() -> Rational {
+    Rational::from_bigint(BigInt::from_i64(1))
+}
+
+/// Variables first, then function calls, then anything else, so products
+/// read `2 * x * sin(x)`
+fn factor_order(expr: &Expr) -> (u8, String) {
+    let rank = match expr {
+        Expr::Variable(_) => 0,
+        Expr::Call(..) => 1,
+        _ => 2,
+    };
+    (rank, expr.to_string())
+}
+
+/// The exact value of a number literal
+fn literal_value(expr: &Expr) -> Option<Rational> {
+    match expr {
+        Expr::Number(text) => match radix_literal(text) {
+            Some(pattern) => Some(Rational::from_bigint(BigInt::from_u128(pattern))),
+            None => Rational::parse_decimal(text),
+        },
+        _ => None,
+    }
+}
+
+/// A constant written the shortest exact way: `3`, `0.25` or `1 / 3`
+pub(crate) fn constant_expr(value: &Rational) -> Expr {
+    if value.is_integer() {
+        return Expr::Number(value.num.to_string());
+    }
+    for places in 1..=MAX_COEFFICIENT_PLACES {
+        let decimal = Decimal::from_rational(value, places);
+        if decimal.to_rational() == *value {
+            return Expr::Number(decimal.to_string());
+        }
+    }
+    Expr::Binary(
+        Box::new(Expr::Number(value.num.to_string())),
+        '/',
+        Box::new(Expr::Number(value.den.to_string())),
+    )
+}
+
+/// Flatten sums and differences into a list of terms
+fn to_sum(expr: &Expr) -> Vec<Term> {
+    match expr {
+        Expr::Binary(left, '+', right) => {
+            let mut terms = to_sum(left);
+            terms.extend(to_sum(right));
+            terms
+        }
+        Expr::Binary(left, '-', right) => {
+            let mut terms = to_sum(left);
+            terms.extend(to_sum(right).into_iter().map(Term::negate));
+            terms
+        }
+        Expr::Negate(inner) => to_sum(inner).into_iter().map(Term::negate).collect(),
+        _ => {
+            let term = to_term(expr);
+            // Spread a constant over a sum, so `2 * (x + 1) - 2` is `2 * x`
+            match term.factors.as_slice() {
+                [(sum @ Expr::Binary(_, '+' | '-', _), power)] if *power == one() => to_sum(sum)
+                    .into_iter()
+                    .map(|part| part.mul(Term::constant(term.coefficient.clone())))
+                    .collect(),
+                _ => vec![term],
+            }
+        }
+    }
+}
+
+/// Read a product, quotient or power as a single term
+fn to_term(expr: &Expr) -> Term {
+    match expr {
+        Expr::Binary(left, '*', right) => as_factor(left).mul(as_factor(right)),
+        Expr::Binary(left, '/', right) => match as_factor(right).reciprocal() {
+            Some(divisor) => as_factor(left).mul(divisor),
+            // Keep `x / 0` as written so evaluating it reports the error
+            None => Term::opaque(Expr::Binary(
+                Box::new(simplify(left)),
+                '/',
+                Box::new(simplify(right)),
+            )),
+        },
+        Expr::Binary(base, '^', exponent) => {
+            let exponent = simplify(exponent);
+            let power = match combine(to_sum(&exponent)).as_slice() {
+                [] => Some(Rational::from_bigint(BigInt::from_i64(0))),
+                [term] if term.factors.is_empty() => Some(term.coefficient.clone()),
+                _ => None,
+            };
+            power
+                .and_then(|power| as_factor(base).pow(&power))
+                .unwrap_or_else(|| {
+                    Term::opaque(Expr::Binary(
+                        Box::new(simplify(base)),
+                        '^',
+                        Box::new(exponent),
+                    ))
+                })
+        }
+        _ => Term::opaque(simplify_node(expr)),
+    }
+}
+
+/// A sub-expression used as a factor. A sum that does not collapse to a
+/// single term stays in one piece, as in `(x + 1)^2`.
+fn as_factor(expr: &Expr) -> Term {
+    let terms = combine(to_sum(expr));
+    match terms.len() {
+        0 => Term::constant(Rational::from_bigint(BigInt::from_i64(0))),
+        1 => terms.into_iter().next().expect("one term"),
+        _ => Term::opaque(from_sum(terms)),
+    }
+}
+
+/// Add up like terms, keeping the order they first appeared in
+fn combine(terms: Vec<Term>) -> Vec<Term> {
+    let mut combined: Vec<Term> = Vec::new();
+    for term in terms {
+        let key = term.key();
+        match combined.iter_mut().find(|existing| existing.key() == key) {
+            Some(existing) => {
+                existing.coefficient = existing.coefficient.add(&term.coefficient);
+            }
+            None => combined.push(term),
+        }
+    }
+    combined.retain(|term| !term.coefficient.is_zero());
+    // A lone constant reads best at the end: `x^2 + 1`, not `1 + x^2`
+    combined.sort_by(|a, b| match (a.factors.is_empty(), b.factors.is_empty()) {
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        _ => Ordering::Equal,
+    });
+    combined
+}
+
+/// Multiply a list of factors together, left to right
+fn product(factors: Vec<Expr>) -> Expr {
+    factors
+        .into_iter()
+        .reduce(|left, right| Expr::Binary(Box::new(left), '*', Box::new(right)))
+        .unwrap_or_else(|| literal("1"))
+}
+
+fn power_expr(base: Expr, power: &Rational) -> Expr {
+    if *power == one() {
+        base
+    } else {
+        Expr::Binary(Box::new(base), '^', Box::new(constant_expr(power)))
+    }
+}
+
+/// Write a term back out. Negative powers go below a `/`, and the sign of
+/// the coefficient goes on the first factor so `-x^2 / 3` needs no parentheses.
+fn term_expr(term: &Term) -> Expr {
+    let negative = term.coefficient.num.is_negative();
+    let coefficient = term.coefficient.abs();
+
+    let mut numerator = Vec::new();
+    let mut denominator = Vec::new();
+    if coefficient.is_integer() {
+        if coefficient != one() || term.factors.is_empty() {
+            numerator.push(constant_expr(&coefficient));
+        }
+    } else {
+        match constant_expr(&coefficient) {
+            // Coefficients that print as decimals stay in front: `0.5 * x`
+            decimal @ Expr::Number(_) => numerator.push(decimal),
+            _ => {
+                if coefficient.num != BigInt::from_i64(1) {
+                    numerator.push(literal(&coefficient.num.to_string()));
+                }
+                denominator.push(literal(&coefficient.den.to_string()));
+            }
+        }
+    }
+    for (base, power) in &term.factors {
+        if power.num.is_negative() {
+            denominator.push(power_expr(base.clone(), &power.neg()));
+        } else {
+            numerator.push(power_expr(base.clone(), power));
+        }
+    }
+
+    if negative {
+        let first = if numerator.is_empty() {
+            literal("1")
+        } else {
+            numerator.remove(0)
+        };
+        numerator.insert(
+            0,
+            match first {
+                Expr::Number(text) => Expr::Number(format!("-{}", text)),
+                other => Expr::Negate(Box::new(other)),
+            },
+        );
+    }
+
+    let numerator = product(numerator);
+    if denominator.is_empty() {
+        numerator
+    } else {
+        Expr::Binary(Box::new(numerator), '/', Box::new(product(denominator)))
+    }
+}
+
+/// Write a list of terms back out as `a + b - c`
+fn from_sum(terms: Vec<Term>) -> Expr {
+    let mut terms = terms.into_iter();
+    let Some(first) = terms.next() else {
+        return literal("0");
+    };
+    let mut sum = term_expr(&first);
+    for term in terms {
+        if term.coefficient.num.is_negative() {
+            let positive = term.negate();
+            sum = Expr::Binary(Box::new(sum), '-', Box::new(term_expr(&positive)));
+        } else {
+            sum = Expr::Binary(Box::new(sum), '+', Box::new(term_expr(&term)));
+        }
+    }
+    sum
+}
+
+/// Fold constants and cancel like terms: `x + x` is `2 * x`, `x * x^2` is
+/// `x^3` and `(x + 1) / (x + 1)` is `1`. Arithmetic is exact whatever the
+/// numeric mode.
+pub(crate) fn simplify(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Binary(_, '+' | '-' | '*' | '/' | '^', _) | Expr::Negate(_) => {
+            from_sum(combine(to_sum(expr)))
+        }
+        _ => simplify_node(expr),
+    }
+}
+
+/// Simplify the children of a node that is not plain arithmetic
+fn simplify_node(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Call(name, args) => fold_call(name, args.iter().map(simplify).collect()),
+        Expr::Factorial(inner) => Expr::Factorial(Box::new(simplify(inner))),
+        Expr::BitNot(inner) => Expr::BitNot(Box::new(simplify(inner))),
+        Expr::Binary(left, op, right) => {
+            Expr::Binary(Box::new(simplify(left)), *op, Box::new(simplify(right)))
+        }
+        Expr::Convert(value, unit) => Expr::Convert(Box::new(simplify(value)), unit.clone()),
+        Expr::Matrix(rows) => Expr::Matrix(
+            rows.iter()
+                .map(|row| row.iter().map(simplify).collect())
+                .collect(),
+        ),
+        _ => expr.clone(),
+    }
+}
+
+/// Calls with a well-known exact value, such as `ln(e)` or `cos(0)`
+fn fold_call(name: &str, args: Vec<Expr>) -> Expr {
+    let text: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let text: Vec<&str> = text.iter().map(String::as_str).collect();
+    let value = match (name, text.as_slice()) {
+        ("ln" | "log10", ["1"]) | ("sin" | "tan" | "asin" | "atan" | "sqrt", ["0"]) => "0",
+        ("ln", ["e"]) | ("log10", ["10"]) | ("cos" | "sqrt", ["0" | "1"]) => "1",
+        _ => return Expr::Call(name.to_string(), args),
+    };
+    Expr::Number(value.to_string())
+}
+
+/// The exact value of an expression made only of numbers, such as a
+/// simplified derivative of a linear equation
+pub(crate) fn constant_value(expr: &Expr) -> Option<Rational> {
+    match combine(to_sum(expr)).as_slice() {
+        [] => Some(Rational::from_bigint(BigInt::from_i64(0))),
+        [term] if term.factors.is_empty() => Some(term.coefficient.clone()),
+        _ => None,
+    }
+}
+
+/// Whether `expr` mentions the variable `name`
+pub(crate) fn contains(expr: &Expr, name: &str) -> bool {
+    match expr {
+        Expr::Variable(variable) => variable == name,
+        Expr::Number(_) | Expr::Date(_) | Expr::Duration(_) | Expr::History(_) => false,
+        Expr::Negate(inner) | Expr::BitNot(inner) | Expr::Factorial(inner) => contains(inner, name),
+        Expr::Binary(left, _, right) | Expr::Convert(left, right) => {
+            contains(left, name) || contains(right, name)
+        }
+        Expr::Call(_, args) => args.iter().any(|arg| contains(arg, name)),
+        Expr::Matrix(rows) => rows.iter().flatten().any(|entry| contains(entry, name)),
+    }
+}
+
+fn literal(text: &str) -> Expr {
+    Expr::Number(text.to_string())
+}
+
+fn binary(left: Expr, op: char, right: Expr) -> Expr {
+    Expr::Binary(Box::new(left), op, Box::new(right))
+}
+
+fn call(name: &str, arg: &Expr) -> Expr {
+    Expr::Call(name.to_string(), vec![arg.clone()])
+}
+
+/// Derivative of `expr` with respect to `variable`, not yet simplified.
+/// Trig functions follow the session's angle unit, so in degrees
+/// `diff(sin(x), x)` carries a factor of `pi / 180`.
+pub(crate) fn differentiate(
+    expr: &Expr,
+    variable: &str,
+    angle: AngleUnit,
+) -> Result<Expr, CalcError> {
+    if !contains(expr, variable) {
+        return Ok(literal("0"));
+    }
+    let d = |inner: &Expr| differentiate(inner, variable, angle);
+
+    let derivative = match expr {
+        Expr::Variable(_) => literal("1"),
+        Expr::Negate(inner) => Expr::Negate(Box::new(d(inner)?)),
+        // Entry by entry, so `diff([x^2, x], x)` is `[2 * x, 1]`
+        Expr::Matrix(rows) => Expr::Matrix(
+            rows.iter()
+                .map(|row| row.iter().map(d).collect())
+                .collect::<Result<_, CalcError>>()?,
+        ),
+        Expr::Binary(left, op @ ('+' | '-'), right) => binary(d(left)?, *op, d(right)?),
+        Expr::Binary(left, '*', right) => binary(
+            binary(d(left)?, '*', (**right).clone()),
+            '+',
+            binary((**left).clone(), '*', d(right)?),
+        ),
+        Expr::Binary(left, '/', right) => binary(
+            binary(
+                binary(d(left)?, '*', (**right).clone()),
+                '-',
+                binary((**left).clone(), '*', d(right)?),
+            ),
+            '/',
+            binary((**right).clone(), '^', literal("2")),
+        ),
+        Expr::Binary(base, '^', exponent) => power_rule(base, exponent, variable, angle)?,
+        Expr::Call(name, args) if name == "pow" && args.len() == 2 => {
+            power_rule(&args[0], &args[1], variable, angle)?
+        }
+        Expr::Call(name, args) if args.len() == 1 => {
+            let u = &args[0];
+            // Trig functions of degrees pick up pi/180 from the chain rule,
+            // and inverse trig functions return degrees, adding 180/pi
+            let (to_radians, from_radians) = match angle {
+                AngleUnit::Radians => (literal("1"), literal("1")),
+                AngleUnit::Degrees => (
+                    binary(Expr::Variable("pi".to_string()), '/', literal("180")),
+                    binary(literal("180"), '/', Expr::Variable("pi".to_string())),
+                ),
+            };
+            let one_minus_square = || {
+                call(
+                    "sqrt",
+                    &binary(literal("1"), '-', binary(u.clone(), '^', literal("2"))),
+                )
+            };
+            let outer = match name.as_str() {
+                "sqrt" => binary(
+                    literal("1"),
+                    '/',
+                    binary(literal("2"), '*', call("sqrt", u)),
+                ),
+                "ln" => binary(literal("1"), '/', u.clone()),
+                "log10" => binary(
+                    literal("1"),
+                    '/',
+                    binary(u.clone(), '*', call("ln", &literal("10"))),
+                ),
+                "abs" => binary(u.clone(), '/', call("abs", u)),
+                "sin" => binary(call("cos", u), '*', to_radians),
+                "cos" => Expr::Negate(Box::new(binary(call("sin", u), '*', to_radians))),
+                "tan" => binary(to_radians, '/', binary(call("cos", u), '^', literal("2"))),
+                "asin" => binary(from_radians, '/', one_minus_square()),
+                "acos" => Expr::Negate(Box::new(binary(from_radians, '/', one_minus_square()))),
+                "atan" => binary(
+                    from_radians,
+                    '/',
+                    binary(literal("1"), '+', binary(u.clone(), '^', literal("2"))),
+                ),
+                _ => return Err(cannot_differentiate(&format!("{}()", name))),
+            };
+            binary(outer, '*', d(u)?)
+        }
+        Expr::Call(name, _) => return Err(cannot_differentiate(&format!("{}()", name))),
+        _ => return Err(cannot_differentiate(&format!("'{}'", expr))),
+    };
+    Ok(derivative)
+}
+
+/// `d(a^b)`, using the simpler power or exponential rule when only one
+/// side depends on the variable
+fn power_rule(
+    base: &Expr,
+    exponent: &Expr,
+    variable: &str,
+    angle: AngleUnit,
+) -> Result<Expr, CalcError> {
+    let power = binary(base.clone(), '^', exponent.clone());
+    if !contains(exponent, variable) {
+        // b * a^(b - 1) * a'
+        let lowered = binary(exponent.clone(), '-', literal("1"));
+        return Ok(binary(
+            binary(exponent.clone(), '*', binary(base.clone(), '^', lowered)),
+            '*',
+            differentiate(base, variable, angle)?,
+        ));
+    }
+    if !contains(base, variable) {
+        // a^b * ln(a) * b'
+        return Ok(binary(
+            binary(power, '*', call("ln", base)),
+            '*',
+            differentiate(exponent, variable, angle)?,
+        ));
+    }
+    // a^b * (b' * ln(a) + b * a' / a)
+    Ok(binary(
+        power,
+        '*',
+        binary(
+            binary(
+                differentiate(exponent, variable, angle)?,
+                '*',
+                call("ln", base),
+            ),
+            '+',
+            binary(
+                binary(exponent.clone(), '*', differentiate(base, variable, angle)?),
+                '/',
+                base.clone(),
+            ),
+        ),
+    ))
+}
+
+fn cannot_differentiate(what: &str) -> CalcError {
+    CalcError::Domain(format!("diff() cannot differentiate {}", what))
+}
@@ -0,0 +1,637 @@
+//! Units of measure and values that carry them
+
+use std::fmt;
+
+use crate::backend::Backend;
+use crate::datetime::{format_duration, DateTime};
+use crate::error::CalcError;
+use crate::matrix::Matrix;
+use crate::number::{BigInt, Number, Rational};
+use crate::parser::Expr;
+use crate::plot::Plot;
+use crate::symbolic::constant_expr;
+
+/// Powers of the base dimensions: length, mass, time, data size, temperature
+type Dimension = [i32; 5];
+
+const DIMENSIONLESS: Dimension = [0, 0, 0, 0, 0];
+const LENGTH: Dimension = [1, 0, 0, 0, 0];
+const MASS: Dimension = [0, 1, 0, 0, 0];
+const TIME: Dimension = [0, 0, 1, 0, 0];
+const DATA: Dimension = [0, 0, 0, 1, 0];
+const TEMPERATURE: Dimension = [0, 0, 0, 0, 1];
+
+/// Plural name and symbol of the base unit of each dimension, for messages
+const BASE_UNITS: [(&str, &str); 5] = [
+    ("metres", "m"),
+    ("kilograms", "kg"),
+    ("seconds", "s"),
+    ("bytes", "B"),
+    ("kelvins", "K"),
+];
+
+/// A named unit. A value `v` in this unit is `(v + offset) * factor` in the
+/// base unit of its dimension; only temperature scales have an offset.
+pub struct UnitDef {
+    symbol: &'static str,
+    aliases: &'static [&'static str],
+    dimension: Dimension,
+    factor: &'static str,
+    offset: &'static str,
+}
+
+const fn unit(
+    symbol: &'static str,
+    aliases: &'static [&'static str],
+    dimension: Dimension,
+    factor: &'static str,
+) -> UnitDef {
+    UnitDef {
+        symbol,
+        aliases,
+        dimension,
+        factor,
+        offset: "0",
+    }
+}
+
+const UNITS: &[UnitDef] = &[
+    unit("m", &["metre", "metres", "meter", "meters"], LENGTH, "1"),
+    unit(
+        "km",
+        &["kilometre", "kilometres", "kilometer", "kilometers"],
+        LENGTH,
+        "1000",
+    ),
+    unit(
+        "cm",
+        &["centimetre", "centimetres", "centimeter", "centimeters"],
+        LENGTH,
+        "0.01",
+    ),
+    unit(
+        "mm",
+        &["millimetre", "millimetres", "millimeter", "millimeters"],
+        LENGTH,
+        "0.001",
+    ),
+    unit("mi", &["mile", "miles"], LENGTH, "1609.344"),
+    unit("yd", &["yard", "yards"], LENGTH, "0.9144"),
+    unit("ft", &["foot", "feet"], LENGTH, "0.3048"),
+    unit("inch", &["inches"], LENGTH, "0.0254"),
+    unit("kg", &["kilogram", "kilograms"], MASS, "1"),
+    unit("g", &["gram", "grams"], MASS, "0.001"),
+    unit("mg", &["milligram", "milligrams"], MASS, "0.000001"),
+    unit("t", &["tonne", "tonnes"], MASS, "1000"),
+    unit("lb", &["lbs", "pound", "pounds"], MASS, "0.45359237"),
+    unit("oz", &["ounce", "ounces"], MASS, "0.028349523125"),
+    unit("s", &["sec", "second", "seconds"], TIME, "1"),
+    unit("ms", &["millisecond", "milliseconds"], TIME, "0.001"),
+    unit("min", &["minute", "minutes"], TIME, "60"),
+    unit("h", &["hr", "hour", "hours"], TIME, "3600"),
+    unit("day", &["days"], TIME, "86400"),
+    unit("week", &["weeks"], TIME, "604800"),
+    unit("yr", &["year", "years"], TIME, "31557600"),
+    unit("bit", &["bits"], DATA, "1/8"),
+    unit("B", &["byte", "bytes"], DATA, "1"),
+    unit("kB", &["kilobyte", "kilobytes"], DATA, "1000"),
+    unit("MB", &["megabyte", "megabytes"], DATA, "1000000"),
+    unit("GB", &["gigabyte", "gigabytes"], DATA, "1000000000"),
+    unit("TB", &["terabyte", "terabytes"], DATA, "1000000000000"),
+    unit("KiB", &["kibibyte", "kibibytes"], DATA, "1024"),
+    unit("MiB", &["mebibyte", "mebibytes"], DATA, "1048576"),
+    unit("GiB", &["gibibyte", "gibibytes"], DATA, "1073741824"),
+    unit("TiB", &["tebibyte", "tebibytes"], DATA, "1099511627776"),
+    unit("K", &["kelvin"], TEMPERATURE, "1"),
+    UnitDef {
+        symbol: "degC",
+        aliases: &["°C", "celsius"],
+        dimension: TEMPERATURE,
+        factor: "1",
+        offset: "273.15",
+    },
+    UnitDef {
+        symbol: "degF",
+        aliases: &["°F", "fahrenheit"],
+        dimension: TEMPERATURE,
+        factor: "5/9",
+        offset: "459.67",
+    },
+];
+
+/// Read a factor from the unit table, written as a decimal or `a/b`
+fn table_fraction(text: &str) -> Rational {
+    let parse = |part: &str| Rational::parse_decimal(part).expect("unit table holds valid numbers");
+    match text.split_once('/') {
+        Some((num, den)) => parse(num).div(&parse(den)),
+        None => parse(text),
+    }
+}
+
+/// Describe a dimension in words for error messages, e.g. `metres` or `m/s`
+pub(crate) fn describe_dimension(dimension: &Dimension) -> String {
+    if *dimension == DIMENSIONLESS {
+        return "plain numbers".to_string();
+    }
+    if let Some(i) = dimension.iter().position(|&power| power == 1) {
+        if dimension.iter().filter(|&&power| power != 0).count() == 1 {
+            return BASE_UNITS[i].0.to_string();
+        }
+    }
+    let parts = dimension
+        .iter()
+        .enumerate()
+        .filter(|(_, &power)| power != 0)
+        .map(|(i, &power)| (BASE_UNITS[i].1, power))
+        .collect();
+    format_unit_parts(parts)
+}
+
+/// Write `symbol^power` pairs as `kg*m/s^2`, or `s^-1` when nothing is on top
+fn format_unit_parts(parts: Vec<(&str, i32)>) -> String {
+    let power = |symbol: &str, power: i32| {
+        if power == 1 {
+            symbol.to_string()
+        } else {
+            format!("{}^{}", symbol, power)
+        }
+    };
+    let numerator: Vec<String> = parts
+        .iter()
+        .filter(|(_, p)| *p > 0)
+        .map(|(symbol, p)| power(symbol, *p))
+        .collect();
+    if numerator.is_empty() {
+        let all: Vec<String> = parts.iter().map(|(symbol, p)| power(symbol, *p)).collect();
+        return all.join("*");
+    }
+    let mut text = numerator.join("*");
+    for (symbol, p) in parts.iter().filter(|(_, p)| *p < 0) {
+        text.push('/');
+        text.push_str(&power(symbol, -p));
+    }
+    text
+}
+
+/// A product of named units with whole powers; `km/h` is `[(km, 1), (h, -1)]`.
+/// Each part is an index into `UNITS`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Unit {
+    parts: Vec<(usize, i32)>,
+}
+
+impl Unit {
+    /// Find a unit by its symbol or any alias
+    pub(crate) fn lookup(name: &str) -> Option<Unit> {
+        UNITS
+            .iter()
+            .position(|def| def.symbol == name || def.aliases.contains(&name))
+            .map(|index| Unit {
+                parts: vec![(index, 1)],
+            })
+    }
+
+    pub(crate) fn is_unit_name(name: &str) -> bool {
+        Unit::lookup(name).is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    pub(crate) fn dimension(&self) -> Dimension {
+        let mut dimension = DIMENSIONLESS;
+        for &(index, power) in &self.parts {
+            for (total, base) in dimension.iter_mut().zip(UNITS[index].dimension) {
+                *total += base * power;
+            }
+        }
+        dimension
+    }
+
+    /// Size of this unit in base units, ignoring temperature offsets
+    fn factor(&self) -> Rational {
+        let mut factor = Rational::from_bigint(BigInt::from_i64(1));
+        for &(index, power) in &self.parts {
+            let part = table_fraction(UNITS[index].factor)
+                .pow(&BigInt::from_i64(power as i64))
+                .expect("unit powers stay small");
+            factor = factor.mul(&part);
+        }
+        factor
+    }
+
+    /// Factor and offset when this is a single temperature scale such as
+    /// `degC`, the only case where offsets apply
+    fn temperature_scale(&self) -> Option<(Rational, Rational)> {
+        match self.parts.as_slice() {
+            [(index, 1)] if UNITS[*index].dimension == TEMPERATURE => Some((
+                table_fraction(UNITS[*index].factor),
+                table_fraction(UNITS[*index].offset),
+            )),
+            _ => None,
+        }
+    }
+
+    fn mul(&self, other: &Unit) -> Unit {
+        let mut parts = self.parts.clone();
+        for &(index, power) in &other.parts {
+            match parts.iter_mut().find(|(existing, _)| *existing == index) {
+                Some((_, total)) => *total += power,
+                None => parts.push((index, power)),
+            }
+        }
+        parts.retain(|&(_, power)| power != 0);
+        Unit { parts }
+    }
+
+    fn pow(&self, exponent: i32) -> Unit {
+        Unit {
+            parts: self
+                .parts
+                .iter()
+                .map(|&(index, power)| (index, power * exponent))
+                .filter(|&(_, power)| power != 0)
+                .collect(),
+        }
+    }
+
+    fn div(&self, other: &Unit) -> Unit {
+        self.mul(&other.pow(-1))
+    }
+
+    /// Halve every power, if they are all even
+    pub(crate) fn sqrt(&self) -> Option<Unit> {
+        if self.parts.iter().any(|&(_, power)| power % 2 != 0) {
+            return None;
+        }
+        Some(Unit {
+            parts: self
+                .parts
+                .iter()
+                .map(|&(index, power)| (index, power / 2))
+                .collect(),
+        })
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts = self
+            .parts
+            .iter()
+            .map(|&(index, power)| (UNITS[index].symbol, power))
+            .collect();
+        write!(f, "{}", format_unit_parts(parts))
+    }
+}
+
+/// A result: a plain number, a number carrying a unit, an expression
+/// still in terms of free variables, such as the result of `diff`, the
+/// answers found by `solve`, a matrix, a date or a duration
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(Number),
+    Quantity(Number, Unit),
+    Symbolic(Expr),
+    /// Each entry is one solution, giving a value to every unknown
+    Solution(Vec<Vec<(String, Number)>>),
+    Matrix(Matrix),
+    Date(DateTime),
+    /// A length of time in seconds, printed as `3h 20m`
+    Duration(Number),
+    Plot(Box<Plot>),
+}
+
+impl Value {
+    /// The number part, in seconds for a duration, or `None` for values
+    /// such as symbolic results and dates that have none
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            Value::Number(n) | Value::Quantity(n, _) | Value::Duration(n) => Some(n),
+            Value::Symbolic(_)
+            | Value::Solution(_)
+            | Value::Matrix(_)
+            | Value::Date(_)
+            | Value::Plot(_) => None,
+        }
+    }
+
+    /// The number part. Evaluation turns whole nodes symbolic and unwraps
+    /// solutions before they reach the arithmetic, so this is only called on
+    /// numbers and quantities.
+    pub(crate) fn number(&self) -> &Number {
+        self.as_number()
+            .expect("symbolic values are handled before arithmetic")
+    }
+
+    pub fn unit(&self) -> Unit {
+        match self {
+            Value::Number(_)
+            | Value::Symbolic(_)
+            | Value::Solution(_)
+            | Value::Matrix(_)
+            | Value::Date(_)
+            | Value::Plot(_) => Unit::default(),
+            Value::Quantity(_, unit) => unit.clone(),
+            Value::Duration(_) => Unit::lookup("s").expect("seconds are in the unit table"),
+        }
+    }
+
+    /// The value as an operand of arithmetic. A solution with one unknown
+    /// and one root stands for that number, so `solve(2x = 4, x) * 3` is `6`.
+    pub(crate) fn operand(self) -> Result<Value, CalcError> {
+        if let Value::Plot(plot) = &self {
+            return Err(CalcError::Domain(format!(
+                "{} cannot be used in arithmetic",
+                plot.title()
+            )));
+        }
+        let Value::Solution(solutions) = &self else {
+            return Ok(self);
+        };
+        match solutions.as_slice() {
+            [solution] if solution.len() == 1 => Ok(Value::Number(solution[0].1.clone())),
+            _ => Err(CalcError::Domain(format!(
+                "{} is more than one number and cannot be used in arithmetic",
+                self
+            ))),
+        }
+    }
+
+    pub(crate) fn is_symbolic(&self) -> bool {
+        matches!(self, Value::Symbolic(_))
+    }
+
+    /// The value written as an expression, so it can join a symbolic one:
+    /// `3 m` becomes `3 * m`
+    pub(crate) fn to_expr(&self) -> Result<Expr, CalcError> {
+        let number = match self {
+            Value::Symbolic(expr) => return Ok(expr.clone()),
+            Value::Solution(_) => return self.clone().operand()?.to_expr(),
+            Value::Matrix(matrix) => {
+                let rows = matrix
+                    .row_list()
+                    .into_iter()
+                    .map(|row| row.iter().map(number_expr).collect())
+                    .collect::<Result<_, CalcError>>()?;
+                return Ok(Expr::Matrix(rows));
+            }
+            Value::Date(date) => return Ok(Expr::Date(date.to_string())),
+            Value::Plot(plot) => return Ok(plot.to_expr()),
+            Value::Number(n) | Value::Quantity(n, _) | Value::Duration(n) => n,
+        };
+        let constant = number_expr(number)?;
+        let unit = self.unit();
+        if unit.is_empty() {
+            return Ok(constant);
+        }
+        Ok(unit
+            .parts
+            .iter()
+            .fold(constant, |product, &(index, power)| {
+                let factor = Expr::Variable(UNITS[index].symbol.to_string());
+                let factor = if power == 1 {
+                    factor
+                } else {
+                    Expr::Binary(
+                        Box::new(factor),
+                        '^',
+                        Box::new(Expr::Number(power.to_string())),
+                    )
+                };
+                Expr::Binary(Box::new(product), '*', Box::new(factor))
+            }))
+    }
+
+    pub(crate) fn from_parts(number: Number, unit: Unit) -> Value {
+        if unit.is_empty() {
+            Value::Number(number)
+        } else {
+            Value::Quantity(number, unit)
+        }
+    }
+
+    /// Attach a unit, folding it into the number when the dimensions cancel
+    /// out, so `2 GiB / 512 MiB` is the plain number `4`
+    fn with_unit(backend: &dyn Backend, number: Number, unit: Unit) -> Result<Value, CalcError> {
+        if !unit.is_empty() && unit.dimension() == DIMENSIONLESS {
+            let factor = backend.convert(&Number::Rational(unit.factor()))?;
+            return Ok(Value::Number(backend.mul(&number, &factor)?));
+        }
+        Ok(Value::from_parts(number, unit))
+    }
+
+    /// Bring the number part into the backend's mode
+    pub(crate) fn convert(&self, backend: &dyn Backend) -> Result<Value, CalcError> {
+        match self {
+            Value::Symbolic(_) => return Ok(self.clone()),
+            Value::Solution(solutions) => {
+                let solutions = solutions
+                    .iter()
+                    .map(|solution| {
+                        solution
+                            .iter()
+                            .map(|(name, n)| Ok((name.clone(), backend.convert(n)?)))
+                            .collect::<Result<Vec<_>, CalcError>>()
+                    })
+                    .collect::<Result<Vec<_>, CalcError>>()?;
+                return Ok(Value::Solution(solutions));
+            }
+            Value::Matrix(matrix) => {
+                return Ok(Value::Matrix(matrix.map(|n| backend.convert(n))?));
+            }
+            Value::Date(_) | Value::Plot(_) => return Ok(self.clone()),
+            Value::Duration(seconds) => return Ok(Value::Duration(backend.convert(seconds)?)),
+            Value::Number(_) | Value::Quantity(..) => {}
+        }
+        Ok(Value::from_parts(
+            backend.convert(self.number())?,
+            self.unit(),
+        ))
+    }
+
+    /// The number, refusing values that carry a unit
+    pub(crate) fn plain(&self, function: &str) -> Result<&Number, CalcError> {
+        match self {
+            Value::Number(n) => Ok(n),
+            Value::Quantity(..)
+            | Value::Symbolic(_)
+            | Value::Solution(_)
+            | Value::Matrix(_)
+            | Value::Date(_)
+            | Value::Duration(_)
+            | Value::Plot(_) => Err(CalcError::Domain(format!(
+                "{} expects a plain number, found {}",
+                function, self
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Quantity(n, unit) => write!(f, "{} {}", n, unit),
+            Value::Symbolic(expr) => write!(f, "{}", expr),
+            Value::Matrix(matrix) => write!(f, "{}", matrix),
+            Value::Date(date) => write!(f, "{}", date),
+            Value::Plot(plot) => write!(f, "{}", plot),
+            Value::Duration(seconds) => write!(f, "{}", format_duration(seconds)),
+            Value::Solution(solutions) => {
+                let solutions: Vec<String> = solutions
+                    .iter()
+                    .map(|solution| {
+                        let values: Vec<String> = solution
+                            .iter()
+                            .map(|(name, n)| format!("{} = {}", name, n))
+                            .collect();
+                        values.join(", ")
+                    })
+                    .collect();
+                write!(f, "{}", solutions.join(" or "))
+            }
+        }
+    }
+}
+
+/// A number written as an expression
+fn number_expr(number: &Number) -> Result<Expr, CalcError> {
+    Ok(match number {
+        Number::Float(x) if *x < 0.0 => Expr::Negate(Box::new(Expr::Number((-x).to_string()))),
+        Number::Float(x) => Expr::Number(x.to_string()),
+        _ => constant_expr(&number.to_rational()?),
+    })
+}
+
+/// Express `number` (measured in `from`) in `to`, which must have the same dimension
+pub(crate) fn rescale(
+    backend: &dyn Backend,
+    number: &Number,
+    from: &Unit,
+    to: &Unit,
+) -> Result<Number, CalcError> {
+    let ratio = from.factor().div(&to.factor());
+    if ratio.is_integer() && ratio.num == BigInt::from_i64(1) {
+        return Ok(number.clone());
+    }
+    backend.mul(number, &backend.convert(&Number::Rational(ratio))?)
+}
+
+/// `+`, `-` and `%` need both sides in the same dimension; the right side is
+/// converted to the left side's unit
+pub(crate) fn add_values(
+    backend: &dyn Backend,
+    a: &Value,
+    op: char,
+    b: &Value,
+) -> Result<Value, CalcError> {
+    let (unit_a, unit_b) = (a.unit(), b.unit());
+    if unit_a.dimension() != unit_b.dimension() {
+        let (from, to) = (
+            describe_dimension(&unit_b.dimension()),
+            describe_dimension(&unit_a.dimension()),
+        );
+        return Err(CalcError::Dimension(match op {
+            '+' => format!("Cannot add {} to {}", from, to),
+            '-' => format!("Cannot subtract {} from {}", from, to),
+            _ => format!("Cannot take the remainder of {} by {}", to, from),
+        }));
+    }
+
+    let right = rescale(backend, b.number(), &unit_b, &unit_a)?;
+    let number = match op {
+        '+' => backend.add(a.number(), &right)?,
+        '-' => backend.sub(a.number(), &right)?,
+        _ => backend.rem(a.number(), &right)?,
+    };
+    Ok(Value::from_parts(number, unit_a))
+}
+
+pub(crate) fn mul_values(
+    backend: &dyn Backend,
+    a: &Value,
+    op: char,
+    b: &Value,
+) -> Result<Value, CalcError> {
+    let (number, unit) = match op {
+        '*' => (
+            backend.mul(a.number(), b.number())?,
+            a.unit().mul(&b.unit()),
+        ),
+        _ => (
+            backend.div(a.number(), b.number())?,
+            a.unit().div(&b.unit()),
+        ),
+    };
+    Value::with_unit(backend, number, unit)
+}
+
+pub(crate) fn pow_values(backend: &dyn Backend, a: &Value, b: &Value) -> Result<Value, CalcError> {
+    let Value::Number(exponent) = b else {
+        return Err(CalcError::Domain(format!(
+            "Exponents must be plain numbers, found {}",
+            b
+        )));
+    };
+    match a {
+        Value::Number(base) => Ok(Value::Number(backend.pow(base, exponent)?)),
+        Value::Quantity(base, unit) => {
+            let power = exponent
+                .to_integer()
+                .ok()
+                .and_then(|n| n.to_string().parse::<i32>().ok())
+                .ok_or_else(|| {
+                    CalcError::Domain(format!("{} can only be raised to whole powers", unit))
+                })?;
+            Value::with_unit(backend, backend.pow(base, exponent)?, unit.pow(power))
+        }
+        Value::Symbolic(_)
+        | Value::Solution(_)
+        | Value::Matrix(_)
+        | Value::Date(_)
+        | Value::Duration(_)
+        | Value::Plot(_) => {
+            unreachable!("symbolic values, dates and durations are handled before arithmetic")
+        }
+    }
+}
+
+/// `value in unit`: re-express a value in the target's unit. Lone
+/// temperature scales convert with their offsets, so `100 degC in degF`
+/// is `212 degF`; everywhere else temperatures act as differences.
+pub(crate) fn convert_to(
+    backend: &dyn Backend,
+    value: &Value,
+    target: &Value,
+) -> Result<Value, CalcError> {
+    let Value::Quantity(_, to) = target else {
+        return Err(CalcError::Domain(format!(
+            "Expected a unit after 'in', found {}",
+            target
+        )));
+    };
+    let from = value.unit();
+    if from.dimension() != to.dimension() {
+        return Err(CalcError::Dimension(format!(
+            "Cannot convert {} to {}",
+            describe_dimension(&from.dimension()),
+            describe_dimension(&to.dimension())
+        )));
+    }
+
+    let number = match (from.temperature_scale(), to.temperature_scale()) {
+        (Some((from_factor, from_offset)), Some((to_factor, to_offset))) => {
+            let kelvin = value
+                .number()
+                .to_rational()?
+                .add(&from_offset)
+                .mul(&from_factor);
+            let converted = kelvin.div(&to_factor).sub(&to_offset);
+            backend.convert(&Number::Rational(converted))?
+        }
+        _ => rescale(backend, value.number(), &from, to)?,
+    };
+    Ok(Value::Quantity(number, to.clone()))
+}
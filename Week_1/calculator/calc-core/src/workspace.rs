@@ -0,0 +1,231 @@
+//! Saving a session as text and loading it back. A workspace lists the
+//! settings, functions, variables and results one per line, written the
+//! way they are typed, so the file can be read, diffed and shared.
+//!
+//! ```text
+//! # calc workspace
+//! mode = rational
+//! angle = radians
+//! tolerance = 1e-12
+//!
+//! [functions]
+//! f(x) = x^2 + 1
+//!
+//! [variables]
+//! x = 1 / 3
+//!
+//! [history]
+//! 1 / 3
+//! ```
+
+use std::fmt;
+
+use crate::backend::{backend_for, OverflowPolicy, DEFAULT_SCALE};
+use crate::datetime::duration_source;
+use crate::error::CalcError;
+use crate::eval::{evaluate_expr, execute, is_reserved, AngleUnit, Env, Outcome};
+use crate::parser::{parse, Statement};
+use crate::units::Value;
+
+/// First line of every workspace
+const HEADER: &str = "# calc workspace";
+
+/// A workspace line that could not be read back
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceError {
+    /// 1-based line number in the workspace text
+    pub line: usize,
+    pub error: CalcError,
+}
+
+impl fmt::Display for WorkspaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for WorkspaceError {}
+
+/// Write the whole session as workspace text
+pub fn save_workspace(env: &Env) -> String {
+    let mut lines = vec![
+        HEADER.to_string(),
+        format!("mode = {}", env.backend.name()),
+        format!("angle = {}", env.angle),
+        format!("tolerance = {:e}", env.tolerance),
+        String::new(),
+        "[functions]".to_string(),
+    ];
+    lines.extend(
+        env.functions
+            .iter()
+            .map(|(name, function)| format!("{}{}", name, function)),
+    );
+    lines.push(String::new());
+    lines.push("[variables]".to_string());
+    lines.extend(
+        env.variables
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, source(value))),
+    );
+    lines.push(String::new());
+    lines.push("[history]".to_string());
+    lines.extend(env.history.iter().map(source));
+    lines.join("\n") + "\n"
+}
+
+/// A value written so that evaluating the text gives it back
+fn source(value: &Value) -> String {
+    match value {
+        // `simplify` of an expression with free variables stays symbolic
+        Value::Symbolic(expr) => format!("simplify({})", expr),
+        // Solutions print as `x = 2 or x = -2`, which `read_value` understands
+        Value::Solution(_) => value.to_string(),
+        Value::Duration(seconds) => duration_source(seconds),
+        _ => match value.to_expr() {
+            Ok(expr) => expr.to_string(),
+            Err(_) => value.to_string(),
+        },
+    }
+}
+
+/// Replace the session with the one saved in `text`. Nothing changes if
+/// any line fails to load.
+pub fn load_workspace(text: &str, env: &mut Env) -> Result<(), WorkspaceError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err(WorkspaceError {
+            line: 1,
+            error: CalcError::Domain(format!("A workspace must start with '{}'", HEADER)),
+        });
+    }
+
+    let mut loaded = Env::new(
+        backend_for("float", DEFAULT_SCALE, OverflowPolicy::Wrapping)
+            .expect("float mode always exists"),
+    );
+    let mut section = "";
+    for (number, line) in lines {
+        let at_line = |error| WorkspaceError {
+            line: number,
+            error,
+        };
+        if line.starts_with('#') {
+            continue;
+        }
+        // Matrix values start with `[` too, but never hold a bare word
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|l| l.strip_suffix(']'))
+            .filter(|name| name.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            section = match name {
+                "functions" | "variables" | "history" => name,
+                _ => {
+                    return Err(at_line(CalcError::Domain(format!(
+                        "Unknown section '[{}]'",
+                        name
+                    ))))
+                }
+            };
+            continue;
+        }
+        match section {
+            "functions" => match execute(line, &mut loaded).map_err(at_line)? {
+                Outcome::Defined(_) => {}
+                Outcome::Value { .. } => {
+                    return Err(at_line(CalcError::Domain(format!(
+                        "Expected a function definition, found '{}'",
+                        line
+                    ))))
+                }
+            },
+            "variables" => {
+                let (name, value) = line.split_once(" = ").ok_or_else(|| {
+                    at_line(CalcError::Domain(format!(
+                        "Expected 'name = value', found '{}'",
+                        line
+                    )))
+                })?;
+                if is_reserved(name) {
+                    return Err(at_line(CalcError::Reserved(name.to_string())));
+                }
+                let value = read_value(value, &loaded).map_err(at_line)?;
+                loaded.variables.insert(name.to_string(), value);
+            }
+            "history" => {
+                let value = read_value(line, &loaded).map_err(at_line)?;
+                loaded.history.push(value);
+            }
+            _ => apply_setting(line, &mut loaded).map_err(at_line)?,
+        }
+    }
+    *env = loaded;
+    Ok(())
+}
+
+/// `mode = ...`, `angle = ...` or `tolerance = ...` from the top of the file
+fn apply_setting(line: &str, env: &mut Env) -> Result<(), CalcError> {
+    let unknown = || CalcError::Domain(format!("Unknown setting '{}'", line));
+    let (key, value) = line.split_once(" = ").ok_or_else(unknown)?;
+    match key {
+        // Written by `Backend::name`: `float`, `decimal (scale 20)`, `u8 (checked)`
+        "mode" => {
+            let (mode, detail) = match value.split_once(" (") {
+                Some((mode, detail)) => (mode, detail.trim_end_matches(')')),
+                None => (value, ""),
+            };
+            let mut scale = DEFAULT_SCALE;
+            let mut overflow = OverflowPolicy::Wrapping;
+            match detail.strip_prefix("scale ") {
+                Some(places) => scale = places.parse().map_err(|_| unknown())?,
+                None if detail == "checked" => overflow = OverflowPolicy::Checked,
+                None => {}
+            }
+            env.backend = backend_for(mode, scale, overflow).ok_or_else(unknown)?;
+        }
+        "angle" => {
+            env.angle = match value {
+                "radians" => AngleUnit::Radians,
+                "degrees" => AngleUnit::Degrees,
+                _ => return Err(unknown()),
+            }
+        }
+        "tolerance" => env.tolerance = value.parse().map_err(|_| unknown())?,
+        _ => return Err(unknown()),
+    }
+    Ok(())
+}
+
+/// Evaluate a value written by `source`
+fn read_value(text: &str, env: &Env) -> Result<Value, CalcError> {
+    if text.contains(" = ") {
+        let solutions = text
+            .split(" or ")
+            .map(|solution| {
+                solution
+                    .split(", ")
+                    .map(|pair| {
+                        let (name, number) = pair.split_once(" = ").ok_or_else(|| {
+                            CalcError::Domain(format!("Expected 'name = value', found '{}'", pair))
+                        })?;
+                        let value = read_value(number, env)?;
+                        Ok((name.to_string(), value.plain("a solution")?.clone()))
+                    })
+                    .collect::<Result<Vec<_>, CalcError>>()
+            })
+            .collect::<Result<Vec<_>, CalcError>>()?;
+        return Ok(Value::Solution(solutions));
+    }
+    match parse(text, env.backend.caret_is_xor())? {
+        Statement::Expr(expr) => evaluate_expr(&expr, env),
+        _ => Err(CalcError::Domain(format!(
+            "Expected a value, found '{}'",
+            text
+        ))),
+    }
+}
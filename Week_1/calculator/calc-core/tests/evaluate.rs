@@ -0,0 +1,736 @@
+use calc_core::{
+    backend_for, evaluate, execute, load_workspace, save_workspace, summarize, CalcError, Env,
+    Number, Outcome, OverflowPolicy, Value, DEFAULT_SCALE,
+};
+
+fn env(mode: &str) -> Env {
+    Env::new(backend_for(mode, DEFAULT_SCALE, OverflowPolicy::Wrapping).expect("known mode"))
+}
+
+/// Evaluate a line in a fresh session of the given mode and return the printed result
+fn eval_in(mode: &str, input: &str) -> String {
+    match evaluate(input, &mut env(mode)) {
+        Ok(value) => value.to_string(),
+        Err(e) => panic!("{} failed in {} mode: {}", input, mode, e),
+    }
+}
+
+fn eval(input: &str) -> String {
+    eval_in("float", input)
+}
+
+/// The error kind a line produces in a fresh session
+fn error_kind(mode: &str, input: &str) -> &'static str {
+    match evaluate(input, &mut env(mode)) {
+        Ok(value) => panic!("{} should fail in {} mode, got {}", input, mode, value),
+        Err(e) => e.kind(),
+    }
+}
+
+// Precedence
+
+#[test]
+fn multiplication_binds_tighter_than_addition() {
+    assert_eq!(eval("2 + 3 * 4"), "14");
+    assert_eq!(eval("(2 + 3) * 4"), "20");
+    assert_eq!(eval("10 - 4 - 3"), "3");
+    assert_eq!(eval("100 / 10 / 5"), "2");
+}
+
+#[test]
+fn power_is_right_associative_and_beats_unary_minus() {
+    assert_eq!(eval("2^3^2"), "512");
+    assert_eq!(eval("-2^2"), "-4");
+    assert_eq!(eval("(-2)^2"), "4");
+    assert_eq!(eval("2^-1"), "0.5");
+    assert_eq!(eval("2**10"), "1024");
+}
+
+#[test]
+fn factorial_and_remainder() {
+    assert_eq!(eval("3! + 1"), "7");
+    assert_eq!(eval("-3!"), "-6");
+    assert_eq!(eval("17 % 5 * 2"), "4");
+}
+
+#[test]
+fn shifts_bind_looser_than_addition() {
+    assert_eq!(eval("1 << 2 + 1"), "8");
+    assert_eq!(eval("6 & 3 | 8"), "10");
+    assert_eq!(eval("1 | 2 xor 3"), "1");
+    assert_eq!(eval("~5"), "-6");
+}
+
+#[test]
+fn conversion_binds_loosest() {
+    assert_eq!(eval("1 km + 500 m in m"), "1500 m");
+}
+
+// Session state
+
+#[test]
+fn variables_history_and_functions_persist_in_the_env() {
+    let mut env = env("float");
+    evaluate("let x = 4", &mut env).unwrap();
+    assert_eq!(evaluate("x * 2", &mut env).unwrap().to_string(), "8");
+    assert_eq!(evaluate("ans + $1", &mut env).unwrap().to_string(), "12");
+
+    let outcome = execute("f(a, b) = a^2 + b", &mut env).unwrap();
+    assert_eq!(outcome, Outcome::Defined("f(a, b) = a^2 + b".to_string()));
+    assert_eq!(evaluate("f(3, 1)", &mut env).unwrap().to_string(), "10");
+}
+
+#[test]
+fn evaluate_rejects_function_definitions() {
+    let mut env = env("float");
+    assert_eq!(error_kind("float", "f(x) = x"), "parse");
+    assert!(evaluate("f(x) = x", &mut env).is_err());
+    assert!(env.functions.is_empty());
+}
+
+// Errors
+
+#[test]
+fn parse_errors_report_the_column() {
+    match evaluate("2 + * 3", &mut env("float")) {
+        Err(CalcError::Parse(e)) => assert_eq!(e.column, 5),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+    assert_eq!(error_kind("float", "(1 + 2"), "parse");
+    assert_eq!(error_kind("float", "2 $"), "parse");
+}
+
+#[test]
+fn each_failure_has_its_own_kind() {
+    assert_eq!(error_kind("float", "1 / 0"), "division_by_zero");
+    assert_eq!(error_kind("float", "sqrt(-1)"), "domain");
+    assert_eq!(error_kind("float", "10^400"), "overflow");
+    assert_eq!(error_kind("float", "5 m + 3 s"), "dimension");
+    assert_eq!(error_kind("float", "nope + 1"), "unknown_variable");
+    assert_eq!(error_kind("float", "nope(1)"), "unknown_function");
+    assert_eq!(error_kind("float", "sqrt(1, 2)"), "arity");
+    assert_eq!(error_kind("float", "ans"), "missing_result");
+    assert_eq!(error_kind("float", "let pi = 3"), "reserved");
+}
+
+#[test]
+fn runaway_recursion_is_an_error() {
+    let mut env = env("float");
+    execute("f(x) = f(x + 1)", &mut env).unwrap();
+    assert_eq!(
+        evaluate("f(1)", &mut env).unwrap_err().kind(),
+        "recursion_limit"
+    );
+}
+
+#[test]
+fn deep_nesting_is_a_parse_error() {
+    let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(eval(&nested(60)), "1");
+    assert_eq!(error_kind("float", &nested(10_000)), "parse");
+    assert_eq!(
+        error_kind("float", &format!("{}1", "-".repeat(10_000))),
+        "parse"
+    );
+    assert_eq!(error_kind("float", &"2^".repeat(10_000)), "parse");
+}
+
+#[test]
+fn failed_lines_leave_the_session_alone() {
+    let mut env = env("float");
+    evaluate("let x = 1", &mut env).unwrap();
+    assert!(evaluate("let x = 1 / 0", &mut env).is_err());
+    assert_eq!(evaluate("x", &mut env).unwrap().to_string(), "1");
+    assert_eq!(env.history.len(), 2);
+}
+
+// Backends
+
+#[test]
+fn float_backend_uses_binary_floating_point() {
+    assert_eq!(eval_in("float", "0.1 + 0.2"), "0.30000000000000004");
+    assert_eq!(eval_in("float", "7 / 2"), "3.5");
+}
+
+#[test]
+fn rational_backend_is_exact() {
+    assert_eq!(eval_in("rational", "0.1 + 0.2"), "3/10");
+    assert_eq!(eval_in("rational", "1/3 * 3"), "1");
+    assert_eq!(eval_in("rational", "(2/3)^-2"), "9/4");
+    assert_eq!(eval_in("rational", "-7 % 3"), "-1");
+}
+
+#[test]
+fn decimal_backend_rounds_to_its_scale() {
+    assert_eq!(eval_in("decimal", "0.1 + 0.2"), "0.3");
+    assert_eq!(eval_in("decimal", "1 / 3"), "0.33333333333333333333");
+    assert_eq!(eval_in("decimal", "2 / 3"), "0.66666666666666666667");
+
+    let backend = backend_for("decimal", 2, OverflowPolicy::Wrapping).unwrap();
+    let mut env = Env::new(backend);
+    assert_eq!(evaluate("10 / 3", &mut env).unwrap().to_string(), "3.33");
+    assert_eq!(evaluate("0.125 + 0", &mut env).unwrap().to_string(), "0.13");
+}
+
+#[test]
+fn exact_modes_refuse_huge_exponents() {
+    for mode in ["decimal", "rational", "integer"] {
+        let mut env = env(mode);
+        assert_eq!(
+            evaluate("2^100000000", &mut env).unwrap_err().to_string(),
+            "Exponent 100000000 is too large for exact arithmetic (limit 10000)"
+        );
+    }
+}
+
+#[test]
+fn integer_backend_has_no_size_limit() {
+    assert_eq!(
+        eval_in("integer", "2^100"),
+        "1267650600228229401496703205376"
+    );
+    assert_eq!(eval_in("integer", "7 / 2"), "3");
+    assert_eq!(eval_in("integer", "-7 / 2"), "-3");
+    assert_eq!(eval_in("integer", "25!"), "15511210043330985984000000");
+    assert_eq!(error_kind("integer", "1.5"), "domain");
+}
+
+#[test]
+fn fixed_width_backend_wraps_by_default() {
+    assert_eq!(eval_in("u8", "255 + 1"), "0 (0x0, 0o0, 0b0)");
+    assert_eq!(eval_in("u8", "0 - 1"), "255 (0xff, 0o377, 0b1111_1111)");
+    assert_eq!(eval_in("i8", "0xff"), "-1 (0xff, 0o377, 0b1111_1111)");
+    assert_eq!(eval_in("i8", "-128 >> 1"), "-64 (0xc0, 0o300, 0b1100_0000)");
+    assert_eq!(eval_in("u16", "5 ^ 3"), "6 (0x6, 0o6, 0b110)");
+    assert_eq!(
+        eval_in("u32", "2 ** 31"),
+        "2147483648 (0x80000000, 0o20000000000, 0b1000_0000_0000_0000_0000_0000_0000_0000)"
+    );
+}
+
+#[test]
+fn fixed_width_backend_can_check_overflow() {
+    let backend = backend_for("i8", DEFAULT_SCALE, OverflowPolicy::Checked).unwrap();
+    let mut env = Env::new(backend);
+    assert_eq!(
+        evaluate("127 + 1", &mut env).unwrap_err().kind(),
+        "overflow"
+    );
+    assert_eq!(
+        evaluate("-128", &mut env).unwrap().to_string(),
+        "-128 (0x80, 0o200, 0b1000_0000)"
+    );
+    assert_eq!(evaluate("1 << 8", &mut env).unwrap_err().kind(), "overflow");
+    assert_eq!(evaluate("0x100", &mut env).unwrap_err().kind(), "overflow");
+}
+
+#[test]
+fn radix_literals_work_in_every_mode() {
+    for mode in ["float", "rational", "decimal", "integer", "u64"] {
+        let result = eval_in(mode, "0x10 + 0o10 + 0b10");
+        assert!(result.starts_with("26"), "{} mode gave {}", mode, result);
+    }
+}
+
+#[test]
+fn number_literals_take_an_exponent() {
+    assert_eq!(eval("1E5"), "100000");
+    assert_eq!(eval("1e308 / 1e307"), "10");
+    assert_eq!(error_kind("float", "1e309"), "overflow");
+    for mode in ["rational", "decimal", "integer"] {
+        assert_eq!(eval_in(mode, "6.02e23"), "602000000000000000000000");
+    }
+    assert_eq!(eval_in("rational", "-2.5e-3"), "-1/400");
+    assert_eq!(error_kind("integer", "1.5e-1"), "domain");
+}
+
+#[test]
+fn values_move_between_modes() {
+    let mut env = env("rational");
+    evaluate("let third = 1/3", &mut env).unwrap();
+    env.backend = backend_for("decimal", 4, OverflowPolicy::Wrapping).unwrap();
+    assert_eq!(evaluate("third", &mut env).unwrap().to_string(), "0.3333");
+    // The variable still holds the exact fraction, not the rounded result
+    env.backend = backend_for("float", DEFAULT_SCALE, OverflowPolicy::Wrapping).unwrap();
+    assert_eq!(evaluate("third * 3", &mut env).unwrap().to_string(), "1");
+}
+
+#[test]
+fn unknown_modes_are_rejected() {
+    assert!(backend_for("complex", DEFAULT_SCALE, OverflowPolicy::Wrapping).is_none());
+    assert!(backend_for("u7", DEFAULT_SCALE, OverflowPolicy::Wrapping).is_none());
+}
+
+// Units
+
+#[test]
+fn units_convert_and_cancel() {
+    assert_eq!(eval("5 km + 300 m in miles"), "3.2932673188578696 mi");
+    assert_eq!(eval("2 GiB / 512 MiB"), "4");
+    assert_eq!(eval("10 km / 2 h"), "5 km/h");
+    assert_eq!(eval("100 degC in degF"), "212 degF");
+    assert_eq!(eval_in("rational", "1 ft in inch"), "12 inch");
+}
+
+#[test]
+fn unit_names_cannot_be_assigned() {
+    let mut env = env("float");
+    for (name, line, result) in [
+        ("s", "10 m / s", "10 m/s"),
+        ("h", "5 h in min", "300 min"),
+        ("m", "2 m + 50 cm in cm", "250 cm"),
+    ] {
+        let error = evaluate(&format!("let {} = 2", name), &mut env).unwrap_err();
+        assert_eq!(error.kind(), "reserved");
+        assert_eq!(evaluate(line, &mut env).unwrap().to_string(), result);
+    }
+    assert_eq!(
+        execute("s(x) = x", &mut env).unwrap_err().kind(),
+        "reserved"
+    );
+    let error = load_workspace("# calc workspace\n\n[variables]\ns = 2", &mut env).unwrap_err();
+    assert_eq!((error.line, error.error.kind()), (4, "reserved"));
+}
+
+#[test]
+fn functions_and_constants() {
+    assert_eq!(eval("max(3, 9, 4) - min(2, 8)"), "7");
+    assert_eq!(eval("round(pi * 100)"), "314");
+    assert_eq!(eval("sqrt(16 m^2)"), "4 m");
+
+    let mut env = env("float");
+    env.angle = calc_core::AngleUnit::Degrees;
+    assert_eq!(evaluate("sin(90)", &mut env).unwrap().to_string(), "1");
+}
+
+// Symbolic math
+
+#[test]
+fn diff_applies_the_product_and_chain_rules() {
+    assert_eq!(
+        eval("diff(x^2 * sin(x), x)"),
+        "2 * x * sin(x) + x^2 * cos(x)"
+    );
+    assert_eq!(eval("diff(x / (x + 1), x)"), "1 / (x + 1)^2");
+    assert_eq!(eval("diff(cos(x)^2, x)"), "-2 * cos(x) * sin(x)");
+    assert_eq!(eval("diff(2^x, x)"), "ln(2) * 2^x");
+    assert_eq!(eval("diff(5, x)"), "0");
+}
+
+#[test]
+fn simplify_folds_constants_and_cancels_like_terms() {
+    assert_eq!(eval("simplify(x + x + 2 * 3)"), "2 * x + 6");
+    assert_eq!(eval("simplify(x * x^2 / x)"), "x^2");
+    assert_eq!(eval("simplify((x + 1) / (x + 1))"), "1");
+    assert_eq!(eval("simplify(2 * (x + 1) - 2)"), "2 * x");
+    assert_eq!(eval_in("u8", "simplify(x - x + 3)"), "3 (0x3, 0o3, 0b11)");
+}
+
+#[test]
+fn symbolic_results_flow_through_the_session() {
+    let mut env = env("float");
+    execute("f(t) = t^3 + 2 * t", &mut env).unwrap();
+    assert_eq!(
+        evaluate("diff(f(y), y)", &mut env).unwrap().to_string(),
+        "3 * y^2 + 2"
+    );
+    assert_eq!(
+        evaluate("ans * 2", &mut env).unwrap().to_string(),
+        "6 * y^2 + 4"
+    );
+
+    // Inside a function body the variable is bound, so the result is a number
+    execute("slope(x) = diff(x^3, x)", &mut env).unwrap();
+    assert_eq!(evaluate("slope(2)", &mut env).unwrap().to_string(), "12");
+}
+
+#[test]
+fn diff_reports_what_it_cannot_do() {
+    assert_eq!(error_kind("float", "diff(floor(x), x)"), "domain");
+    assert_eq!(error_kind("float", "diff(x^2, 2)"), "domain");
+    assert_eq!(error_kind("float", "diff(x^2)"), "arity");
+    assert_eq!(error_kind("float", "let diff = 1"), "reserved");
+}
+
+// Equations
+
+#[test]
+fn a_number_next_to_a_name_multiplies_it() {
+    let mut env = env("float");
+    evaluate("let x = 5", &mut env).unwrap();
+    assert_eq!(evaluate("2x^2", &mut env).unwrap().to_string(), "50");
+    assert_eq!(evaluate("3sqrt(4)", &mut env).unwrap().to_string(), "6");
+    // Units still come first
+    assert_eq!(evaluate("2m", &mut env).unwrap().to_string(), "2 m");
+    assert_eq!(error_kind("float", "2 x"), "parse");
+    // An exponent is part of the number, but a bare `e` is still Euler's number
+    assert_eq!(evaluate("1e-3", &mut env).unwrap().to_string(), "0.001");
+    assert_eq!(evaluate("2.5e+2", &mut env).unwrap().to_string(), "250");
+    assert_eq!(
+        evaluate("6.02e23", &mut env).unwrap().to_string(),
+        "602000000000000000000000"
+    );
+    assert_eq!(evaluate("2e3x", &mut env).unwrap().to_string(), "10000");
+    assert_eq!(
+        evaluate("2e", &mut env).unwrap().to_string(),
+        "5.43656365691809"
+    );
+}
+
+#[test]
+fn solve_finds_every_root_of_one_equation() {
+    assert_eq!(eval("solve(x^2 - 4 = 0, x)"), "x = -2 or x = 2");
+    assert_eq!(eval("solve(x^3 - 2x - 5, x)"), "x = 2.0945514815423265");
+    assert_eq!(eval("solve((x - 1)^2 = 0, x)"), "x = 1");
+    assert_eq!(eval("solve(2x = 4, x) * 3"), "6");
+}
+
+#[test]
+fn solve_handles_linear_systems_exactly() {
+    assert_eq!(eval("solve(2x + 3y = 7, x - y = 1)"), "x = 2, y = 1");
+    assert_eq!(
+        eval_in("rational", "solve(2x + 3y = 7, x - 2y = 1/3)"),
+        "x = 15/7, y = 19/21"
+    );
+    assert_eq!(eval_in("rational", "solve(3x = 1)"), "x = 1/3");
+}
+
+#[test]
+fn solve_handles_nonlinear_systems() {
+    assert_eq!(eval("solve(x * y = 6, x + y = 5)"), "x = 3, y = 2");
+}
+
+#[test]
+fn solve_uses_the_session_tolerance() {
+    let mut env = env("float");
+    env.tolerance = 1e-3;
+    let coarse = evaluate("solve(x^2 = 2, x)", &mut env).unwrap().to_string();
+    assert_ne!(coarse, "x = -1.4142135623730951 or x = 1.4142135623730951");
+    assert_eq!(
+        eval("solve(x^2 = 2, x)"),
+        "x = -1.4142135623730951 or x = 1.4142135623730951"
+    );
+}
+
+#[test]
+fn solve_reports_systems_without_a_single_answer() {
+    assert_eq!(
+        error_kind("float", "solve(x + y = 1, x + y = 2)"),
+        "no_solution"
+    );
+    assert_eq!(error_kind("float", "solve(x^2 = -1, x)"), "no_solution");
+    // A periodic function has more roots than are listed, so none are
+    assert_eq!(
+        error_kind("float", "solve(sin(x) = 0, x)"),
+        "too_many_roots"
+    );
+    assert_eq!(
+        error_kind("float", "solve(x + y = 1, 2x + 2y = 2)"),
+        "underdetermined"
+    );
+    assert_eq!(error_kind("float", "solve(x^2 = 4, x) + 1"), "domain");
+    assert_eq!(error_kind("float", "sqrt(x = 1)"), "domain");
+}
+
+// Matrices
+
+#[test]
+fn matrix_literals_print_the_way_they_are_typed() {
+    assert_eq!(eval("[1, 2; 3, 4]"), "[1, 2; 3, 4]");
+    assert_eq!(eval("transpose([1, 2, 3])"), "[1; 2; 3]");
+    assert_eq!(eval("[1 + 1, 2^3]"), "[2, 8]");
+    assert_eq!(error_kind("float", "[1, 2; 3]"), "parse");
+    assert_eq!(error_kind("float", "[1 m, 2]"), "domain");
+}
+
+#[test]
+fn star_is_the_matrix_product_and_dot_star_is_element_wise() {
+    let mut env = env("float");
+    evaluate("let A = [1, 2; 3, 4]", &mut env).unwrap();
+    let mut eval = |input: &str| evaluate(input, &mut env).unwrap().to_string();
+    assert_eq!(eval("A * A"), "[7, 10; 15, 22]");
+    assert_eq!(eval("A .* A"), "[1, 4; 9, 16]");
+    assert_eq!(eval("A ./ A"), "[1, 1; 1, 1]");
+    assert_eq!(eval("2 * A - 1"), "[1, 3; 5, 7]");
+    assert_eq!(eval("A^3"), "[37, 54; 81, 118]");
+    assert_eq!(eval("abs(-A)"), "[1, 2; 3, 4]");
+    assert_eq!(eval("dot([1, 2, 3], [4; 5; 6])"), "32");
+}
+
+#[test]
+fn det_and_inv_are_exact() {
+    assert_eq!(eval("det([1, 2; 3, 4])"), "-2");
+    assert_eq!(
+        eval_in("rational", "inv([1, 2; 3, 4])"),
+        "[-2, 1; 3/2, -1/2]"
+    );
+    assert_eq!(eval("[1, 2; 3, 4] * inv([1, 2; 3, 4])"), "[1, 0; 0, 1]");
+    assert_eq!(eval_in("rational", "[1, 2; 3, 4]^-1"), "[-2, 1; 3/2, -1/2]");
+    assert_eq!(error_kind("float", "inv([1, 2; 2, 4])"), "domain");
+}
+
+#[test]
+fn shape_mismatches_are_errors() {
+    assert_eq!(error_kind("float", "[1, 2] * [1, 2]"), "shape");
+    assert_eq!(error_kind("float", "[1, 2; 3, 4] + [1, 2]"), "shape");
+    assert_eq!(error_kind("float", "det([1, 2, 3])"), "shape");
+    assert_eq!(error_kind("float", "dot([1, 2], [1, 2, 3])"), "shape");
+    assert_eq!(error_kind("float", "det(5)"), "shape");
+    assert_eq!(error_kind("float", "max([1, 2], 3)"), "shape");
+}
+
+// Statistics
+
+#[test]
+fn aggregates_take_a_list_or_separate_numbers() {
+    assert_eq!(eval("sum([1, 2, 3, 4])"), "10");
+    assert_eq!(eval("product(1, 2, 3, 4)"), "24");
+    assert_eq!(eval("mean([1, 2, 3, 4])"), "2.5");
+    assert_eq!(eval("median(3, 1, 2)"), "2");
+    assert_eq!(eval("median([4, 1, 3, 2])"), "2.5");
+    assert_eq!(eval("mode([3, 1, 3, 2, 2])"), "2");
+    assert_eq!(eval("max([3, 9, 4])"), "9");
+    assert_eq!(eval("sum([1, 2; 3, 4])"), "10");
+}
+
+#[test]
+fn spread_uses_the_sample_formulas() {
+    assert_eq!(
+        eval_in("rational", "variance([2, 4, 4, 4, 5, 5, 7, 9])"),
+        "32/7"
+    );
+    assert_eq!(eval("stdev([1, 3])"), eval("sqrt(2)"));
+    assert_eq!(eval("percentile([1, 2, 3, 4, 5], 90)"), "4.6");
+    assert_eq!(eval("percentile([5, 1, 3], 100)"), "5");
+    assert_eq!(error_kind("float", "variance([7])"), "domain");
+    assert_eq!(error_kind("float", "percentile([1, 2], 101)"), "domain");
+    assert_eq!(error_kind("float", "mean(1 m, 2 m)"), "domain");
+}
+
+#[test]
+fn summarize_describes_a_column() {
+    let backend = backend_for("rational", DEFAULT_SCALE, OverflowPolicy::Wrapping).unwrap();
+    let numbers: Vec<Number> = ["4", "8", "15", "16"]
+        .iter()
+        .map(|n| backend.literal(n).unwrap())
+        .collect();
+    let rows = summarize(&numbers, backend.as_ref()).unwrap();
+    let text: Vec<String> = rows
+        .iter()
+        .take(10)
+        .map(|(name, value)| format!("{} {}", name, value))
+        .collect();
+    assert_eq!(
+        text,
+        [
+            "count 4",
+            "sum 43",
+            "mean 43/4",
+            "median 23/2",
+            "mode 4",
+            "min 4",
+            "q1 7",
+            "q3 61/4",
+            "max 16",
+            "variance 395/12"
+        ]
+    );
+    assert_eq!(rows[10].0, "stdev");
+    assert!(summarize(&[], backend.as_ref()).is_err());
+}
+
+// Dates and durations
+
+#[test]
+fn dates_move_by_lengths_of_time() {
+    assert_eq!(eval("2026-10-18 + 45 days"), "2026-12-02");
+    assert_eq!(eval("2024-02-28 + 1 day"), "2024-02-29");
+    assert_eq!(eval("2026-10-18 14:30 + 90 min"), "2026-10-18T16:00");
+    assert_eq!(eval("2026-10-18 - 1 ms"), "2026-10-17T23:59:59.999");
+    assert_eq!(eval("2026-03-01 - 2024-02-28"), "732d");
+    assert_eq!(eval("2026-01-02 - 2026-01-01 in hours"), "24 h");
+    assert_eq!(eval("2026 - 10 - 18"), "1998");
+}
+
+#[test]
+fn offsets_keep_the_same_moment() {
+    assert_eq!(eval("2026-10-18T09:30+02:00 in UTC"), "2026-10-18T07:30Z");
+    assert_eq!(eval("2026-10-18 in UTC-5"), "2026-10-17T19:00-05:00");
+    assert_eq!(eval("2026-10-18T09:30+02:00 - 2026-10-18T09:30Z"), "-2h");
+}
+
+#[test]
+fn compound_durations_are_values_of_their_own() {
+    assert_eq!(eval("3h 20m * 4"), "13h 20m");
+    assert_eq!(eval("1h 30m + 15 min"), "1h 45m");
+    assert_eq!(eval("1h 30m / 45 min"), "2");
+    assert_eq!(eval("max(1h 30m, 100 min)"), "1h 40m");
+    assert_eq!(eval_in("rational", "1d 12h in days"), "3/2 day");
+}
+
+#[test]
+fn invalid_dates_and_date_arithmetic_are_errors() {
+    assert_eq!(error_kind("float", "2026-02-30"), "parse");
+    assert_eq!(error_kind("float", "2026-10-18T24:00"), "parse");
+    assert_eq!(error_kind("float", "2026-10-18 + 5"), "dimension");
+    assert_eq!(error_kind("float", "2026-10-18 * 2"), "domain");
+    assert_eq!(error_kind("float", "2026-10-18 + 2026-10-19"), "domain");
+    assert_eq!(error_kind("float", "let now = 1"), "reserved");
+}
+
+// Workspaces
+
+#[test]
+fn workspaces_round_trip_the_whole_session() {
+    let mut saved = env("rational");
+    for line in [
+        "f(x) = x^2 + 1",
+        "let third = 1/3",
+        "let trip = 1h 30m",
+        "let start = 2026-10-18",
+        "let grid = [1, 2; 3, 4]",
+        "let slope = diff(x^3, x)",
+        "solve(x^2 = 4)",
+        "f(third)",
+        "5 s",
+        "grid * 2",
+    ] {
+        execute(line, &mut saved).unwrap_or_else(|e| panic!("{} failed: {}", line, e));
+    }
+    let text = save_workspace(&saved);
+    assert!(text.starts_with("# calc workspace\nmode = rational\n"));
+
+    let mut loaded = env("float");
+    load_workspace(&text, &mut loaded).expect("workspace loads");
+    assert_eq!(loaded.backend.name(), "rational");
+    assert_eq!(save_workspace(&loaded), text);
+    assert_eq!(loaded.history.len(), saved.history.len());
+    assert!(loaded
+        .history
+        .iter()
+        .any(|value| value.to_string() == "x = -2 or x = 2"));
+    assert_eq!(
+        evaluate("f(third) + trip / 1 h", &mut loaded)
+            .unwrap()
+            .to_string(),
+        "47/18"
+    );
+    assert_eq!(loaded.variables["start"].to_string(), "2026-10-18");
+}
+
+#[test]
+fn broken_workspaces_leave_the_session_alone() {
+    let mut session = env("float");
+    execute("let x = 2", &mut session).unwrap();
+    let error = load_workspace("x = 1", &mut session).unwrap_err();
+    assert_eq!(error.line, 1);
+    let error =
+        load_workspace("# calc workspace\n\n[variables]\ny = 1 +", &mut session).unwrap_err();
+    assert_eq!((error.line, error.error.kind()), (4, "parse"));
+    assert_eq!(evaluate("x", &mut session).unwrap().to_string(), "2");
+}
+
+// Finance
+
+#[test]
+fn loan_payments_stay_exact_in_decimal_mode() {
+    assert_eq!(
+        eval_in("decimal", "pmt(0.06 / 12, 360, 200000)"),
+        "-1199.10105030550478918308"
+    );
+    assert_eq!(eval_in("decimal", "pmt(0, 10, 1000)"), "-100");
+    assert_eq!(eval_in("decimal", "compound(1000, 0.05, 2)"), "1102.5");
+    assert_eq!(eval_in("decimal", "effective(0.12, 4)"), "0.12550881");
+    assert_eq!(eval_in("decimal", "fv(0.1, 2, -100)"), "210");
+    assert_eq!(eval_in("decimal", "pv(0.1, 2, 0, -121)"), "100");
+    assert_eq!(
+        eval("round(pmt(0.06 / 12, 360, 200000) * 100) / 100"),
+        "-1199.1"
+    );
+}
+
+#[test]
+fn npv_discounts_and_irr_undoes_it() {
+    assert_eq!(eval_in("decimal", "npv(0.1, 110, 121)"), "200");
+    assert_eq!(eval_in("decimal", "npv(0.1, [110, 121]) - 200"), "0");
+    assert_eq!(eval("irr(-100, 110)"), "0.1");
+    assert_eq!(eval("irr([-200, 110, 121])"), "0.1");
+    assert_eq!(error_kind("float", "irr(100, 50)"), "no_solution");
+    assert_eq!(error_kind("float", "pmt(0.1, 2)"), "arity");
+}
+
+#[test]
+fn irr_stops_at_the_precision_of_a_float() {
+    let mut env = env("float");
+    env.tolerance = 1e-30;
+    let rate = evaluate("irr([-100, 50, 60])", &mut env).unwrap();
+    assert!(rate.to_string().starts_with("0.0639"), "got {}", rate);
+}
+
+#[test]
+fn amortization_tables_pay_off_the_loan() {
+    let table = eval_in("decimal", "amortize(0.1, 2, 210)");
+    assert_eq!(table, "[1, 121, 21, 100, 110; 2, 121, 11, 110, 0]");
+    assert!(eval("amortize(0.01, 12, 1000)").ends_with(", 0]"));
+    assert_eq!(error_kind("float", "amortize(0.01, 2.5, 1000)"), "domain");
+
+    // The columns are named for `:export`, until arithmetic changes them
+    let mut env = env("decimal");
+    let columns = |value: Value| match value {
+        Value::Matrix(matrix) => matrix.columns().map(|names| names.join(",")),
+        other => panic!("expected a matrix, got {}", other),
+    };
+    let table = evaluate("amortize(0.1, 2, 210)", &mut env).unwrap();
+    assert_eq!(
+        columns(table).as_deref(),
+        Some("period,payment,interest,principal,balance")
+    );
+    assert_eq!(columns(evaluate("ans * 2", &mut env).unwrap()), None);
+}
+
+// Plots
+
+#[test]
+fn plots_draw_in_the_terminal() {
+    let chart = eval("plot(x^2, -1, 1)");
+    let lines: Vec<&str> = chart.lines().collect();
+    assert_eq!(lines[0], "plot of x^2 for x from -1 to 1");
+    // The parabola touches both top corners and bottoms out in the middle
+    assert_eq!(lines[1], format!("1 │⢳⡀{}⢀⡞", "⠀".repeat(56)));
+    assert!(lines[15].starts_with("0 │⠀") && lines[15].contains("⣀⣀⣀⣀"));
+    assert_eq!(
+        lines[17].split_whitespace().collect::<Vec<_>>(),
+        ["-1", "x", "1"]
+    );
+
+    let chart = eval("plot(sin(x), cos(x), 0, pi)");
+    assert!(chart.contains('*') && chart.contains('+'));
+    assert!(chart.ends_with("* sin(x)   + cos(x)"));
+}
+
+#[test]
+fn plots_break_at_poles_and_skip_undefined_points() {
+    let Value::Plot(plot) = evaluate("plot(tan(x), -pi, pi)", &mut env("float")).unwrap() else {
+        panic!("plot() should give a plot");
+    };
+    // Each branch of tan is its own line
+    assert_eq!(plot.to_svg(400, 300).matches("<polyline").count(), 3);
+    assert!(eval("plot(sqrt(x), -1, 4)").contains("from -1 to 4"));
+    assert!(plot.to_png(40, 30).starts_with(b"\x89PNG\r\n\x1a\n"));
+
+    assert_eq!(error_kind("float", "plot(x * y, 0, 1)"), "domain");
+    assert_eq!(error_kind("float", "plot(sin(x), 1, 0)"), "domain");
+    assert_eq!(error_kind("float", "plot(ln(x), -2, -1)"), "domain");
+    assert_eq!(error_kind("float", "plot(sin(x), 0, 1) + 1"), "domain");
+}
+
+#[test]
+fn plots_are_saved_as_the_call_that_draws_them() {
+    let mut session = env("float");
+    execute("let p = plot(sin(t2), -pi, pi)", &mut session).unwrap();
+    let text = save_workspace(&session);
+    assert!(text.contains("p = plot(sin(t2), -pi, pi)"));
+    let mut loaded = env("float");
+    load_workspace(&text, &mut loaded).unwrap();
+    assert_eq!(loaded.variables["p"], session.variables["p"]);
+}
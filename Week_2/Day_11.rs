@@ -1,99 +1,200 @@
-#[derive(Debug)]
-enum Category {
-    Electronics,
-    Groceries,
-    Clothing,
+/// Position of a category in its `Taxonomy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CategoryId(usize);
+
+/// One node of the category tree, such as `Phones` under `Electronics`
+struct Category {
+    name: String,
+    parent: Option<CategoryId>,
+}
+
+/// Categories defined at runtime as a hierarchy, such as
+/// `Electronics > Phones > Android`
+struct Taxonomy {
+    categories: Vec<Category>,
+}
+
+impl Taxonomy {
+    fn new() -> Taxonomy {
+        Taxonomy {
+            categories: Vec::new(),
+        }
+    }
+
+    // Add whatever is missing along a path such as "Electronics > Phones > Android"
+    // and return the last category on it. Blank names are skipped.
+    fn add_path(&mut self, path: &str) -> Option<CategoryId> {
+        let mut parent = None;
+        for name in path.split('>').map(str::trim).filter(|name| !name.is_empty()) {
+            let id = match self.child(parent, name) {
+                Some(id) => id,
+                None => {
+                    self.categories.push(Category {
+                        name: name.to_string(),
+                        parent,
+                    });
+                    CategoryId(self.categories.len() - 1)
+                }
+            };
+            parent = Some(id);
+        }
+        parent
+    }
+
+    fn name(&self, id: CategoryId) -> &str {
+        &self.categories[id.0].name
+    }
+
+    // The categories directly under `parent`, or the top-level ones for `None`
+    fn children(&self, parent: Option<CategoryId>) -> Vec<CategoryId> {
+        (0..self.categories.len())
+            .map(CategoryId)
+            .filter(|id| self.categories[id.0].parent == parent)
+            .collect()
+    }
+
+    fn child(&self, parent: Option<CategoryId>, name: &str) -> Option<CategoryId> {
+        self.children(parent)
+            .into_iter()
+            .find(|&id| self.name(id) == name)
+    }
 }
 
 struct Item {
     id: i32,
     name: String,
-    category: Category,
+    category: CategoryId,
     quantity: i32,
     price: i32,
 }
 
+/// The items filed directly under one category, and the groups for the
+/// categories below it. The counts and value roll up the whole branch.
+struct CategoryGroup<'a> {
+    name: &'a str,
+    items: Vec<&'a Item>,
+    subcategories: Vec<CategoryGroup<'a>>,
+    item_count: usize,
+    quantity: i64,
+    value: i64,
+}
+
 struct Inventory {
+    categories: Taxonomy,
     items: Vec<Item>,
 }
 
 impl Inventory {
     fn new() -> Inventory {
-        Inventory { items: Vec::new() }
+        Inventory {
+            categories: Taxonomy::new(),
+            items: Vec::new(),
+        }
     }
 
     fn add_item(&mut self, item: Item) {
         self.items.push(item);
     }
 
-    // Method to categorize items
-    fn categorize_items(&self) -> (Vec<&Item>, Vec<&Item>, Vec<&Item>) {
-        let mut electronics = Vec::new();
-        let mut groceries = Vec::new();
-        let mut clothing = Vec::new();
-
-        for item in &self.items {
-            match item.category {
-                Category::Electronics => electronics.push(item),
-                Category::Groceries => groceries.push(item),
-                Category::Clothing => clothing.push(item),
-            }
+    // Method to categorize items into a tree that follows the taxonomy,
+    // one group per top-level category
+    fn categorize(&self) -> Vec<CategoryGroup<'_>> {
+        self.categories
+            .children(None)
+            .into_iter()
+            .map(|id| self.group(id))
+            .collect()
+    }
+
+    fn group(&self, id: CategoryId) -> CategoryGroup<'_> {
+        let items: Vec<&Item> = self.items.iter().filter(|item| item.category == id).collect();
+        let subcategories: Vec<CategoryGroup> = self
+            .categories
+            .children(Some(id))
+            .into_iter()
+            .map(|child| self.group(child))
+            .collect();
+
+        let mut group = CategoryGroup {
+            name: self.categories.name(id),
+            item_count: items.len(),
+            quantity: items.iter().map(|item| i64::from(item.quantity)).sum(),
+            value: items
+                .iter()
+                .map(|item| i64::from(item.quantity) * i64::from(item.price))
+                .sum(),
+            items,
+            subcategories: Vec::new(),
+        };
+        for sub in &subcategories {
+            group.item_count += sub.item_count;
+            group.quantity += sub.quantity;
+            group.value += sub.value;
         }
+        group.subcategories = subcategories;
+        group
+    }
+}
 
-        (electronics, groceries, clothing)
+// Print a group and everything under it, indented by depth
+fn print_group(group: &CategoryGroup, depth: usize) {
+    let indent = "  ".repeat(depth);
+    println!(
+        "{}{}: Items: {}, Quantity: {}, Value: {}",
+        indent, group.name, group.item_count, group.quantity, group.value
+    );
+    for item in &group.items {
+        println!(
+            "{}  ID: {}, Name: {}, Quantity: {}, Price: {}",
+            indent, item.id, item.name, item.quantity, item.price
+        );
+    }
+    for sub in &group.subcategories {
+        print_group(sub, depth + 1);
     }
 }
+
 fn main() {
     let mut inv = Inventory::new();
 
+    // Define the categories at runtime
+    let android = inv.categories.add_path("Electronics > Phones > Android").unwrap();
+    let electronics = inv.categories.add_path("Electronics").unwrap();
+    let groceries = inv.categories.add_path("Groceries > Fruit").unwrap();
+    let clothing = inv.categories.add_path("Clothing").unwrap();
+
     // Add items to the inventory
     inv.add_item(Item {
         id: 1,
         name: "Phone".to_string(),
-        category: Category::Electronics,
+        category: android,
         quantity: 5,
         price: 1000,
     });
     inv.add_item(Item {
         id: 2,
+        name: "Charger".to_string(),
+        category: electronics,
+        quantity: 8,
+        price: 25,
+    });
+    inv.add_item(Item {
+        id: 3,
         name: "Apple".to_string(),
-        category: Category::Groceries,
+        category: groceries,
         quantity: 10,
         price: 2,
     });
     inv.add_item(Item {
-        id: 3,
+        id: 4,
         name: "T-Shirt".to_string(),
-        category: Category::Clothing,
+        category: clothing,
         quantity: 20,
         price: 15,
     });
 
-    // Categorize items
-    let (electronics, groceries, clothing) = inv.categorize_items();
-
-    // Print categorized items
-    println!("Electronics:");
-    for item in electronics {
-        println!(
-            "  ID: {}, Name: {}, Quantity: {}, Price: {}",
-            item.id, item.name, item.quantity, item.price
-        );
-    }
-
-    println!("Groceries:");
-    for item in groceries {
-        println!(
-            "  ID: {}, Name: {}, Quantity: {}, Price: {}",
-            item.id, item.name, item.quantity, item.price
-        );
-    }
-
-    println!("Clothing:");
-    for item in clothing {
-        println!(
-            "  ID: {}, Name: {}, Quantity: {}, Price: {}",
-            item.id, item.name, item.quantity, item.price
-        );
+    // Categorize items and print the tree with its roll-up totals
+    for group in inv.categorize() {
+        print_group(&group, 0);
     }
 }
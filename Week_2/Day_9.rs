@@ -1,25 +1,64 @@
-#[derive(Debug)]
-enum Category {
-    Electronics,
-    Groceries,
-    Clothing,
+/// Position of a category in its `Taxonomy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CategoryId(usize);
+
+/// One node of the category tree, such as `Phones` under `Electronics`
+struct Category {
+    name: String,
+    parent: Option<CategoryId>,
+}
+
+/// Categories defined at runtime as a hierarchy, such as
+/// `Electronics > Phones > Android`
+struct Taxonomy {
+    categories: Vec<Category>,
+}
+
+impl Taxonomy {
+    fn new() -> Taxonomy {
+        Taxonomy {
+            categories: Vec::new(),
+        }
+    }
+
+    // Add a category under `parent`, or at the top level when it has none
+    fn add_category(&mut self, name: &str, parent: Option<CategoryId>) -> CategoryId {
+        self.categories.push(Category {
+            name: name.to_string(),
+            parent,
+        });
+        CategoryId(self.categories.len() - 1)
+    }
+
+    // The full name of a category, such as "Electronics > Phones > Android"
+    fn path(&self, id: CategoryId) -> String {
+        let category = &self.categories[id.0];
+        match category.parent {
+            Some(parent) => format!("{} > {}", self.path(parent), category.name),
+            None => category.name.clone(),
+        }
+    }
 }
 
 struct Item {
     id: i32,
     name: String,
-    category: Category,
+    category: CategoryId,
     quantity: i32,
     price: i32,
 }
 
 struct Inventory {
+    categories: Taxonomy,
     items: Vec<Item>,
 }
 
 impl Inventory {
     fn new() -> Inventory {
-        Inventory { items: Vec::new() }
+        Inventory {
+            categories: Taxonomy::new(),
+            items: Vec::new(),
+        }
     }
 
     fn add_item(&mut self, item: Item) {
@@ -30,10 +69,17 @@ impl Inventory {
 fn main() {
     let mut inv = Inventory::new();
 
+    // Defining the categories at runtime
+    let electronics = inv.categories.add_category("Electronics", None);
+    let phones = inv.categories.add_category("Phones", Some(electronics));
+    let android = inv.categories.add_category("Android", Some(phones));
+    let clothing = inv.categories.add_category("Clothing", None);
+    let groceries = inv.categories.add_category("Groceries", None);
+
     let item1 = Item {
         id: 1,
         name: "Phone".to_string(),
-        category: Category::Electronics,
+        category: android,
         quantity: 5,
         price: 1000,
     };
@@ -41,7 +87,7 @@ fn main() {
     let item2: Item = Item {
         id: 2,
         name: "Shirt".to_string(),
-        category: Category::Clothing,
+        category: clothing,
         quantity: 3,
         price: 20,
     };
@@ -49,7 +95,7 @@ fn main() {
     let item3 = Item {
         id: 3,
         name: "Bread".to_string(),
-        category: Category::Groceries,
+        category: groceries,
         quantity: 2,
         price: 10,
     };
@@ -63,7 +109,11 @@ fn main() {
     let item = &inv.items[0];
 
     println!(
-        "The id {}, name {}, category {:?}, quantity {}, and price {}.",
-        item.id, item.name, item.category, item.quantity, item.price
+        "The id {}, name {}, category {}, quantity {}, and price {}.",
+        item.id,
+        item.name,
+        inv.categories.path(item.category),
+        item.quantity,
+        item.price
     );
 }
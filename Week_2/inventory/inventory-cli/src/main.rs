@@ -0,0 +1,856 @@
+//! `inventory`: keep a stock list in a JSON, CSV or SQLite file and work on
+//! it one subcommand at a time
+
+mod output;
+mod time;
+
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
+
+use serde_json::{json, Value};
+
+use inventory_core::{
+    storage_for, Currency, ExchangeRates, Inventory, InventoryError, Item, ItemUpdate, Location,
+    LocationFilter, Money, Reorder, Stock, StorageError,
+};
+
+use output::{
+    audit_json, audit_row, group_json, group_text, item_json, item_row, locations_text,
+    low_stock_row, margin_json, margin_row, movement_json, order_json, order_text, reorder_text,
+    stock_json, stock_row, table, valuation_json, valuation_rows, AUDIT_HEADER, ITEM_HEADER,
+    LOW_STOCK_HEADER, MARGIN_HEADER, STOCK_HEADER,
+};
+
+const USAGE: &str = "Usage: inventory [options] <command> [arguments]
+
+Commands:
+  add <id> <name> --category <path> --price <amount> [--cost <amount>]
+      [--quantity <n>] [--location <location>] [--supplier <name>]
+      [--reorder <threshold>,<quantity>]
+                          Add an item, creating its category if needed
+  list [--category <path>]
+                          List the items, or those in one category and below
+  show <id>               Show an item and its stock movements
+  update <id> [--name <name>] [--category <path>] [--cost <amount>]
+      [--price <amount>] [--supplier <name>] [--reorder <threshold>,<quantity>]
+                          Change an item's details; an empty supplier or
+                          --reorder off clears them
+  remove <id>             Remove an item
+  restock <id> <quantity> [--location <location>]
+                          Add stock, unplaced unless a location is given
+  sell <id> <quantity> [--location <location>]
+                          Take stock out, unplaced stock first unless a
+                          location is given, warning if the item runs low
+  transfer <id> <quantity> [--from <location>] [--to <location>]
+                          Move stock between locations; leaving one out
+                          means the unplaced stock
+  stock [--location <filter>]
+                          Show where the stock is kept
+  low-stock               List the items below their reorder threshold
+  reorder                 Draft purchase orders for them, one per supplier
+  categorize [--location <filter>]
+                          Show the category tree with totals, counting
+                          only the stock kept there if asked
+  valuation [--currency <code>] [--rates <path>]
+                          Value the stock at cost and at selling price,
+                          converted into one currency if asked
+  margins [--rates <path>]
+                          Show the margin on one unit of each item
+  history [<id>]          Show who changed an item, or anything, and when
+  undo                    Undo the most recent change
+  redo                    Make the most recently undone change again
+  import <path> [--replace]
+                          Add the items from another inventory file, or
+                          replace this inventory's items with its items
+  export <path>           Write the inventory to another file
+
+Options:
+      --store <path>      Inventory file: .json, .csv, or .db for SQLite
+                          (default $INVENTORY_STORE, then
+                          $XDG_DATA_HOME/inventory/inventory.json)
+      --rates <path>      Exchange rates file (default $INVENTORY_RATES)
+      --actor <name>      Who is making the changes, for the audit log
+                          (default $INVENTORY_ACTOR, then $USER)
+      --as-of <time>      Show the inventory as it was at a UTC time such as
+                          2026-10-18 or 2026-10-18T14:03, rebuilt from the
+                          audit log (list, show, stock, categorize,
+                          valuation, margins, low-stock, reorder and history)
+      --json              Print JSON instead of tables
+  -h, --help              Show this help
+
+Categories are paths such as \"Electronics > Phones > Android\", and amounts
+carry their currency, such as \"2.50 USD\". A rates file has a \"base = USD\"
+line, then one \"NGN = 1550.25\" line per currency. A location is a
+warehouse, aisle and bin such as \"Main/A3/07\", and a filter names a whole
+warehouse, an aisle or a bin: \"Main\", \"Main/A3\" or \"Main/A3/07\".";
+
+/// Options that take a value, with the commands that accept them
+const VALUE_OPTIONS: &[(&str, &[&str])] = &[
+    ("--category", &["add", "list", "update"]),
+    ("--quantity", &["add"]),
+    ("--cost", &["add", "update"]),
+    ("--price", &["add", "update"]),
+    ("--supplier", &["add", "update"]),
+    ("--reorder", &["add", "update"]),
+    ("--name", &["update"]),
+    (
+        "--location",
+        &["add", "restock", "sell", "stock", "categorize"],
+    ),
+    ("--from", &["transfer"]),
+    ("--to", &["transfer"]),
+    ("--currency", &["valuation"]),
+    ("--rates", &["valuation", "margins"]),
+    (
+        "--as-of",
+        &[
+            "list",
+            "show",
+            "stock",
+            "categorize",
+            "valuation",
+            "margins",
+            "low-stock",
+            "reorder",
+            "history",
+        ],
+    ),
+];
+
+struct Options {
+    store: Option<PathBuf>,
+    actor: Option<String>,
+    replace: bool,
+    /// `--category`, `--price` and the rest, as given
+    values: Vec<(String, String)>,
+    /// The command and its positional arguments
+    words: Vec<String>,
+}
+
+impl Options {
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(name, _)| name == flag)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, Failure> {
+    let mut options = Options {
+        store: None,
+        actor: None,
+        replace: false,
+        values: Vec::new(),
+        words: Vec::new(),
+    };
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .ok_or_else(|| Failure::Usage(format!("{} expects a value", flag)))
+        };
+        match arg.as_str() {
+            // Read by `main`, which needs it even when the rest does not parse
+            "--json" => {}
+            "--replace" => options.replace = true,
+            "--store" => options.store = Some(PathBuf::from(value(&arg)?)),
+            "--actor" => options.actor = Some(value(&arg)?),
+            flag if VALUE_OPTIONS.iter().any(|(name, _)| *name == flag) => {
+                let text = value(flag)?;
+                options.values.push((arg, text));
+            }
+            flag if flag.starts_with("--") => {
+                return Err(Failure::Usage(format!("Unknown option '{}'", flag)))
+            }
+            _ => options.words.push(arg),
+        }
+    }
+
+    let command = options.words.first().map(String::as_str).unwrap_or("");
+    for (flag, _) in &options.values {
+        let (_, commands) = VALUE_OPTIONS.iter().find(|(name, _)| name == flag).unwrap();
+        if !commands.contains(&command) {
+            return Err(Failure::Usage(format!(
+                "{} does not apply to '{}'",
+                flag, command
+            )));
+        }
+    }
+    if options.replace && command != "import" {
+        return Err(Failure::Usage(format!(
+            "--replace does not apply to '{}'",
+            command
+        )));
+    }
+    Ok(options)
+}
+
+/// Why a command did not run
+enum Failure {
+    Usage(String),
+    Inventory(InventoryError),
+    Storage(StorageError),
+}
+
+impl Failure {
+    fn kind(&self) -> &'static str {
+        match self {
+            Failure::Usage(_) => "usage",
+            Failure::Inventory(e) => e.kind(),
+            Failure::Storage(_) => "storage",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Failure::Usage(message) => message.clone(),
+            Failure::Inventory(e) => e.to_string(),
+            Failure::Storage(e) => e.to_string(),
+        }
+    }
+}
+
+impl From<InventoryError> for Failure {
+    fn from(e: InventoryError) -> Failure {
+        Failure::Inventory(e)
+    }
+}
+
+impl From<StorageError> for Failure {
+    fn from(e: StorageError) -> Failure {
+        Failure::Storage(e)
+    }
+}
+
+/// What a command prints, both ways, and whether the inventory changed
+struct Report {
+    text: String,
+    json: Value,
+    changed: bool,
+}
+
+impl Report {
+    fn read(text: String, json: Value) -> Report {
+        Report {
+            text,
+            json,
+            changed: false,
+        }
+    }
+
+    fn changed(text: String, json: Value) -> Report {
+        Report {
+            text,
+            json,
+            changed: true,
+        }
+    }
+}
+
+/// `--store`, then `$INVENTORY_STORE`, then `inventory.json` in the XDG
+/// data directory
+fn store_path(options: &Options) -> Result<PathBuf, Failure> {
+    if let Some(path) = &options.store {
+        return Ok(path.clone());
+    }
+    if let Some(path) = env::var_os("INVENTORY_STORE").filter(|p| !p.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    let base = match env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => PathBuf::from(env::var_os("HOME").ok_or_else(|| {
+            Failure::Usage("Cannot find a data directory; use --store <path>".to_string())
+        })?)
+        .join(".local/share"),
+    };
+    Ok(base.join("inventory").join("inventory.json"))
+}
+
+/// The positional argument after the command, by name for error messages
+fn word<'a>(options: &'a Options, index: usize, name: &str) -> Result<&'a str, Failure> {
+    options
+        .words
+        .get(index)
+        .map(String::as_str)
+        .ok_or_else(|| Failure::Usage(format!("'{}' expects {}", options.words[0], name)))
+}
+
+fn number(text: &str, name: &str) -> Result<i32, Failure> {
+    text.parse()
+        .map_err(|_| Failure::Usage(format!("{} must be a whole number, found '{}'", name, text)))
+}
+
+/// An amount such as `2.50 USD`
+fn amount(text: &str, name: &str) -> Result<Money, Failure> {
+    Money::parse(text).map_err(|e| match e {
+        InventoryError::AmountTooLarge => Failure::Inventory(e),
+        _ => Failure::Usage(format!(
+            "{} must be an amount such as 2.50 USD, found '{}'",
+            name, text
+        )),
+    })
+}
+
+/// `<threshold>,<quantity>`, or `off` for no rule
+fn reorder_rule(text: &str) -> Result<Option<Reorder>, Failure> {
+    if text == "off" {
+        return Ok(None);
+    }
+    let (threshold, quantity) = text.split_once(',').ok_or_else(|| {
+        Failure::Usage(format!(
+            "--reorder must be <threshold>,<quantity> or off, found '{}'",
+            text
+        ))
+    })?;
+    Ok(Some(Reorder {
+        threshold: number(threshold.trim(), "The reorder threshold")?,
+        quantity: number(quantity.trim(), "The reorder quantity")?,
+    }))
+}
+
+/// The location given with `flag`, such as `Main/A3/07`
+fn location(options: &Options, flag: &str) -> Result<Option<Location>, Failure> {
+    Ok(options.value(flag).map(Location::parse).transpose()?)
+}
+
+/// The location filter from `--location`, such as `Main` or `Main/A3`
+fn location_filter(options: &Options) -> Result<Option<LocationFilter>, Failure> {
+    Ok(options
+        .value("--location")
+        .map(LocationFilter::parse)
+        .transpose()?)
+}
+
+/// The table from `--rates` or `$INVENTORY_RATES`. Without either only
+/// amounts already in the same currency can be compared.
+fn exchange_rates(options: &Options) -> Result<ExchangeRates, Failure> {
+    let path = options.value("--rates").map(PathBuf::from).or_else(|| {
+        env::var_os("INVENTORY_RATES")
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+    });
+    match path {
+        Some(path) => Ok(ExchangeRates::load(&path)?),
+        None => Ok(ExchangeRates::new(Currency::NONE)),
+    }
+}
+
+fn check_arity(options: &Options, count: usize) -> Result<(), Failure> {
+    match options.words.get(count + 1) {
+        Some(extra) => Err(Failure::Usage(format!(
+            "Unexpected argument '{}' to '{}'",
+            extra, options.words[0]
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn run_command(options: &Options, inv: &mut Inventory) -> Result<Report, Failure> {
+    let command = options.words.first().map(String::as_str).unwrap_or("");
+    match command {
+        "add" => {
+            check_arity(options, 2)?;
+            let id = number(word(options, 1, "an item ID")?, "The item ID")?;
+            let name = word(options, 2, "an item name")?.to_string();
+            let path = options
+                .value("--category")
+                .ok_or_else(|| Failure::Usage("'add' needs --category <path>".to_string()))?;
+            let quantity = options
+                .value("--quantity")
+                .map_or(Ok(0), |q| number(q, "--quantity"))?;
+            let price = options
+                .value("--price")
+                .ok_or_else(|| Failure::Usage("'add' needs --price <amount>".to_string()))
+                .and_then(|p| amount(p, "--price"))?;
+            let cost = options
+                .value("--cost")
+                .map_or(Ok(Money::zero(price.currency)), |c| amount(c, "--cost"))?;
+            let locations = match location(options, "--location")? {
+                Some(_) if quantity == 0 => {
+                    return Err(Failure::Usage(
+                        "'add --location' needs a --quantity to put there".to_string(),
+                    ))
+                }
+                Some(location) => vec![Stock { location, quantity }],
+                None => Vec::new(),
+            };
+
+            // The category is only kept if the item goes in with it
+            let mut updated = inv.clone();
+            let category = updated.categories.add_path(path)?;
+            updated.add_item(Item {
+                id,
+                name,
+                category,
+                quantity,
+                cost,
+                price,
+                supplier: options
+                    .value("--supplier")
+                    .filter(|s| !s.trim().is_empty())
+                    .map(str::to_string),
+                reorder: options
+                    .value("--reorder")
+                    .map(reorder_rule)
+                    .transpose()?
+                    .flatten(),
+                locations,
+            })?;
+            *inv = updated;
+            let item = inv.get_item_by_id(id)?;
+            Ok(Report::changed(
+                format!("Added item {} ({})", item.id, item.name),
+                item_json(inv, item),
+            ))
+        }
+        "list" => {
+            check_arity(options, 0)?;
+            let within = options
+                .value("--category")
+                .map(|path| inv.categories.find(path))
+                .transpose()?;
+            let items: Vec<&Item> = inv
+                .items()
+                .iter()
+                .filter(|item| within.is_none_or(|c| inv.categories.is_under(item.category, c)))
+                .collect();
+            let rows: Vec<Vec<String>> = items.iter().map(|item| item_row(inv, item)).collect();
+            let text = if rows.is_empty() {
+                "No items".to_string()
+            } else {
+                table(ITEM_HEADER, &rows)
+            };
+            let json = items.iter().map(|item| item_json(inv, item)).collect();
+            Ok(Report::read(text, json))
+        }
+        "show" => {
+            check_arity(options, 1)?;
+            let id = number(word(options, 1, "an item ID")?, "The item ID")?;
+            let item = inv.get_item_by_id(id)?;
+            let movements = inv.movements_for(id);
+            let mut text = table(ITEM_HEADER, &[item_row(inv, item)]);
+            let rows: Vec<Vec<String>> = movements
+                .iter()
+                .map(|m| {
+                    vec![
+                        m.kind.to_string(),
+                        format!("{:+}", m.change),
+                        m.balance.to_string(),
+                    ]
+                })
+                .collect();
+            if let Some(supplier) = &item.supplier {
+                text.push_str(&format!("\n\nSupplier: {}", supplier));
+            }
+            if let Some(reorder) = item.reorder {
+                text.push_str(&format!("\nReorder: {}", reorder_text(reorder)));
+            }
+            if !item.locations.is_empty() {
+                text.push_str(&format!(
+                    "\nLocations: {}; {} unplaced",
+                    locations_text(item),
+                    item.unplaced()
+                ));
+            }
+            text.push_str("\n\nMovements:\n");
+            text.push_str(&table(&["Kind", "Change", "Balance"], &rows));
+            let mut json = item_json(inv, item);
+            json["movements"] = movements.iter().map(|m| movement_json(m)).collect();
+            Ok(Report::read(text, json))
+        }
+        "update" => {
+            check_arity(options, 1)?;
+            let id = number(word(options, 1, "an item ID")?, "The item ID")?;
+            let mut updated = inv.clone();
+            let update = ItemUpdate {
+                name: options.value("--name").map(str::to_string),
+                category: options
+                    .value("--category")
+                    .map(|path| updated.categories.add_path(path))
+                    .transpose()?,
+                cost: options
+                    .value("--cost")
+                    .map(|c| amount(c, "--cost"))
+                    .transpose()?,
+                price: options
+                    .value("--price")
+                    .map(|p| amount(p, "--price"))
+                    .transpose()?,
+                supplier: options
+                    .value("--supplier")
+                    .map(|s| Some(s.trim()).filter(|s| !s.is_empty()).map(str::to_string)),
+                reorder: options.value("--reorder").map(reorder_rule).transpose()?,
+            };
+            if update == ItemUpdate::default() {
+                return Err(Failure::Usage(
+                    "'update' needs --name, --category, --cost, --price, --supplier or --reorder"
+                        .to_string(),
+                ));
+            }
+            updated.update_item(id, update)?;
+            *inv = updated;
+            let item = inv.get_item_by_id(id)?;
+            Ok(Report::changed(
+                format!("Updated item {} ({})", item.id, item.name),
+                item_json(inv, item),
+            ))
+        }
+        "remove" => {
+            check_arity(options, 1)?;
+            let id = number(word(options, 1, "an item ID")?, "The item ID")?;
+            let item = inv.remove_item(id)?;
+            Ok(Report::changed(
+                format!("Removed item {} ({})", item.id, item.name),
+                item_json(inv, &item),
+            ))
+        }
+        "restock" | "sell" => {
+            check_arity(options, 2)?;
+            let id = number(word(options, 1, "an item ID")?, "The item ID")?;
+            let quantity = number(word(options, 2, "a quantity")?, "The quantity")?;
+            let (low, fell_low) = mpsc::channel();
+            inv.on_low_stock(move |item| {
+                let _ = low.send(item.id);
+            });
+            let balance = match (command, location(options, "--location")?) {
+                ("restock", None) => inv.restock(id, quantity)?,
+                ("restock", Some(location)) => inv.restock_at(id, &location, quantity)?,
+                (_, None) => inv.sell(id, quantity)?,
+                (_, Some(location)) => inv.sell_from(id, &location, quantity)?,
+            };
+            let verb = if command == "restock" {
+                "Restocked"
+            } else {
+                "Sold"
+            };
+            let mut text = format!("{} {} of item {}, {} in stock", verb, quantity, id, balance);
+            let fell_low = fell_low.try_recv().is_ok();
+            if fell_low {
+                let item = inv.get_item_by_id(id)?;
+                if let Some(reorder) = item.reorder {
+                    text.push_str(&format!(
+                        "\nItem {} ({}) is low on stock: {}",
+                        item.id,
+                        item.name,
+                        reorder_text(reorder)
+                    ));
+                }
+            }
+            Ok(Report::changed(
+                text,
+                json!({ "id": id, "quantity": balance, "fell_low": fell_low }),
+            ))
+        }
+        "transfer" => {
+            check_arity(options, 2)?;
+            let id = number(word(options, 1, "an item ID")?, "The item ID")?;
+            let quantity = number(word(options, 2, "a quantity")?, "The quantity")?;
+            let (from, to) = (location(options, "--from")?, location(options, "--to")?);
+            inv.transfer(id, from.as_ref(), to.as_ref(), quantity)?;
+            let item = inv.get_item_by_id(id)?;
+            let place = |location: &Option<Location>| {
+                location
+                    .as_ref()
+                    .map_or("unplaced stock".to_string(), Location::to_string)
+            };
+            Ok(Report::changed(
+                format!(
+                    "Moved {} of item {} ({}) from {} to {}",
+                    quantity,
+                    item.id,
+                    item.name,
+                    place(&from),
+                    place(&to)
+                ),
+                item_json(inv, item),
+            ))
+        }
+        "stock" => {
+            check_arity(options, 0)?;
+            let filter = location_filter(options)?;
+            let levels = inv.stock_levels(filter.as_ref());
+            let total = inv.total_stock(filter.as_ref());
+            let mut rows: Vec<Vec<String>> = levels.iter().map(stock_row).collect();
+            let text = if rows.is_empty() {
+                "No stock".to_string()
+            } else {
+                rows.push(vec![
+                    "Total".to_string(),
+                    String::new(),
+                    String::new(),
+                    total.to_string(),
+                ]);
+                table(STOCK_HEADER, &rows)
+            };
+            let json = json!({
+                "levels": levels.iter().map(stock_json).collect::<Vec<_>>(),
+                "total": total,
+            });
+            Ok(Report::read(text, json))
+        }
+        "low-stock" => {
+            check_arity(options, 0)?;
+            let items = inv.low_stock();
+            let rows: Vec<Vec<String>> = items.iter().map(|item| low_stock_row(item)).collect();
+            let text = if rows.is_empty() {
+                "No items are low on stock".to_string()
+            } else {
+                table(LOW_STOCK_HEADER, &rows)
+            };
+            let json = items.iter().map(|item| item_json(inv, item)).collect();
+            Ok(Report::read(text, json))
+        }
+        "reorder" => {
+            check_arity(options, 0)?;
+            let orders = inv.purchase_orders();
+            let text = if orders.is_empty() {
+                "Nothing to reorder".to_string()
+            } else {
+                let texts: Vec<String> = orders.iter().map(order_text).collect();
+                texts.join("\n\n")
+            };
+            let json = orders.iter().map(order_json).collect();
+            Ok(Report::read(text, json))
+        }
+        "categorize" => {
+            check_arity(options, 0)?;
+            let filter = location_filter(options)?;
+            let tree = match &filter {
+                Some(filter) => inv.categorize_at(filter),
+                None => inv.categorize(),
+            };
+            let mut lines = Vec::new();
+            for group in &tree {
+                group_text(group, filter.as_ref(), 0, &mut lines);
+            }
+            if lines.is_empty() {
+                lines.push("No categories".to_string());
+            }
+            let json = tree.iter().map(|group| group_json(inv, group)).collect();
+            Ok(Report::read(lines.join("\n"), json))
+        }
+        "valuation" => {
+            check_arity(options, 0)?;
+            let valuation = inv.valuation();
+            let target = options.value("--currency").map(Currency::new).transpose()?;
+            let total = match target {
+                Some(currency) => {
+                    let rates = exchange_rates(options)?;
+                    let cost = rates.total(&valuation.cost, currency)?;
+                    let retail = rates.total(&valuation.retail, currency)?;
+                    Some((cost, retail))
+                }
+                None => None,
+            };
+
+            let mut rows = valuation_rows(&valuation);
+            if let Some((cost, retail)) = total {
+                rows.push(vec![
+                    "Total".to_string(),
+                    cost.to_string(),
+                    retail.to_string(),
+                ]);
+            }
+            let text = if rows.is_empty() {
+                "No items".to_string()
+            } else {
+                table(&["Currency", "Cost", "Retail"], &rows)
+            };
+            Ok(Report::read(text, valuation_json(&valuation, total)))
+        }
+        "margins" => {
+            check_arity(options, 0)?;
+            let margins = inv.margins(&exchange_rates(options)?)?;
+            let rows: Vec<Vec<String>> = margins.iter().map(margin_row).collect();
+            let text = if rows.is_empty() {
+                "No items".to_string()
+            } else {
+                table(MARGIN_HEADER, &rows)
+            };
+            let json = margins.iter().map(margin_json).collect();
+            Ok(Report::read(text, json))
+        }
+        "history" => {
+            check_arity(options, 1)?;
+            let records: Vec<_> = match options.words.get(1) {
+                Some(id) => inv.history(number(id, "The item ID")?),
+                None => inv.audit_log().iter().collect(),
+            };
+            let rows: Vec<Vec<String>> = records
+                .iter()
+                .map(|record| audit_row(inv, record))
+                .collect();
+            let text = if rows.is_empty() {
+                "No changes".to_string()
+            } else {
+                table(AUDIT_HEADER, &rows)
+            };
+            let json = records
+                .iter()
+                .map(|record| audit_json(inv, record))
+                .collect();
+            Ok(Report::read(text, json))
+        }
+        "undo" | "redo" => {
+            check_arity(options, 0)?;
+            let records = if command == "undo" {
+                inv.undo()?
+            } else {
+                inv.redo()?
+            };
+            let verb = if command == "undo" { "Undid" } else { "Redid" };
+            // An import is one change made of a record per item
+            let lines: Vec<String> = records
+                .iter()
+                .map(|record| {
+                    format!(
+                        "{} {} of item {} by {} at {}",
+                        verb,
+                        record.operation,
+                        record.item_id,
+                        record.actor,
+                        time::format(record.timestamp)
+                    )
+                })
+                .collect();
+            let json = records
+                .iter()
+                .map(|record| audit_json(inv, record))
+                .collect();
+            Ok(Report::changed(lines.join("\n"), json))
+        }
+        "import" => {
+            check_arity(options, 1)?;
+            let path = Path::new(word(options, 1, "a file to import")?);
+            let source = storage_for(path)?.load()?;
+            let count = source.items().len();
+            // One change, so a single undo takes the whole import back out,
+            // and nothing is kept unless every item goes in
+            inv.grouped(|inv| {
+                if options.replace {
+                    // The old items are taken out one by one rather than the
+                    // file swapped in, so the audit log keeps their history
+                    // and says who replaced them. Categories are only ever
+                    // added, so the file's join the ones already here.
+                    let ids: Vec<i32> = inv.items().iter().map(|item| item.id).collect();
+                    for id in ids {
+                        inv.remove_item(id)?;
+                    }
+                    for id in source.categories.ids() {
+                        inv.categories.add_path(&source.categories.path(id))?;
+                    }
+                }
+                for item in source.items() {
+                    let category = inv
+                        .categories
+                        .add_path(&source.categories.path(item.category))?;
+                    inv.add_item(Item {
+                        category,
+                        ..item.clone()
+                    })?;
+                }
+                Ok(())
+            })?;
+            Ok(Report::changed(
+                format!("Imported {} items from {}", count, path.display()),
+                json!({ "imported": count, "replaced": options.replace }),
+            ))
+        }
+        "export" => {
+            check_arity(options, 1)?;
+            let path = Path::new(word(options, 1, "a file to export to")?);
+            storage_for(path)?.save(inv)?;
+            Ok(Report::read(
+                format!("Exported {} items to {}", inv.items().len(), path.display()),
+                json!({ "exported": inv.items().len(), "path": path.display().to_string() }),
+            ))
+        }
+        "" => Err(Failure::Usage("Missing command".to_string())),
+        other => Err(Failure::Usage(format!("Unknown command '{}'", other))),
+    }
+}
+
+/// `--actor`, then `$INVENTORY_ACTOR`, then `$USER`
+fn actor(options: &Options) -> Option<String> {
+    options.actor.clone().or_else(|| {
+        ["INVENTORY_ACTOR", "USER"]
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+    })
+}
+
+fn run(options: &Options) -> Result<Report, Failure> {
+    let storage = storage_for(&store_path(options)?)?;
+    let mut inv = storage.load()?;
+    if let Some(actor) = actor(options) {
+        inv.set_actor(actor);
+    }
+    if let Some(text) = options.value("--as-of") {
+        let timestamp = time::parse(text).ok_or_else(|| {
+            Failure::Usage(format!(
+                "--as-of must be a time such as 2026-10-18T14:03, found '{}'",
+                text
+            ))
+        })?;
+        // Only commands that read take --as-of, so this is never saved
+        inv = inv.as_of(timestamp);
+    }
+    let report = run_command(options, &mut inv)?;
+    if report.changed {
+        storage.save(&inv)?;
+    }
+    Ok(report)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let json = args.iter().any(|arg| arg == "--json");
+    let failure = match parse_args(args.into_iter()).and_then(|options| run(&options)) {
+        Ok(report) => {
+            let text = if json {
+                serde_json::to_string_pretty(&report.json).unwrap()
+            } else {
+                report.text
+            };
+            return match writeln!(io::stdout().lock(), "{}", text) {
+                Ok(()) => ExitCode::SUCCESS,
+                // The reader stopped early, as `head` does; the command
+                // itself still ran
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("Error: cannot write the output: {}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Err(failure) => failure,
+    };
+
+    if json {
+        let error = json!({ "error": { "kind": failure.kind(), "message": failure.message() } });
+        // The exit code still reports the failure if no one reads this
+        let _ = writeln!(
+            io::stdout().lock(),
+            "{}",
+            serde_json::to_string_pretty(&error).unwrap()
+        );
+    } else if let Failure::Usage(message) = &failure {
+        eprintln!("Error: {}\n\n{}", message, USAGE);
+    } else {
+        eprintln!("Error: {}", failure.message());
+    }
+    match failure {
+        Failure::Usage(_) => ExitCode::from(2),
+        _ => ExitCode::FAILURE,
+    }
+}
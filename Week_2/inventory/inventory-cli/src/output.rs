@@ -0,0 +1,379 @@
+//! Tables for people and JSON for scripts
+
+use serde_json::{json, Value};
+
+use inventory_core::{
+    Amounts, AuditRecord, CategoryGroup, Inventory, Item, LocationFilter, Margin, Money, Movement,
+    PurchaseOrder, Reorder, StockLevel, Valuation,
+};
+
+/// Lay out rows under a header, numbers right-aligned and text left-aligned
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let numeric: Vec<bool> = (0..header.len())
+        .map(|column| {
+            !rows.is_empty()
+                && rows
+                    .iter()
+                    .all(|row| row[column].parse::<f64>().is_ok() || row[column].is_empty())
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain([header[column].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |cells: Vec<&str>| -> String {
+        let padded: Vec<String> = cells
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| {
+                if numeric[column] {
+                    format!("{:>1$}", cell, widths[column])
+                } else {
+                    format!("{:<1$}", cell, widths[column])
+                }
+            })
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    let mut lines = vec![line(header.to_vec())];
+    let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+    lines.push(line(rule.iter().map(String::as_str).collect()));
+    lines.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(String::as_str).collect())),
+    );
+    lines.join("\n")
+}
+
+pub const ITEM_HEADER: &[&str] = &["ID", "Name", "Category", "Quantity", "Cost", "Price"];
+
+pub fn item_row(inv: &Inventory, item: &Item) -> Vec<String> {
+    vec![
+        item.id.to_string(),
+        item.name.clone(),
+        inv.categories.path(item.category),
+        item.quantity.to_string(),
+        item.cost.to_string(),
+        item.price.to_string(),
+    ]
+}
+
+pub fn item_json(inv: &Inventory, item: &Item) -> Value {
+    json!({
+        "id": item.id,
+        "name": item.name,
+        "category": inv.categories.path(item.category),
+        "quantity": item.quantity,
+        "cost": item.cost.to_string(),
+        "price": item.price.to_string(),
+        "supplier": item.supplier,
+        "reorder": item.reorder.map(|reorder| json!({
+            "threshold": reorder.threshold,
+            "quantity": reorder.quantity,
+        })),
+        "locations": item
+            .locations
+            .iter()
+            .map(|stock| json!({
+                "location": stock.location.to_string(),
+                "quantity": stock.quantity,
+            }))
+            .collect::<Vec<_>>(),
+        "unplaced": item.unplaced(),
+    })
+}
+
+/// `Main/A3/07: 3, Annex/A1/01: 2`
+pub fn locations_text(item: &Item) -> String {
+    let stock: Vec<String> = item
+        .locations
+        .iter()
+        .map(|stock| format!("{}: {}", stock.location, stock.quantity))
+        .collect();
+    stock.join(", ")
+}
+
+pub const STOCK_HEADER: &[&str] = &["Location", "ID", "Name", "Quantity"];
+
+pub fn stock_row(level: &StockLevel) -> Vec<String> {
+    vec![
+        level
+            .location
+            .map_or("unplaced".to_string(), |location| location.to_string()),
+        level.item.id.to_string(),
+        level.item.name.clone(),
+        level.quantity.to_string(),
+    ]
+}
+
+pub fn stock_json(level: &StockLevel) -> Value {
+    json!({
+        "location": level.location.map(|location| location.to_string()),
+        "id": level.item.id,
+        "name": level.item.name,
+        "quantity": level.quantity,
+    })
+}
+
+pub fn movement_json(movement: &Movement) -> Value {
+    json!({
+        "kind": movement.kind.name(),
+        "change": movement.change,
+        "balance": movement.balance,
+    })
+}
+
+/// One line per category, indented by depth, with the items under it and
+/// how many of each are in the locations `filter` matches, if there is one
+pub fn group_text(
+    group: &CategoryGroup,
+    filter: Option<&LocationFilter>,
+    depth: usize,
+    lines: &mut Vec<String>,
+) {
+    let indent = "  ".repeat(depth);
+    let noun = if group.item_count == 1 {
+        "item"
+    } else {
+        "items"
+    };
+    lines.push(format!(
+        "{}{}: {} {}, quantity {}, value {}",
+        indent, group.name, group.item_count, noun, group.quantity, group.value
+    ));
+    for item in &group.items {
+        let quantity = filter.map_or(item.quantity, |filter| item.quantity_at(filter));
+        lines.push(format!(
+            "{}  #{} {} x{} @ {}",
+            indent, item.id, item.name, quantity, item.price
+        ));
+    }
+    for sub in &group.subcategories {
+        group_text(sub, filter, depth + 1, lines);
+    }
+}
+
+pub fn group_json(inv: &Inventory, group: &CategoryGroup) -> Value {
+    json!({
+        "name": group.name,
+        "path": inv.categories.path(group.id),
+        "item_count": group.item_count,
+        "quantity": group.quantity,
+        "value": amounts_json(&group.value),
+        "items": group.items.iter().map(|item| item_json(inv, item)).collect::<Vec<_>>(),
+        "subcategories": group
+            .subcategories
+            .iter()
+            .map(|sub| group_json(inv, sub))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// One amount per currency, as `["5000.00 NGN", "12.50 USD"]`
+fn amounts_json(amounts: &Amounts) -> Value {
+    amounts.iter().map(|money| money.to_string()).collect()
+}
+
+/// One row per currency held at cost or at retail
+pub fn valuation_rows(valuation: &Valuation) -> Vec<Vec<String>> {
+    let mut currencies: Vec<_> = valuation
+        .cost
+        .iter()
+        .chain(valuation.retail.iter())
+        .map(|money| money.currency)
+        .collect();
+    currencies.sort();
+    currencies.dedup();
+    currencies
+        .into_iter()
+        .map(|currency| {
+            vec![
+                currency.to_string(),
+                valuation.cost.get(currency).to_string(),
+                valuation.retail.get(currency).to_string(),
+            ]
+        })
+        .collect()
+}
+
+pub fn valuation_json(valuation: &Valuation, total: Option<(Money, Money)>) -> Value {
+    json!({
+        "cost": amounts_json(&valuation.cost),
+        "retail": amounts_json(&valuation.retail),
+        "total": total.map(|(cost, retail)| json!({
+            "currency": retail.currency.to_string(),
+            "cost": cost.to_string(),
+            "retail": retail.to_string(),
+        })),
+    })
+}
+
+pub const MARGIN_HEADER: &[&str] = &["ID", "Name", "Cost", "Price", "Margin", "Margin %"];
+
+/// Hundredths of a percent as `12.50`
+fn percent(basis_points: i64) -> String {
+    let sign = if basis_points < 0 { "-" } else { "" };
+    let points = basis_points.unsigned_abs();
+    format!("{}{}.{:02}", sign, points / 100, points % 100)
+}
+
+pub fn margin_row(margin: &Margin) -> Vec<String> {
+    vec![
+        margin.item.id.to_string(),
+        margin.item.name.clone(),
+        margin.cost.to_string(),
+        margin.item.price.to_string(),
+        margin.margin.to_string(),
+        margin.basis_points.map(percent).unwrap_or_default(),
+    ]
+}
+
+pub fn margin_json(margin: &Margin) -> Value {
+    json!({
+        "id": margin.item.id,
+        "name": margin.item.name,
+        "cost": margin.cost.to_string(),
+        "price": margin.item.price.to_string(),
+        "margin": margin.margin.to_string(),
+        "margin_basis_points": margin.basis_points,
+    })
+}
+
+/// `order 20 when below 5`
+pub fn reorder_text(reorder: Reorder) -> String {
+    format!(
+        "order {} when below {}",
+        reorder.quantity, reorder.threshold
+    )
+}
+
+pub const LOW_STOCK_HEADER: &[&str] = &["ID", "Name", "Quantity", "Threshold", "Supplier"];
+
+pub fn low_stock_row(item: &Item) -> Vec<String> {
+    vec![
+        item.id.to_string(),
+        item.name.clone(),
+        item.quantity.to_string(),
+        item.reorder
+            .map(|reorder| reorder.threshold.to_string())
+            .unwrap_or_default(),
+        item.supplier.clone().unwrap_or_default(),
+    ]
+}
+
+/// A heading naming the supplier, the lines and the total
+pub fn order_text(order: &PurchaseOrder) -> String {
+    let rows: Vec<Vec<String>> = order
+        .lines
+        .iter()
+        .map(|line| {
+            vec![
+                line.item.id.to_string(),
+                line.item.name.clone(),
+                line.quantity.to_string(),
+                line.item.cost.to_string(),
+                line.cost.to_string(),
+            ]
+        })
+        .collect();
+    format!(
+        "Purchase order: {}\n{}\nTotal: {}",
+        order.supplier.unwrap_or("no supplier"),
+        table(&["ID", "Name", "Quantity", "Unit Cost", "Cost"], &rows),
+        order.total
+    )
+}
+
+pub fn order_json(order: &PurchaseOrder) -> Value {
+    json!({
+        "supplier": order.supplier,
+        "lines": order
+            .lines
+            .iter()
+            .map(|line| json!({
+                "id": line.item.id,
+                "name": line.item.name,
+                "quantity": line.quantity,
+                "unit_cost": line.item.cost.to_string(),
+                "cost": line.cost.to_string(),
+            }))
+            .collect::<Vec<_>>(),
+        "total": amounts_json(&order.total),
+    })
+}
+
+pub const AUDIT_HEADER: &[&str] = &["Time", "Actor", "Operation", "Item", "Change"];
+
+pub fn audit_row(inv: &Inventory, record: &AuditRecord) -> Vec<String> {
+    vec![
+        crate::time::format(record.timestamp),
+        record.actor.clone(),
+        record.operation.to_string(),
+        record.item_id.to_string(),
+        describe_change(inv, record),
+    ]
+}
+
+/// What changed, as `quantity 5 -> 3; price 10.00 USD -> 12.00 USD`
+fn describe_change(inv: &Inventory, record: &AuditRecord) -> String {
+    let (before, after) = match (&record.before, &record.after) {
+        (None, Some(after)) => return format!("added {} x{}", after.name, after.quantity),
+        (Some(before), None) => return format!("removed {} x{}", before.name, before.quantity),
+        (Some(before), Some(after)) => (before, after),
+        (None, None) => return String::new(),
+    };
+    let none = || "none".to_string();
+    let reorder = |item: &Item| item.reorder.map(reorder_text).unwrap_or_else(none);
+    let locations = |item: &Item| Some(locations_text(item)).filter(|text| !text.is_empty());
+    let fields: [(&str, String, String); 8] = [
+        ("name", before.name.clone(), after.name.clone()),
+        (
+            "category",
+            inv.categories.path(before.category),
+            inv.categories.path(after.category),
+        ),
+        (
+            "quantity",
+            before.quantity.to_string(),
+            after.quantity.to_string(),
+        ),
+        ("cost", before.cost.to_string(), after.cost.to_string()),
+        ("price", before.price.to_string(), after.price.to_string()),
+        (
+            "supplier",
+            before.supplier.clone().unwrap_or_else(none),
+            after.supplier.clone().unwrap_or_else(none),
+        ),
+        ("reorder", reorder(before), reorder(after)),
+        (
+            "locations",
+            locations(before).unwrap_or_else(none),
+            locations(after).unwrap_or_else(none),
+        ),
+    ];
+    let changes: Vec<String> = fields
+        .iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| format!("{} {} -> {}", field, old, new))
+        .collect();
+    changes.join("; ")
+}
+
+pub fn audit_json(inv: &Inventory, record: &AuditRecord) -> Value {
+    json!({
+        "timestamp": record.timestamp,
+        "time": crate::time::format(record.timestamp),
+        "actor": record.actor,
+        "operation": record.operation.name(),
+        "item_id": record.item_id,
+        "before": record.before.as_ref().map(|item| item_json(inv, item)),
+        "after": record.after.as_ref().map(|item| item_json(inv, item)),
+    })
+}
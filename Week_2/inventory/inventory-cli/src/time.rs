@@ -0,0 +1,146 @@
+//! Audit timestamps, milliseconds since the Unix epoch, as UTC dates and
+//! times such as `2026-10-18T14:03:05Z`
+
+const MS_PER_DAY: u64 = 86_400_000;
+
+/// Days since 1970-01-01 for a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = i64::from((month + 9) % 12);
+    let day_of_year = (153 * month_index + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date `days` after 1970-01-01, the inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+pub fn format(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / MS_PER_DAY) as i64);
+    let seconds = timestamp % MS_PER_DAY / 1000;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Read `2026-10-18`, `2026-10-18T14:03` or `2026-10-18T14:03:05`, with or
+/// without a final `Z`, as UTC. Each means the end of the day, minute or
+/// second it names, so that changes made during it count as before it.
+/// A timestamp as the JSON output gives it needs its unit, as in
+/// `1792307108201ms`; a bare number such as `2026` is refused rather than
+/// read as two seconds after the epoch.
+pub fn parse(text: &str) -> Option<u64> {
+    if let Some(milliseconds) = text.strip_suffix("ms") {
+        return milliseconds.parse().ok();
+    }
+    let text = text.strip_suffix('Z').unwrap_or(text);
+    let (date, time) = match text.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+
+    let mut date = date.splitn(3, '-');
+    let year: i64 = date.next()?.parse().ok()?;
+    let month: u32 = date.next()?.parse().ok()?;
+    let day: u32 = date.next()?.parse().ok()?;
+    let days_in_month = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    let start = u64::try_from(days_from_civil(year, month, day)).ok()? * MS_PER_DAY;
+
+    let Some(time) = time else {
+        return Some(start + MS_PER_DAY - 1);
+    };
+    let parts: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let (hours, minutes, seconds, rest) = match parts[..] {
+        [hours, minutes] => (hours, minutes, 0, 59_999),
+        [hours, minutes, seconds] => (hours, minutes, seconds, 999),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some(start + ((hours * 60 + minutes) * 60 + seconds) * 1000 + rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, parse, MS_PER_DAY};
+
+    #[test]
+    fn times_read_back_as_the_end_of_what_they_name() {
+        assert_eq!(format(0), "1970-01-01T00:00:00Z");
+        assert_eq!(parse("1970-01-01"), Some(MS_PER_DAY - 1));
+        assert_eq!(parse("1970-01-01T00:01"), Some(60_000 + 59_999));
+        assert_eq!(parse("1970-01-01T00:00:01"), Some(1_999));
+        assert_eq!(parse("1970-01-01T00:00:01Z"), parse("1970-01-01 00:00:01"));
+
+        // Every second written by `format` reads back as that second
+        for day in (0..60_000u64).step_by(37) {
+            let timestamp = day * MS_PER_DAY + day * 7_919_000 % MS_PER_DAY;
+            let text = format(timestamp);
+            assert_eq!(parse(&text), Some(timestamp + 999), "{}", text);
+            assert_eq!(parse(text.trim_end_matches('Z')), Some(timestamp + 999));
+        }
+    }
+
+    #[test]
+    fn leap_years_and_month_ends_are_checked() {
+        assert_eq!(format(parse("2024-02-29").unwrap()), "2024-02-29T23:59:59Z");
+        assert_eq!(format(parse("2000-02-29").unwrap()), "2000-02-29T23:59:59Z");
+        assert_eq!(parse("2023-02-29"), None);
+        assert_eq!(parse("2100-02-29"), None);
+        assert_eq!(parse("2026-04-31"), None);
+        assert_eq!(parse("2026-13-01"), None);
+        assert_eq!(parse("2026-01-00"), None);
+
+        let new_year = parse("2026-12-31").unwrap() + 1;
+        assert_eq!(format(new_year), "2027-01-01T00:00:00Z");
+        let march = parse("2024-02-29").unwrap() + 1;
+        assert_eq!(format(march), "2024-03-01T00:00:00Z");
+    }
+
+    #[test]
+    fn bare_numbers_and_other_zones_are_refused() {
+        assert_eq!(parse("2026"), None);
+        assert_eq!(parse("1792307108201"), None);
+        assert_eq!(parse("1792307108201ms"), Some(1_792_307_108_201));
+        assert_eq!(parse("2026-10-18T14:03+02:00"), None);
+        assert_eq!(parse("2026-10-18T24:00"), None);
+        assert_eq!(parse("2026-10-18T14:60"), None);
+    }
+}
@@ -0,0 +1,117 @@
+//! The audit log: one record per change to an item, saying who made it,
+//! when, and what the item looked like before and after. Records are only
+//! ever appended, so undo and redo are recorded as changes of their own.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::item::{Item, Movement, MovementKind};
+
+/// What was done to an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Add,
+    Update,
+    Remove,
+    Restock,
+    Sale,
+    /// Stock moved from one location to another
+    Transfer,
+    Undo,
+    Redo,
+    /// An item that was already there when an inventory from before the
+    /// audit log was loaded
+    Baseline,
+}
+
+impl Operation {
+    /// The name the operation is stored under
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Add => "add",
+            Operation::Update => "update",
+            Operation::Remove => "remove",
+            Operation::Restock => "restock",
+            Operation::Sale => "sale",
+            Operation::Transfer => "transfer",
+            Operation::Undo => "undo",
+            Operation::Redo => "redo",
+            Operation::Baseline => "baseline",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Operation> {
+        match name {
+            "add" => Some(Operation::Add),
+            "update" => Some(Operation::Update),
+            "remove" => Some(Operation::Remove),
+            "restock" => Some(Operation::Restock),
+            "sale" => Some(Operation::Sale),
+            "transfer" => Some(Operation::Transfer),
+            "undo" => Some(Operation::Undo),
+            "redo" => Some(Operation::Redo),
+            "baseline" => Some(Operation::Baseline),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One change to one item. `before` is `None` for an item that did not
+/// exist yet and `after` is `None` for one that was taken out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub actor: String,
+    pub operation: Operation,
+    pub item_id: i32,
+    pub before: Option<Item>,
+    pub after: Option<Item>,
+    /// Made in the same step as the record before it, as when an import
+    /// adds several items. `undo` and `redo` treat such records as one
+    /// change.
+    pub with_previous: bool,
+}
+
+impl AuditRecord {
+    /// The stock movement this change made, if it moved any stock
+    pub fn movement(&self) -> Option<Movement> {
+        let kind = match self.operation {
+            Operation::Add => MovementKind::Added,
+            Operation::Remove => MovementKind::Removed,
+            Operation::Restock => MovementKind::Restock,
+            Operation::Sale => MovementKind::Sale,
+            Operation::Undo => MovementKind::Undo,
+            Operation::Redo => MovementKind::Redo,
+            // A transfer moves stock around without changing how much there is
+            Operation::Update | Operation::Transfer | Operation::Baseline => return None,
+        };
+        let quantity = |item: &Option<Item>| item.as_ref().map_or(0, |item| item.quantity);
+        let (before, after) = (quantity(&self.before), quantity(&self.after));
+
+        // Undoing a price change leaves the stock alone
+        let reversal = matches!(kind, MovementKind::Undo | MovementKind::Redo);
+        if reversal && before == after && self.before.is_some() == self.after.is_some() {
+            return None;
+        }
+        Some(Movement {
+            item_id: self.item_id,
+            kind,
+            change: after - before,
+            balance: after,
+        })
+    }
+}
+
+/// The time now in milliseconds since the Unix epoch
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
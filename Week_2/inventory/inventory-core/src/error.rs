@@ -0,0 +1,99 @@
+use std::fmt;
+
+/// Everything an inventory operation can refuse to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryError {
+    DuplicateItemId,
+    ItemNotFound,
+    DuplicateCategory,
+    CategoryNotFound,
+    InvalidCategoryName,
+    InvalidQuantity,
+    InsufficientStock,
+    InvalidPrice,
+    InvalidMoney,
+    InvalidCurrency,
+    CurrencyMismatch,
+    MissingExchangeRate,
+    AmountTooLarge,
+    NothingToUndo,
+    NothingToRedo,
+    InvalidLocation,
+    SameLocation,
+}
+
+impl InventoryError {
+    /// Stable, machine-readable name of the error, used in JSON output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            InventoryError::DuplicateItemId => "duplicate_item_id",
+            InventoryError::ItemNotFound => "item_not_found",
+            InventoryError::DuplicateCategory => "duplicate_category",
+            InventoryError::CategoryNotFound => "category_not_found",
+            InventoryError::InvalidCategoryName => "invalid_category_name",
+            InventoryError::InvalidQuantity => "invalid_quantity",
+            InventoryError::InsufficientStock => "insufficient_stock",
+            InventoryError::InvalidPrice => "invalid_price",
+            InventoryError::InvalidMoney => "invalid_money",
+            InventoryError::InvalidCurrency => "invalid_currency",
+            InventoryError::CurrencyMismatch => "currency_mismatch",
+            InventoryError::MissingExchangeRate => "missing_exchange_rate",
+            InventoryError::AmountTooLarge => "amount_too_large",
+            InventoryError::NothingToUndo => "nothing_to_undo",
+            InventoryError::NothingToRedo => "nothing_to_redo",
+            InventoryError::InvalidLocation => "invalid_location",
+            InventoryError::SameLocation => "same_location",
+        }
+    }
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InventoryError::DuplicateItemId => write!(f, "Item with the same ID already exists."),
+            InventoryError::ItemNotFound => write!(f, "Item not found in the inventory."),
+            InventoryError::DuplicateCategory => {
+                write!(f, "Category with the same name already exists here.")
+            }
+            InventoryError::CategoryNotFound => write!(f, "Category not found in the taxonomy."),
+            InventoryError::InvalidCategoryName => {
+                write!(f, "Category names must not be empty or contain '>'.")
+            }
+            InventoryError::InvalidQuantity => write!(f, "Quantity must be a positive number."),
+            InventoryError::InsufficientStock => {
+                write!(f, "Not enough stock for this sale or transfer.")
+            }
+            InventoryError::InvalidPrice => write!(f, "Price must not be negative."),
+            InventoryError::InvalidMoney => {
+                write!(
+                    f,
+                    "Amounts must look like 2.50 USD, within the currency's decimals."
+                )
+            }
+            InventoryError::InvalidCurrency => {
+                write!(f, "Currency must be a three-letter ISO code such as NGN.")
+            }
+            InventoryError::CurrencyMismatch => {
+                write!(
+                    f,
+                    "Amounts in different currencies cannot be added directly."
+                )
+            }
+            InventoryError::MissingExchangeRate => {
+                write!(f, "No exchange rate for this currency in the rates file.")
+            }
+            InventoryError::AmountTooLarge => write!(f, "Amount is too large."),
+            InventoryError::NothingToUndo => write!(f, "There is nothing to undo."),
+            InventoryError::NothingToRedo => write!(f, "There is nothing to redo."),
+            InventoryError::InvalidLocation => write!(
+                f,
+                "Locations must look like warehouse/aisle/bin, each part without '/', ';' or '='."
+            ),
+            InventoryError::SameLocation => {
+                write!(f, "Stock must be moved to a different location.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
@@ -0,0 +1,672 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::audit::{now, AuditRecord, Operation};
+use crate::error::InventoryError;
+use crate::item::{Item, ItemUpdate, Movement, Reorder};
+use crate::location::{Location, LocationFilter};
+use crate::money::Amounts;
+use crate::taxonomy::{CategoryId, Taxonomy};
+
+/// The items filed directly under one category, and the groups for the
+/// categories below it. The counts and value roll up the whole branch;
+/// the value is the stock at its selling price, one amount per currency.
+/// Grouped by `categorize_at`, only the items with stock in the chosen
+/// locations are listed and only that stock is counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryGroup<'a> {
+    pub id: CategoryId,
+    pub name: &'a str,
+    pub items: Vec<&'a Item>,
+    pub subcategories: Vec<CategoryGroup<'a>>,
+    pub item_count: usize,
+    pub quantity: i64,
+    pub value: Amounts,
+}
+
+/// Some of the stock of an item, in one location or, for stock not yet
+/// put away, in none
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockLevel<'a> {
+    pub item: &'a Item,
+    pub location: Option<&'a Location>,
+    pub quantity: i32,
+}
+
+/// A function told about an item whose stock a sale has just taken below
+/// its reorder threshold
+type LowStockHook = Arc<dyn Fn(&Item) + Send + Sync>;
+
+/// Who is making changes and who wants to hear about them. None of it is
+/// part of the inventory's data, so it is never saved and any two sessions
+/// compare equal.
+#[derive(Clone, Default)]
+struct Session {
+    actor: Option<String>,
+    low_stock_hooks: Vec<LowStockHook>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Session({:?}, {} hook(s))",
+            self.actor,
+            self.low_stock_hooks.len()
+        )
+    }
+}
+
+impl PartialEq for Session {
+    fn eq(&self, _: &Session) -> bool {
+        true
+    }
+}
+
+/// Items, the categories they are filed under and the history of their
+/// stock. The quantity of an item only changes through `restock` and
+/// `sell`, each of which records a `Movement`, and stock only changes
+/// location through those and `transfer`. Every change to an item is kept
+/// in the audit log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+    pub categories: Taxonomy,
+    items: Vec<Item>,
+    movements: Vec<Movement>,
+    audit: Vec<AuditRecord>,
+    session: Session,
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory::default()
+    }
+
+    /// Put an inventory back together from stored parts, checking the same
+    /// rules `add_item` does. The movements and audit log are taken as they
+    /// are.
+    pub fn from_parts(
+        categories: Taxonomy,
+        items: Vec<Item>,
+        movements: Vec<Movement>,
+        audit: Vec<AuditRecord>,
+    ) -> Result<Inventory, InventoryError> {
+        let mut inv = Inventory {
+            categories,
+            items: Vec::with_capacity(items.len()),
+            movements,
+            audit,
+            session: Session::default(),
+        };
+        for item in items {
+            inv.check_new_item(&item)?;
+            inv.items.push(item);
+        }
+        Ok(inv)
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Every stock movement, oldest first
+    pub fn movements(&self) -> &[Movement] {
+        &self.movements
+    }
+
+    /// Every change to an item, oldest first
+    pub fn audit_log(&self) -> &[AuditRecord] {
+        &self.audit
+    }
+
+    /// Every change to one item, oldest first: who changed it, when, and
+    /// what it was before and after
+    pub fn history(&self, id: i32) -> Vec<&AuditRecord> {
+        self.audit
+            .iter()
+            .filter(|record| record.item_id == id)
+            .collect()
+    }
+
+    /// Name who is making the changes from here on, for the audit log.
+    /// Changes made before one is set are put down to "unknown".
+    pub fn set_actor(&mut self, actor: impl Into<String>) {
+        self.session.actor = Some(actor.into());
+    }
+
+    pub fn add_item(&mut self, item: Item) -> Result<(), InventoryError> {
+        self.check_new_item(&item)?;
+        self.commit(Operation::Add, item.id, None, Some(item));
+        Ok(())
+    }
+
+    fn check_new_item(&self, item: &Item) -> Result<(), InventoryError> {
+        if self
+            .items
+            .iter()
+            .any(|existing_item| existing_item.id == item.id)
+        {
+            return Err(InventoryError::DuplicateItemId);
+        }
+        if !self.categories.contains(item.category) {
+            return Err(InventoryError::CategoryNotFound);
+        }
+        if item.quantity < 0 {
+            return Err(InventoryError::InvalidQuantity);
+        }
+        if item.cost.is_negative() || item.price.is_negative() {
+            return Err(InventoryError::InvalidPrice);
+        }
+        check_reorder(item.reorder)?;
+        check_locations(item)
+    }
+
+    pub fn get_item_by_id(&self, id: i32) -> Result<&Item, InventoryError> {
+        self.items
+            .iter()
+            .find(|&item| item.id == id)
+            .ok_or(InventoryError::ItemNotFound)
+    }
+
+    /// Change the name, category, cost, price, supplier or reorder rule of
+    /// an item
+    pub fn update_item(&mut self, id: i32, update: ItemUpdate) -> Result<&Item, InventoryError> {
+        if update
+            .category
+            .is_some_and(|category| !self.categories.contains(category))
+        {
+            return Err(InventoryError::CategoryNotFound);
+        }
+        if [update.cost, update.price]
+            .iter()
+            .flatten()
+            .any(|money| money.is_negative())
+        {
+            return Err(InventoryError::InvalidPrice);
+        }
+        if let Some(reorder) = update.reorder {
+            check_reorder(reorder)?;
+        }
+        let before = self.get_item_by_id(id)?.clone();
+        let mut item = before.clone();
+        if let Some(name) = update.name {
+            item.name = name;
+        }
+        if let Some(category) = update.category {
+            item.category = category;
+        }
+        if let Some(cost) = update.cost {
+            item.cost = cost;
+        }
+        if let Some(price) = update.price {
+            item.price = price;
+        }
+        if let Some(supplier) = update.supplier {
+            item.supplier = supplier;
+        }
+        if let Some(reorder) = update.reorder {
+            item.reorder = reorder;
+        }
+        self.commit(Operation::Update, id, Some(before), Some(item));
+        self.get_item_by_id(id)
+    }
+
+    /// Take an item out of the inventory. Any stock it still had is written
+    /// off as a movement.
+    pub fn remove_item(&mut self, id: i32) -> Result<Item, InventoryError> {
+        let item = self.get_item_by_id(id)?.clone();
+        self.commit(Operation::Remove, id, Some(item.clone()), None);
+        Ok(item)
+    }
+
+    /// Add `quantity` units to the stock and return the new quantity. The
+    /// new stock is unplaced until it is transferred somewhere.
+    pub fn restock(&mut self, id: i32, quantity: i32) -> Result<i32, InventoryError> {
+        self.restock_to(id, None, quantity)
+    }
+
+    /// Add `quantity` units to the stock straight into `location`, and
+    /// return the new quantity across all locations
+    pub fn restock_at(
+        &mut self,
+        id: i32,
+        location: &Location,
+        quantity: i32,
+    ) -> Result<i32, InventoryError> {
+        self.restock_to(id, Some(location), quantity)
+    }
+
+    fn restock_to(
+        &mut self,
+        id: i32,
+        location: Option<&Location>,
+        quantity: i32,
+    ) -> Result<i32, InventoryError> {
+        if quantity <= 0 {
+            return Err(InventoryError::InvalidQuantity);
+        }
+        let before = self.get_item_by_id(id)?.clone();
+        let mut after = before.clone();
+        after.quantity = before
+            .quantity
+            .checked_add(quantity)
+            .ok_or(InventoryError::InvalidQuantity)?;
+        if let Some(location) = location {
+            after.put(location, quantity);
+        }
+        let balance = after.quantity;
+        self.commit(Operation::Restock, id, Some(before), Some(after));
+        Ok(balance)
+    }
+
+    /// Take `quantity` units out of the stock and return what is left.
+    /// Unplaced stock goes first, then stock from each location in the
+    /// order it was put there. If the sale takes the item below its reorder
+    /// threshold, every low-stock hook is called with it.
+    pub fn sell(&mut self, id: i32, quantity: i32) -> Result<i32, InventoryError> {
+        self.sell_out_of(id, None, quantity)
+    }
+
+    /// Take `quantity` units out of the stock in `location` and return what
+    /// is left across all locations
+    pub fn sell_from(
+        &mut self,
+        id: i32,
+        location: &Location,
+        quantity: i32,
+    ) -> Result<i32, InventoryError> {
+        self.sell_out_of(id, Some(location), quantity)
+    }
+
+    fn sell_out_of(
+        &mut self,
+        id: i32,
+        location: Option<&Location>,
+        quantity: i32,
+    ) -> Result<i32, InventoryError> {
+        if quantity <= 0 {
+            return Err(InventoryError::InvalidQuantity);
+        }
+        let before = self.get_item_by_id(id)?.clone();
+        if quantity > before.quantity {
+            return Err(InventoryError::InsufficientStock);
+        }
+        let mut after = before.clone();
+        match location {
+            Some(location) => after.take(location, quantity)?,
+            None => after.take_any(quantity),
+        }
+        after.quantity -= quantity;
+        let was_low = before.is_low();
+        let balance = after.quantity;
+        self.commit(Operation::Sale, id, Some(before), Some(after));
+
+        let item = self.get_item_by_id(id)?;
+        if item.is_low() && !was_low {
+            for hook in &self.session.low_stock_hooks {
+                hook(item);
+            }
+        }
+        Ok(balance)
+    }
+
+    /// Move `quantity` units of an item from one location to another, in a
+    /// single logged change. `None` stands for the unplaced stock, so
+    /// putting new stock away is a transfer from `None` and taking it back
+    /// out of the shelves one to `None`. Nothing moves unless all of it can.
+    pub fn transfer(
+        &mut self,
+        id: i32,
+        from: Option<&Location>,
+        to: Option<&Location>,
+        quantity: i32,
+    ) -> Result<&Item, InventoryError> {
+        if quantity <= 0 {
+            return Err(InventoryError::InvalidQuantity);
+        }
+        if from == to {
+            return Err(InventoryError::SameLocation);
+        }
+        let before = self.get_item_by_id(id)?.clone();
+        let mut after = before.clone();
+        match from {
+            Some(from) => after.take(from, quantity)?,
+            None if quantity > before.unplaced() => return Err(InventoryError::InsufficientStock),
+            None => {}
+        }
+        if let Some(to) = to {
+            after.put(to, quantity);
+        }
+        self.commit(Operation::Transfer, id, Some(before), Some(after));
+        self.get_item_by_id(id)
+    }
+
+    /// Call `hook` whenever a sale takes an item below its reorder
+    /// threshold. Sales that leave an item that was already low are not
+    /// reported again until it has been restocked.
+    pub fn on_low_stock(&mut self, hook: impl Fn(&Item) + Send + Sync + 'static) {
+        self.session.low_stock_hooks.push(Arc::new(hook));
+    }
+
+    /// Items with a reorder rule whose stock is below its threshold
+    pub fn low_stock(&self) -> Vec<&Item> {
+        self.items.iter().filter(|item| item.is_low()).collect()
+    }
+
+    /// Log a change to an item and make it
+    fn commit(
+        &mut self,
+        operation: Operation,
+        item_id: i32,
+        before: Option<Item>,
+        after: Option<Item>,
+    ) {
+        let record = AuditRecord {
+            timestamp: now(),
+            actor: self
+                .session
+                .actor
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+            operation,
+            item_id,
+            before,
+            after,
+            with_previous: false,
+        };
+        self.apply(&record);
+        self.audit.push(record);
+    }
+
+    /// Mark every record after the one at `start` as made in the same step
+    /// as it
+    fn join_from(&mut self, start: usize) {
+        for record in self.audit.iter_mut().skip(start + 1) {
+            record.with_previous = true;
+        }
+    }
+
+    /// Make several changes as one. They are logged like any others, but
+    /// `undo` and `redo` treat them as a single change. If `changes` fails,
+    /// none of them is kept.
+    pub fn grouped<T>(
+        &mut self,
+        changes: impl FnOnce(&mut Inventory) -> Result<T, InventoryError>,
+    ) -> Result<T, InventoryError> {
+        let start = self.audit.len();
+        let before = self.clone();
+        match changes(self) {
+            Ok(value) => {
+                self.join_from(start);
+                Ok(value)
+            }
+            Err(e) => {
+                *self = before;
+                Err(e)
+            }
+        }
+    }
+
+    /// Make the change a record describes: the item becomes what it was
+    /// after, in the same place if it was already there and at the end if
+    /// not, and any stock it moved is added to the movements
+    fn apply(&mut self, record: &AuditRecord) {
+        let index = self.items.iter().position(|item| item.id == record.item_id);
+        match (index, &record.after) {
+            (Some(index), Some(after)) => self.items[index] = after.clone(),
+            (Some(index), None) => {
+                self.items.remove(index);
+            }
+            (None, Some(after)) => self.items.push(after.clone()),
+            (None, None) => {}
+        }
+        if let Some(movement) = record.movement() {
+            self.movements.push(movement);
+        }
+    }
+
+    /// The changes `undo` and `redo` would reverse next, most recent last,
+    /// each given by the positions of its records in the audit log. Undo
+    /// and redo work like a text editor's: undoing steps back through the
+    /// changes, and any new change forgets what could have been redone.
+    fn undo_stacks(&self) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let (mut undo, mut redo): (Vec<Vec<usize>>, Vec<Vec<usize>>) = (Vec::new(), Vec::new());
+        for (index, record) in self.audit.iter().enumerate() {
+            match record.operation {
+                Operation::Baseline => {}
+                // The rest of the reversal of a change with several records
+                Operation::Undo | Operation::Redo if record.with_previous => {}
+                Operation::Undo => redo.extend(undo.pop()),
+                Operation::Redo => undo.extend(redo.pop()),
+                _ => match undo.last_mut() {
+                    Some(change) if record.with_previous => change.push(index),
+                    _ => {
+                        undo.push(vec![index]);
+                        redo.clear();
+                    }
+                },
+            }
+        }
+        (undo, redo)
+    }
+
+    /// Reverse the most recent change that has not been undone yet, and
+    /// return its records, oldest first. The reversal is logged too.
+    pub fn undo(&mut self) -> Result<Vec<AuditRecord>, InventoryError> {
+        let (mut undo, _) = self.undo_stacks();
+        let change = undo.pop().ok_or(InventoryError::NothingToUndo)?;
+        let originals: Vec<AuditRecord> = change.iter().map(|&i| self.audit[i].clone()).collect();
+        // Newest first, so an item changed twice in one step ends up as it began
+        let start = self.audit.len();
+        for original in originals.iter().rev() {
+            self.commit(
+                Operation::Undo,
+                original.item_id,
+                original.after.clone(),
+                original.before.clone(),
+            );
+        }
+        self.join_from(start);
+        Ok(originals)
+    }
+
+    /// Make the most recently undone change again, and return its records
+    pub fn redo(&mut self) -> Result<Vec<AuditRecord>, InventoryError> {
+        let (_, mut redo) = self.undo_stacks();
+        let change = redo.pop().ok_or(InventoryError::NothingToRedo)?;
+        let originals: Vec<AuditRecord> = change.iter().map(|&i| self.audit[i].clone()).collect();
+        let start = self.audit.len();
+        for original in &originals {
+            self.commit(
+                Operation::Redo,
+                original.item_id,
+                original.before.clone(),
+                original.after.clone(),
+            );
+        }
+        self.join_from(start);
+        Ok(originals)
+    }
+
+    /// The inventory as it was after the first `count` changes in the
+    /// audit log, rebuilt by replaying them. Categories are only ever
+    /// added, so the replay keeps all of today's.
+    pub fn replay(&self, count: usize) -> Inventory {
+        let mut inv = Inventory {
+            categories: self.categories.clone(),
+            ..Inventory::default()
+        };
+        for record in self.audit.iter().take(count) {
+            inv.apply(record);
+            inv.audit.push(record.clone());
+        }
+        inv
+    }
+
+    /// The inventory as it was at `timestamp`, in milliseconds since the
+    /// Unix epoch, with every change made up to and including then
+    pub fn as_of(&self, timestamp: u64) -> Inventory {
+        let count = self
+            .audit
+            .iter()
+            .take_while(|record| record.timestamp <= timestamp)
+            .count();
+        self.replay(count)
+    }
+
+    /// Every stock movement of one item, oldest first
+    pub fn movements_for(&self, id: i32) -> Vec<&Movement> {
+        self.movements
+            .iter()
+            .filter(|movement| movement.item_id == id)
+            .collect()
+    }
+
+    /// Where the stock of every item is, item by item: its unplaced stock
+    /// first, then each location it is kept in. With a filter, only the
+    /// stock in the locations it matches is listed.
+    pub fn stock_levels(&self, filter: Option<&LocationFilter>) -> Vec<StockLevel<'_>> {
+        let mut levels = Vec::new();
+        for item in &self.items {
+            if filter.is_none() && item.unplaced() > 0 {
+                levels.push(StockLevel {
+                    item,
+                    location: None,
+                    quantity: item.unplaced(),
+                });
+            }
+            levels.extend(
+                item.locations
+                    .iter()
+                    .filter(|stock| filter.is_none_or(|filter| filter.matches(&stock.location)))
+                    .map(|stock| StockLevel {
+                        item,
+                        location: Some(&stock.location),
+                        quantity: stock.quantity,
+                    }),
+            );
+        }
+        levels
+    }
+
+    /// Every location that holds any stock, sorted by warehouse, aisle and
+    /// bin
+    pub fn locations(&self) -> Vec<&Location> {
+        let mut locations: Vec<&Location> = self
+            .items
+            .iter()
+            .flat_map(|item| item.locations.iter().map(|stock| &stock.location))
+            .collect();
+        locations.sort();
+        locations.dedup();
+        locations
+    }
+
+    /// How many units of all items are kept in the locations `filter`
+    /// matches, or in all, placed or not, without one
+    pub fn total_stock(&self, filter: Option<&LocationFilter>) -> i64 {
+        self.items
+            .iter()
+            .map(|item| i64::from(quantity_in(item, filter)))
+            .sum()
+    }
+
+    /// Group the items into a tree that follows the taxonomy, one group per
+    /// top-level category
+    pub fn categorize(&self) -> Vec<CategoryGroup<'_>> {
+        self.categorize_in(None)
+    }
+
+    /// Group the items the same way, counting only the stock kept in the
+    /// locations `filter` matches and leaving out items and categories with
+    /// none there
+    pub fn categorize_at(&self, filter: &LocationFilter) -> Vec<CategoryGroup<'_>> {
+        self.categorize_in(Some(filter))
+    }
+
+    fn categorize_in(&self, filter: Option<&LocationFilter>) -> Vec<CategoryGroup<'_>> {
+        self.categories
+            .children(None)
+            .into_iter()
+            .map(|id| self.group(id, filter))
+            .filter(|group| filter.is_none() || group.item_count > 0)
+            .collect()
+    }
+
+    fn group(&self, id: CategoryId, filter: Option<&LocationFilter>) -> CategoryGroup<'_> {
+        let items: Vec<&Item> = self
+            .items
+            .iter()
+            .filter(|item| item.category == id)
+            .filter(|item| filter.is_none() || quantity_in(item, filter) > 0)
+            .collect();
+        let subcategories: Vec<CategoryGroup> = self
+            .categories
+            .children(Some(id))
+            .into_iter()
+            .map(|child| self.group(child, filter))
+            .filter(|group| filter.is_none() || group.item_count > 0)
+            .collect();
+
+        let mut value = Amounts::new();
+        for item in &items {
+            value.add(item.price.times(i64::from(quantity_in(item, filter))));
+        }
+        let mut group = CategoryGroup {
+            id,
+            name: self.categories.name(id),
+            item_count: items.len(),
+            quantity: items
+                .iter()
+                .map(|item| i64::from(quantity_in(item, filter)))
+                .sum(),
+            value,
+            items,
+            subcategories: Vec::new(),
+        };
+        for sub in &subcategories {
+            group.item_count += sub.item_count;
+            group.quantity += sub.quantity;
+            group.value.add_all(&sub.value);
+        }
+        group.subcategories = subcategories;
+        group
+    }
+}
+
+/// A threshold can be zero, but an order has to be for something
+fn check_reorder(reorder: Option<Reorder>) -> Result<(), InventoryError> {
+    match reorder {
+        Some(reorder) if reorder.threshold < 0 || reorder.quantity <= 0 => {
+            Err(InventoryError::InvalidQuantity)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Every location is named once and holds some stock, and together they
+/// hold no more than the item has
+fn check_locations(item: &Item) -> Result<(), InventoryError> {
+    let mut placed: i64 = 0;
+    for (index, stock) in item.locations.iter().enumerate() {
+        if stock.quantity <= 0 {
+            return Err(InventoryError::InvalidQuantity);
+        }
+        if item.locations[..index]
+            .iter()
+            .any(|earlier| earlier.location == stock.location)
+        {
+            return Err(InventoryError::InvalidLocation);
+        }
+        placed += i64::from(stock.quantity);
+    }
+    if placed > i64::from(item.quantity) {
+        return Err(InventoryError::InsufficientStock);
+    }
+    Ok(())
+}
+
+/// The stock of an item in the locations `filter` matches, or all of it
+fn quantity_in(item: &Item, filter: Option<&LocationFilter>) -> i32 {
+    filter.map_or(item.quantity, |filter| item.quantity_at(filter))
+}
@@ -0,0 +1,181 @@
+//! Items and the movements that change their stock
+
+use std::fmt;
+
+use crate::error::InventoryError;
+use crate::location::{Location, LocationFilter, Stock};
+use crate::money::Money;
+use crate::taxonomy::CategoryId;
+
+/// Something the shop stocks. `cost` is what one unit is bought for and
+/// `price` what it sells for; the two may be in different currencies.
+/// Stock is only watched for items with a `reorder` rule.
+///
+/// `quantity` is all the stock there is. `locations` says where some or all
+/// of it is kept, one entry per location, and anything not in a location is
+/// unplaced, as stock is until it has been put away.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub id: i32,
+    pub name: String,
+    pub category: CategoryId,
+    pub quantity: i32,
+    pub cost: Money,
+    pub price: Money,
+    pub supplier: Option<String>,
+    pub reorder: Option<Reorder>,
+    pub locations: Vec<Stock>,
+}
+
+impl Item {
+    /// Whether the stock has fallen below the reorder threshold
+    pub fn is_low(&self) -> bool {
+        self.reorder
+            .is_some_and(|reorder| self.quantity < reorder.threshold)
+    }
+
+    /// Stock that is not kept in any location
+    pub fn unplaced(&self) -> i32 {
+        self.quantity
+            - self
+                .locations
+                .iter()
+                .map(|stock| stock.quantity)
+                .sum::<i32>()
+    }
+
+    /// Stock kept in the locations `filter` matches
+    pub fn quantity_at(&self, filter: &LocationFilter) -> i32 {
+        self.locations
+            .iter()
+            .filter(|stock| filter.matches(&stock.location))
+            .map(|stock| stock.quantity)
+            .sum()
+    }
+
+    /// Put `quantity` more units in `location`. The total quantity is left
+    /// for the caller to change.
+    pub(crate) fn put(&mut self, location: &Location, quantity: i32) {
+        match self
+            .locations
+            .iter_mut()
+            .find(|stock| stock.location == *location)
+        {
+            Some(stock) => stock.quantity += quantity,
+            None => self.locations.push(Stock {
+                location: location.clone(),
+                quantity,
+            }),
+        }
+    }
+
+    /// Take `quantity` units out of `location`, forgetting the location once
+    /// it is empty. The total quantity is again left to the caller.
+    pub(crate) fn take(
+        &mut self,
+        location: &Location,
+        quantity: i32,
+    ) -> Result<(), InventoryError> {
+        let index = self
+            .locations
+            .iter()
+            .position(|stock| stock.location == *location)
+            .filter(|&index| self.locations[index].quantity >= quantity)
+            .ok_or(InventoryError::InsufficientStock)?;
+        self.locations[index].quantity -= quantity;
+        if self.locations[index].quantity == 0 {
+            self.locations.remove(index);
+        }
+        Ok(())
+    }
+
+    /// Take `quantity` units from wherever they are: unplaced stock first,
+    /// then each location in turn. The caller checks there are enough and
+    /// changes the total quantity.
+    pub(crate) fn take_any(&mut self, quantity: i32) {
+        let mut left = quantity - self.unplaced().clamp(0, quantity);
+        for stock in &mut self.locations {
+            let taken = stock.quantity.min(left);
+            stock.quantity -= taken;
+            left -= taken;
+        }
+        self.locations.retain(|stock| stock.quantity > 0);
+    }
+}
+
+/// When to order more of an item and how much: once fewer than `threshold`
+/// units are left, order `quantity` more
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reorder {
+    pub threshold: i32,
+    pub quantity: i32,
+}
+
+/// Changes to an item other than its stock. Fields left as `None` are kept,
+/// and `Some(None)` clears a supplier or reorder rule; the stock only
+/// moves through `restock`, `sell` and `transfer`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItemUpdate {
+    pub name: Option<String>,
+    pub category: Option<CategoryId>,
+    pub cost: Option<Money>,
+    pub price: Option<Money>,
+    pub supplier: Option<Option<String>>,
+    pub reorder: Option<Option<Reorder>>,
+}
+
+/// Why the stock of an item changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementKind {
+    Added,
+    Restock,
+    Sale,
+    Removed,
+    /// Stock put back by undoing an earlier change
+    Undo,
+    /// Stock moved again by redoing a change that was undone
+    Redo,
+}
+
+impl MovementKind {
+    /// The name the kind is stored under
+    pub fn name(self) -> &'static str {
+        match self {
+            MovementKind::Added => "added",
+            MovementKind::Restock => "restock",
+            MovementKind::Sale => "sale",
+            MovementKind::Removed => "removed",
+            MovementKind::Undo => "undo",
+            MovementKind::Redo => "redo",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MovementKind> {
+        match name {
+            "added" => Some(MovementKind::Added),
+            "restock" => Some(MovementKind::Restock),
+            "sale" => Some(MovementKind::Sale),
+            "removed" => Some(MovementKind::Removed),
+            "undo" => Some(MovementKind::Undo),
+            "redo" => Some(MovementKind::Redo),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MovementKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One change to the stock of an item. `change` is positive for stock
+/// coming in and negative for stock going out, and `balance` is the quantity
+/// left afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movement {
+    pub item_id: i32,
+    pub kind: MovementKind,
+    pub change: i32,
+    pub balance: i32,
+}
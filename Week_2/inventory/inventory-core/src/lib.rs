@@ -0,0 +1,48 @@
+//! The Week 2 inventory as a library: categories, items, stock movements
+//! and where the stock is kept, money, the audit log and the storage
+//! backends that keep them between runs.
+//!
+//! ```
+//! use inventory_core::{Inventory, Item, Money};
+//!
+//! let mut inv = Inventory::new();
+//! let android = inv.categories.add_path("Electronics > Phones > Android").unwrap();
+//! inv.add_item(Item {
+//!     id: 1,
+//!     name: "Phone".to_string(),
+//!     category: android,
+//!     quantity: 5,
+//!     cost: Money::parse("800 USD").unwrap(),
+//!     price: Money::parse("999.99 USD").unwrap(),
+//!     supplier: None,
+//!     reorder: None,
+//!     locations: Vec::new(),
+//! })
+//! .unwrap();
+//! assert_eq!(inv.sell(1, 2), Ok(3));
+//! assert_eq!(inv.categorize()[0].value.to_string(), "2999.97 USD");
+//! ```
+
+mod audit;
+mod error;
+mod inventory;
+mod item;
+mod location;
+mod money;
+mod rates;
+mod report;
+mod storage;
+mod taxonomy;
+
+pub use audit::{AuditRecord, Operation};
+pub use error::InventoryError;
+pub use inventory::{CategoryGroup, Inventory, StockLevel};
+pub use item::{Item, ItemUpdate, Movement, MovementKind, Reorder};
+pub use location::{Location, LocationFilter, Stock};
+pub use money::{Amounts, Currency, Money};
+pub use rates::ExchangeRates;
+pub use report::{Margin, OrderLine, PurchaseOrder, Valuation};
+pub use storage::{
+    storage_for, CsvStorage, JsonStorage, SqliteStorage, Storage, StorageError, SCHEMA_VERSION,
+};
+pub use taxonomy::{CategoryId, Taxonomy};
@@ -0,0 +1,88 @@
+//! Where stock is kept: a bin in an aisle of a warehouse, written
+//! `Main/A3/07`
+
+use std::fmt;
+
+use crate::error::InventoryError;
+
+/// Characters a part of a location cannot hold. `/` separates the parts,
+/// and `;` and `=` separate locations where storage lists them together.
+const RESERVED: &[char] = &['/', ';', '='];
+
+/// Check one part of a location and trim it
+fn part(text: &str) -> Result<String, InventoryError> {
+    let text = text.trim();
+    if text.is_empty() || text.contains(RESERVED) {
+        return Err(InventoryError::InvalidLocation);
+    }
+    Ok(text.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub warehouse: String,
+    pub aisle: String,
+    pub bin: String,
+}
+
+impl Location {
+    pub fn new(warehouse: &str, aisle: &str, bin: &str) -> Result<Location, InventoryError> {
+        Ok(Location {
+            warehouse: part(warehouse)?,
+            aisle: part(aisle)?,
+            bin: part(bin)?,
+        })
+    }
+
+    /// Read `warehouse/aisle/bin`
+    pub fn parse(text: &str) -> Result<Location, InventoryError> {
+        match text.split('/').collect::<Vec<_>>()[..] {
+            [warehouse, aisle, bin] => Location::new(warehouse, aisle, bin),
+            _ => Err(InventoryError::InvalidLocation),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.warehouse, self.aisle, self.bin)
+    }
+}
+
+/// A warehouse, an aisle in one, or a single bin: `Main`, `Main/A3` or
+/// `Main/A3/07`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationFilter {
+    parts: Vec<String>,
+}
+
+impl LocationFilter {
+    pub fn parse(text: &str) -> Result<LocationFilter, InventoryError> {
+        let parts = text.split('/').map(part).collect::<Result<Vec<_>, _>>()?;
+        if parts.len() > 3 {
+            return Err(InventoryError::InvalidLocation);
+        }
+        Ok(LocationFilter { parts })
+    }
+
+    pub fn matches(&self, location: &Location) -> bool {
+        let names = [&location.warehouse, &location.aisle, &location.bin];
+        self.parts
+            .iter()
+            .zip(names)
+            .all(|(part, name)| part == name)
+    }
+}
+
+impl fmt::Display for LocationFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.parts.join("/"))
+    }
+}
+
+/// Some of an item's stock, kept in one place
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stock {
+    pub location: Location,
+    pub quantity: i32,
+}
@@ -0,0 +1,251 @@
+//! Amounts of money as whole minor units, such as cents or kobo, tagged
+//! with an ISO 4217 currency code. Nothing here goes through floating
+//! point, so 2.50 stays exactly 2.50.
+
+use std::fmt;
+
+use crate::error::InventoryError;
+
+/// Currencies whose minor unit is not a hundredth. Any other code has two
+/// decimal places.
+const DECIMALS: &[(&str, u32)] = &[
+    ("BIF", 0),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("ISK", 0),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("UGX", 0),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("XXX", 0),
+    ("BHD", 3),
+    ("IQD", 3),
+    ("JOD", 3),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("TND", 3),
+];
+
+/// `numerator / denominator` to the nearest whole number, halves rounded
+/// away from zero. The denominator must be positive.
+pub(crate) fn div_round(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator / denominator;
+    if (numerator % denominator).abs() * 2 >= denominator {
+        quotient + numerator.signum()
+    } else {
+        quotient
+    }
+}
+
+/// A three-letter ISO 4217 code such as `NGN` or `USD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    /// `XXX`, the ISO code for "no currency", given to prices saved before
+    /// items had one
+    pub const NONE: Currency = Currency(*b"XXX");
+
+    pub fn new(code: &str) -> Result<Currency, InventoryError> {
+        match code.trim().as_bytes() {
+            &[a, b, c] if [a, b, c].iter().all(u8::is_ascii_alphabetic) => Ok(Currency([
+                a.to_ascii_uppercase(),
+                b.to_ascii_uppercase(),
+                c.to_ascii_uppercase(),
+            ])),
+            _ => Err(InventoryError::InvalidCurrency),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("codes are ASCII letters")
+    }
+
+    /// Places after the decimal point, 2 for `USD` and 0 for `JPY`
+    pub fn decimals(self) -> u32 {
+        DECIMALS
+            .iter()
+            .find(|(code, _)| *code == self.code())
+            .map_or(2, |&(_, places)| places)
+    }
+
+    /// Minor units in one major unit, 100 for `USD`
+    pub fn minor_per_major(self) -> i128 {
+        10_i128.pow(self.decimals())
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    /// The amount in the currency's smallest unit, 250 for 2.50 USD
+    pub minor: i128,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(minor: i128, currency: Currency) -> Money {
+        Money { minor, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Money {
+        Money::new(0, currency)
+    }
+
+    /// Read `2.50 USD` or `USD 2.50`. The amount may have no more decimal
+    /// places than the currency does.
+    pub fn parse(text: &str) -> Result<Money, InventoryError> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (amount, code) = match words[..] {
+            [amount, code] if amount.starts_with(|c: char| c == '-' || c.is_ascii_digit()) => {
+                (amount, code)
+            }
+            [code, amount] => (amount, code),
+            _ => return Err(InventoryError::InvalidMoney),
+        };
+        let currency = Currency::new(code)?;
+
+        let (negative, digits) = match amount.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, amount),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let places = currency.decimals() as usize;
+        let well_formed = !whole.is_empty()
+            && whole.bytes().all(|b| b.is_ascii_digit())
+            && fraction.bytes().all(|b| b.is_ascii_digit())
+            && (fraction.len() <= places)
+            && !(digits.contains('.') && fraction.is_empty());
+        if !well_formed {
+            return Err(InventoryError::InvalidMoney);
+        }
+
+        let minor: i128 = format!("{}{:0<2$}", whole, fraction, places)
+            .parse()
+            .map_err(|_| InventoryError::AmountTooLarge)?;
+        if minor > i128::from(i64::MAX) {
+            return Err(InventoryError::AmountTooLarge);
+        }
+        Ok(Money::new(if negative { -minor } else { minor }, currency))
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+
+    /// The amount for `count` of something at this price
+    pub fn times(self, count: i64) -> Money {
+        Money::new(self.minor * i128::from(count), self.currency)
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, InventoryError> {
+        self.combine(other, i128::checked_add)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, InventoryError> {
+        self.combine(other, i128::checked_sub)
+    }
+
+    fn combine(
+        self,
+        other: Money,
+        op: fn(i128, i128) -> Option<i128>,
+    ) -> Result<Money, InventoryError> {
+        if self.currency != other.currency {
+            return Err(InventoryError::CurrencyMismatch);
+        }
+        let minor = op(self.minor, other.minor).ok_or(InventoryError::AmountTooLarge)?;
+        Ok(Money::new(minor, self.currency))
+    }
+}
+
+/// `2.50 USD`, `-0.05 NGN` or `1000 JPY`
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let per = self.currency.minor_per_major();
+        let (whole, fraction) = (self.minor.abs() / per, self.minor.abs() % per);
+        match self.currency.decimals() {
+            0 => write!(f, "{}{} {}", sign, whole, self.currency),
+            places => write!(
+                f,
+                "{}{}.{:0width$} {}",
+                sign,
+                whole,
+                fraction,
+                self.currency,
+                width = places as usize
+            ),
+        }
+    }
+}
+
+/// A sum that may span currencies, kept as one amount per currency until
+/// it is converted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Amounts(Vec<Money>);
+
+impl Amounts {
+    pub fn new() -> Amounts {
+        Amounts::default()
+    }
+
+    pub fn add(&mut self, money: Money) {
+        match self.0.iter_mut().find(|m| m.currency == money.currency) {
+            Some(total) => total.minor += money.minor,
+            None => {
+                self.0.push(money);
+                self.0.sort_by_key(|m| m.currency);
+            }
+        }
+    }
+
+    pub fn add_all(&mut self, other: &Amounts) {
+        for &money in other.iter() {
+            self.add(money);
+        }
+    }
+
+    /// The amount in one currency, zero if there is none
+    pub fn get(&self, currency: Currency) -> Money {
+        self.0
+            .iter()
+            .find(|m| m.currency == currency)
+            .copied()
+            .unwrap_or(Money::zero(currency))
+    }
+
+    /// One amount per currency, ordered by code
+    pub fn iter(&self) -> impl Iterator<Item = &Money> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// `5000.00 NGN + 12.50 USD`, or `0` when there is nothing
+impl fmt::Display for Amounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("0");
+        }
+        let parts: Vec<String> = self.0.iter().map(Money::to_string).collect();
+        f.write_str(&parts.join(" + "))
+    }
+}
@@ -0,0 +1,148 @@
+//! Exchange rates read from a small text file: a base currency, then how
+//! much of each other currency one unit of the base buys.
+//!
+//! ```text
+//! # Rates for the month-end report
+//! base = USD
+//! NGN = 1550.25
+//! EUR = 0.92
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::InventoryError;
+use crate::money::{div_round, Amounts, Currency, Money};
+use crate::storage::StorageError;
+
+/// A rate written as a decimal, `units / 10^scale`, so that 1550.25 is
+/// kept exactly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rate {
+    units: i128,
+    scale: u32,
+}
+
+impl Rate {
+    const ONE: Rate = Rate { units: 1, scale: 0 };
+
+    fn parse(text: &str) -> Option<Rate> {
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        let digits = format!("{}{}", whole, fraction);
+        if whole.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || fraction.len() > 18 {
+            return None;
+        }
+        let units: i128 = digits.parse().ok()?;
+        (units > 0).then_some(Rate {
+            units,
+            scale: fraction.len() as u32,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeRates {
+    base: Currency,
+    rates: Vec<(Currency, Rate)>,
+}
+
+impl ExchangeRates {
+    /// A table with no rates yet, which converts only between equal currencies
+    pub fn new(base: Currency) -> ExchangeRates {
+        ExchangeRates {
+            base,
+            rates: Vec::new(),
+        }
+    }
+
+    pub fn base(&self) -> Currency {
+        self.base
+    }
+
+    /// Read the rates file at `path`
+    pub fn load(path: &Path) -> Result<ExchangeRates, StorageError> {
+        let text = fs::read_to_string(path)?;
+        ExchangeRates::parse(&text)
+            .map_err(|e| StorageError::Format(format!("'{}' {}", path.display(), e)))
+    }
+
+    /// Read rates in the file format above. Errors name the line at fault.
+    pub fn parse(text: &str) -> Result<ExchangeRates, String> {
+        let mut base = None;
+        let mut rates = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| format!("line {}: {}", index + 1, message);
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| invalid("expected 'CODE = rate' or 'base = CODE'"))?;
+            if key.eq_ignore_ascii_case("base") {
+                let currency =
+                    Currency::new(value).map_err(|_| invalid("base must be a currency code"))?;
+                base = Some(currency);
+                continue;
+            }
+            let currency = Currency::new(key)
+                .map_err(|_| invalid(&format!("'{}' is not a currency code", key)))?;
+            let rate = Rate::parse(value)
+                .ok_or_else(|| invalid(&format!("'{}' is not a positive rate", value)))?;
+            rates.push((currency, rate));
+        }
+        let base = base.ok_or("has no 'base = CODE' line")?;
+        Ok(ExchangeRates { base, rates })
+    }
+
+    /// How much of `currency` one unit of the base buys
+    fn rate(&self, currency: Currency) -> Result<Rate, InventoryError> {
+        if currency == self.base {
+            return Ok(Rate::ONE);
+        }
+        self.rates
+            .iter()
+            .rev()
+            .find(|(c, _)| *c == currency)
+            .map(|&(_, rate)| rate)
+            .ok_or(InventoryError::MissingExchangeRate)
+    }
+
+    /// The value of `money` in `to`, rounded to the nearest minor unit with
+    /// halves rounded away from zero
+    pub fn convert(&self, money: Money, to: Currency) -> Result<Money, InventoryError> {
+        if money.currency == to {
+            return Ok(money);
+        }
+        let from_rate = self.rate(money.currency)?;
+        let to_rate = self.rate(to)?;
+
+        // minor_to = minor_from / per_from / rate_from * rate_to * per_to
+        let pow = |n: u32| 10_i128.checked_pow(n).ok_or(InventoryError::AmountTooLarge);
+        let numerator = money
+            .minor
+            .checked_mul(pow(from_rate.scale)?)
+            .and_then(|n| n.checked_mul(to_rate.units))
+            .and_then(|n| n.checked_mul(to.minor_per_major()))
+            .ok_or(InventoryError::AmountTooLarge)?;
+        let denominator = money
+            .currency
+            .minor_per_major()
+            .checked_mul(from_rate.units)
+            .and_then(|n| n.checked_mul(pow(to_rate.scale).ok()?))
+            .ok_or(InventoryError::AmountTooLarge)?;
+
+        Ok(Money::new(div_round(numerator, denominator), to))
+    }
+
+    /// A sum across currencies as one amount in `to`. Each currency is
+    /// converted whole, so rounding happens once per currency.
+    pub fn total(&self, amounts: &Amounts, to: Currency) -> Result<Money, InventoryError> {
+        let mut total = Money::zero(to);
+        for &money in amounts.iter() {
+            total = total.checked_add(self.convert(money, to)?)?;
+        }
+        Ok(total)
+    }
+}
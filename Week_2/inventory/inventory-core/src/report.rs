@@ -0,0 +1,119 @@
+//! What the stock is worth, what each item earns and what needs ordering.
+//! Totals are kept per currency; `ExchangeRates::total` turns them into
+//! one amount.
+
+use crate::error::InventoryError;
+use crate::inventory::Inventory;
+use crate::item::Item;
+use crate::money::{div_round, Amounts, Money};
+use crate::rates::ExchangeRates;
+
+/// The stock on hand valued at what it cost and at what it sells for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Valuation {
+    pub cost: Amounts,
+    pub retail: Amounts,
+}
+
+/// A draft order to one supplier for everything of theirs that is low.
+/// Items with no supplier are gathered in an order with `supplier: None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PurchaseOrder<'a> {
+    pub supplier: Option<&'a str>,
+    pub lines: Vec<OrderLine<'a>>,
+    /// What the order costs at the items' cost prices
+    pub total: Amounts,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderLine<'a> {
+    pub item: &'a Item,
+    pub quantity: i32,
+    pub cost: Money,
+}
+
+/// What one unit of an item earns. The cost is converted into the currency
+/// of the price, so `cost`, `item.price` and `margin` can be compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Margin<'a> {
+    pub item: &'a Item,
+    pub cost: Money,
+    pub margin: Money,
+    /// The margin as a share of the price in hundredths of a percent,
+    /// 2500 for 25%, or `None` for an item with no price
+    pub basis_points: Option<i64>,
+}
+
+impl Inventory {
+    /// Value every unit in stock at its cost and at its selling price
+    pub fn valuation(&self) -> Valuation {
+        let mut valuation = Valuation::default();
+        for item in self.items() {
+            let quantity = i64::from(item.quantity);
+            valuation.cost.add(item.cost.times(quantity));
+            valuation.retail.add(item.price.times(quantity));
+        }
+        valuation
+    }
+
+    /// The margin on one unit of each item, in the order the items were
+    /// added. Fails if a cost needs a rate the table does not have.
+    pub fn margins(&self, rates: &ExchangeRates) -> Result<Vec<Margin<'_>>, InventoryError> {
+        self.items()
+            .iter()
+            .map(|item| {
+                let cost = rates.convert(item.cost, item.price.currency)?;
+                let margin = item.price.checked_sub(cost)?;
+                let basis_points = match item.price.minor {
+                    0 => None,
+                    price => margin
+                        .minor
+                        .checked_mul(10_000)
+                        .and_then(|share| i64::try_from(div_round(share, price)).ok())
+                        .map(Some)
+                        .ok_or(InventoryError::AmountTooLarge)?,
+                };
+                Ok(Margin {
+                    item,
+                    cost,
+                    margin,
+                    basis_points,
+                })
+            })
+            .collect()
+    }
+
+    /// Draft purchase orders for every low item, one per supplier in order
+    /// of name and then one for items with no supplier. Each item is
+    /// ordered in its reorder quantity.
+    pub fn purchase_orders(&self) -> Vec<PurchaseOrder<'_>> {
+        let mut orders: Vec<PurchaseOrder> = Vec::new();
+        for item in self.low_stock() {
+            let Some(reorder) = item.reorder else {
+                continue;
+            };
+            let supplier = item.supplier.as_deref();
+            let index = match orders.iter().position(|o| o.supplier == supplier) {
+                Some(index) => index,
+                None => {
+                    orders.push(PurchaseOrder {
+                        supplier,
+                        lines: Vec::new(),
+                        total: Amounts::new(),
+                    });
+                    orders.len() - 1
+                }
+            };
+            let cost = item.cost.times(i64::from(reorder.quantity));
+            orders[index].total.add(cost);
+            orders[index].lines.push(OrderLine {
+                item,
+                quantity: reorder.quantity,
+                cost,
+            });
+        }
+        // `None` sorts first, so key on whether there is a supplier too
+        orders.sort_by_key(|order| (order.supplier.is_none(), order.supplier));
+        orders
+    }
+}
@@ -0,0 +1,261 @@
+//! The inventory as one CSV file that opens in a spreadsheet. The first
+//! field of each row says what the row holds, and the first row carries
+//! the schema version:
+//!
+//! ```text
+//! inventory,6
+//! category,0,Electronics,
+//! category,1,Phones,0
+//! item,1,Phone,1,5,800.00 USD,999.99 USD,Acme,3,10,Main/A3/07=4
+//! movement,1,added,5,5
+//! audit,1760000000000,ana,add,1,
+//! after,1,Phone,1,5,800.00 USD,999.99 USD,Acme,3,10,Main/A3/07=4
+//! ```
+//!
+//! A category row is `id,name,parent`, an item row
+//! `id,name,category,quantity,cost,price,supplier,reorder_threshold,reorder_quantity,locations`
+//! and a movement row `item_id,kind,change,balance`. The last four item
+//! fields are left empty when not set. Rows before version 5 stop before
+//! `locations`, version 2 item rows stop after `price`, and version 1 rows
+//! end in a bare `price` with no `cost`.
+//!
+//! An audit row is `timestamp,actor,operation,item_id,with_previous`, the
+//! last field `1` for a change made in the same step as the one before and
+//! empty otherwise; rows before version 6 stop before it. The item as it
+//! was before and after follows in `before` and `after` rows laid out like
+//! an item row, each left out when there was no item.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+use super::{
+    check_version, v1_amounts, write_atomic, AuditRow, CategoryRecord, ItemRecord, MovementRecord,
+    Snapshot, Storage, StorageError, SCHEMA_VERSION,
+};
+use crate::inventory::Inventory;
+
+/// First field of the version row
+const HEADER: &str = "inventory";
+
+pub struct CsvStorage {
+    path: PathBuf,
+}
+
+impl CsvStorage {
+    pub fn new(path: impl AsRef<Path>) -> CsvStorage {
+        CsvStorage {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn error(&self, e: csv::Error) -> StorageError {
+        StorageError::Format(format!("'{}': {}", self.path.display(), e))
+    }
+}
+
+impl Storage for CsvStorage {
+    fn load(&self) -> Result<Inventory, StorageError> {
+        if !self.path.exists() {
+            return Ok(Inventory::new());
+        }
+        let text = fs::read(&self.path)?;
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(&text[..]);
+        let mut records = reader.records();
+
+        let first = records.next().transpose().map_err(|e| self.error(e))?;
+        let version = match first {
+            Some(row) if row.get(0) == Some(HEADER) => Row::new(&row, 1).field(1)?,
+            _ => {
+                return Err(StorageError::Format(format!(
+                    "'{}' does not start with an '{},<version>' row",
+                    self.path.display(),
+                    HEADER
+                )))
+            }
+        };
+        check_version(version)?;
+
+        let mut snapshot = Snapshot {
+            schema_version: version,
+            categories: Vec::new(),
+            items: Vec::new(),
+            movements: Vec::new(),
+            audit: Vec::new(),
+        };
+        for (index, record) in records.enumerate() {
+            let record = record.map_err(|e| self.error(e))?;
+            let row = Row::new(&record, index + 2);
+            match record.get(0).unwrap_or("") {
+                "category" => snapshot.categories.push(CategoryRecord {
+                    id: row.field(1)?,
+                    name: row.text(2)?,
+                    parent: row.optional(3)?,
+                }),
+                "item" => snapshot.items.push(row.item(version)?),
+                "movement" => snapshot.movements.push(MovementRecord {
+                    item_id: row.field(1)?,
+                    kind: row.text(2)?,
+                    change: row.field(3)?,
+                    balance: row.field(4)?,
+                }),
+                "audit" => snapshot.audit.push(AuditRow {
+                    timestamp: row.field(1)?,
+                    actor: row.text(2)?,
+                    operation: row.text(3)?,
+                    item_id: row.field(4)?,
+                    before: None,
+                    after: None,
+                    with_previous: row.optional::<u8>(5)? == Some(1),
+                }),
+                side @ ("before" | "after") => {
+                    let audit = snapshot.audit.last_mut().ok_or_else(|| {
+                        row.invalid(&format!("a {} row must follow an audit row", side))
+                    })?;
+                    let item = Some(row.item(version)?);
+                    if side == "before" {
+                        audit.before = item;
+                    } else {
+                        audit.after = item;
+                    }
+                }
+                // Blank lines, as left by some spreadsheets, are skipped
+                "" if record.iter().all(str::is_empty) => {}
+                other => return Err(row.invalid(&format!("unknown row type '{}'", other))),
+            }
+        }
+        snapshot.into_inventory()
+    }
+
+    fn save(&self, inventory: &Inventory) -> Result<(), StorageError> {
+        let snapshot = Snapshot::of(inventory);
+        let mut writer = WriterBuilder::new().flexible(true).from_writer(Vec::new());
+        let mut rows = vec![vec![HEADER.to_string(), SCHEMA_VERSION.to_string()]];
+        rows.extend(snapshot.categories.iter().map(|c| {
+            vec![
+                "category".to_string(),
+                c.id.to_string(),
+                c.name.clone(),
+                c.parent.map(|p| p.to_string()).unwrap_or_default(),
+            ]
+        }));
+        rows.extend(snapshot.items.iter().map(|i| item_cells("item", i)));
+        rows.extend(snapshot.movements.iter().map(|m| {
+            vec![
+                "movement".to_string(),
+                m.item_id.to_string(),
+                m.kind.clone(),
+                m.change.to_string(),
+                m.balance.to_string(),
+            ]
+        }));
+        for a in &snapshot.audit {
+            rows.push(vec![
+                "audit".to_string(),
+                a.timestamp.to_string(),
+                a.actor.clone(),
+                a.operation.clone(),
+                a.item_id.to_string(),
+                if a.with_previous { "1" } else { "" }.to_string(),
+            ]);
+            rows.extend(a.before.iter().map(|i| item_cells("before", i)));
+            rows.extend(a.after.iter().map(|i| item_cells("after", i)));
+        }
+        for row in rows {
+            writer.write_record(&row).map_err(|e| self.error(e))?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| StorageError::Io(e.into_error()))?;
+        Ok(write_atomic(&self.path, &bytes)?)
+    }
+}
+
+/// An item row, or a before or after row, under the tag `kind`
+fn item_cells(kind: &str, i: &ItemRecord) -> Vec<String> {
+    vec![
+        kind.to_string(),
+        i.id.to_string(),
+        i.name.clone(),
+        i.category.to_string(),
+        i.quantity.to_string(),
+        i.cost.clone(),
+        i.price.clone(),
+        i.supplier.clone().unwrap_or_default(),
+        i.reorder_threshold
+            .map(|t| t.to_string())
+            .unwrap_or_default(),
+        i.reorder_quantity
+            .map(|q| q.to_string())
+            .unwrap_or_default(),
+        i.locations.clone(),
+    ]
+}
+
+/// One row being read, with its line number for error messages
+struct Row<'a> {
+    record: &'a StringRecord,
+    line: usize,
+}
+
+impl<'a> Row<'a> {
+    fn new(record: &'a StringRecord, line: usize) -> Row<'a> {
+        Row { record, line }
+    }
+
+    fn invalid(&self, message: &str) -> StorageError {
+        StorageError::Format(format!("line {}: {}", self.line, message))
+    }
+
+    fn text(&self, index: usize) -> Result<String, StorageError> {
+        self.record
+            .get(index)
+            .map(str::to_string)
+            .ok_or_else(|| self.invalid(&format!("expected at least {} fields", index + 1)))
+    }
+
+    fn field<T: FromStr>(&self, index: usize) -> Result<T, StorageError> {
+        let text = self.text(index)?;
+        text.trim()
+            .parse()
+            .map_err(|_| self.invalid(&format!("'{}' is not a valid number", text)))
+    }
+
+    /// The item in an item, before or after row written by `version`
+    fn item(&self, version: u32) -> Result<ItemRecord, StorageError> {
+        let (cost, price) = match version {
+            1 => v1_amounts(self.field(5)?),
+            _ => (self.text(5)?, self.text(6)?),
+        };
+        Ok(ItemRecord {
+            id: self.field(1)?,
+            name: self.text(2)?,
+            category: self.field(3)?,
+            quantity: self.field(4)?,
+            cost,
+            price,
+            supplier: self.optional(7)?,
+            reorder_threshold: self.optional(8)?,
+            reorder_quantity: self.optional(9)?,
+            locations: if version < 5 {
+                String::new()
+            } else {
+                self.record.get(10).unwrap_or("").to_string()
+            },
+        })
+    }
+
+    /// A field that may be left empty, such as the parent of a top-level
+    /// category
+    fn optional<T: FromStr>(&self, index: usize) -> Result<Option<T>, StorageError> {
+        match self.record.get(index).map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(_) => self.field(index).map(Some),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! The inventory as one JSON document. Each entry of `audit` holds the
+//! item as it was `before` and `after` the change, laid out like an entry
+//! of `items`, or `null` when there was no item.
+//!
+//! ```text
+//! {
+//!   "schema_version": 6,
+//!   "categories": [{ "id": 0, "name": "Electronics", "parent": null }],
+//!   "items": [{
+//!     "id": 1, "name": "Phone", "category": 0, "quantity": 5,
+//!     "cost": "800.00 USD", "price": "999.99 USD", "supplier": "Acme",
+//!     "reorder_threshold": 3, "reorder_quantity": 10,
+//!     "locations": "Main/A3/07=4"
+//!   }],
+//!   "movements": [{ "item_id": 1, "kind": "added", "change": 5, "balance": 5 }],
+//!   "audit": [{
+//!     "timestamp": 1760000000000, "actor": "ana", "operation": "add",
+//!     "item_id": 1, "before": null, "after": { "id": 1, "name": "Phone", ... },
+//!     "with_previous": false
+//!   }]
+//! }
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use super::{check_version, v1_amounts, write_atomic, Snapshot, Storage, StorageError};
+use crate::inventory::Inventory;
+
+pub struct JsonStorage {
+    path: PathBuf,
+}
+
+impl JsonStorage {
+    pub fn new(path: impl AsRef<Path>) -> JsonStorage {
+        JsonStorage {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn error(&self, e: serde_json::Error) -> StorageError {
+        StorageError::Format(format!("'{}': {}", self.path.display(), e))
+    }
+}
+
+impl Storage for JsonStorage {
+    fn load(&self) -> Result<Inventory, StorageError> {
+        if !self.path.exists() {
+            return Ok(Inventory::new());
+        }
+        let text = fs::read_to_string(&self.path)?;
+        let mut document: Value = serde_json::from_str(&text).map_err(|e| self.error(e))?;
+
+        // The version is read first, so a file from a newer release is
+        // reported as such rather than as a field that will not parse
+        let version = document
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| {
+                StorageError::Format(format!("'{}' has no schema_version", self.path.display()))
+            })?;
+        check_version(u32::try_from(version).unwrap_or(u32::MAX))?;
+        if version == 1 {
+            upgrade_v1(&mut document);
+        }
+
+        let snapshot: Snapshot = serde_json::from_value(document).map_err(|e| self.error(e))?;
+        snapshot.into_inventory()
+    }
+
+    fn save(&self, inventory: &Inventory) -> Result<(), StorageError> {
+        let mut text =
+            serde_json::to_string_pretty(&Snapshot::of(inventory)).map_err(|e| self.error(e))?;
+        text.push('\n');
+        Ok(write_atomic(&self.path, text.as_bytes())?)
+    }
+}
+
+/// Turn the bare number prices of a version 1 document into amounts
+fn upgrade_v1(document: &mut Value) {
+    let items = document.get_mut("items").and_then(Value::as_array_mut);
+    for item in items.into_iter().flatten() {
+        let Some(price) = item.get("price").and_then(Value::as_i64) else {
+            continue;
+        };
+        let (cost, price) = v1_amounts(price);
+        item["cost"] = Value::from(cost);
+        item["price"] = Value::from(price);
+    }
+}
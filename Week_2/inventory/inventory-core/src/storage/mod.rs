@@ -0,0 +1,411 @@
+//! Keeping an inventory between runs. Every backend stores the same
+//! snapshot of categories, items and stock movements, stamped with
+//! `SCHEMA_VERSION`, so an inventory saved by one can be loaded by another
+//! and files written by a newer release are refused rather than misread.
+//!
+//! Version 2 gave items a cost and stored both amounts as text such as
+//! `2.50 USD`. Version 1 files still load: their bare whole-number prices
+//! become amounts in `XXX`, "no currency", with a cost of nothing.
+//! Version 3 added each item's supplier and reorder rule, which older files
+//! load without. Version 4 added the audit log; an older file starts one
+//! with a `baseline` record for each item it holds. Version 5 added the
+//! locations each item's stock is kept in, written as text such as
+//! `Main/A3/07=4;Main/B1/02=2`; in older files all stock is unplaced.
+//! Version 6 marks the audit records made in the same step as the one
+//! before them; in older files every record is a change of its own.
+
+mod csv;
+mod json;
+mod sqlite;
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{AuditRecord, Operation};
+use crate::error::InventoryError;
+use crate::inventory::Inventory;
+use crate::item::{Item, Movement, MovementKind, Reorder};
+use crate::location::{Location, Stock};
+use crate::money::{Currency, Money};
+use crate::taxonomy::{CategoryId, Taxonomy};
+
+pub use self::csv::CsvStorage;
+pub use self::json::JsonStorage;
+pub use self::sqlite::SqliteStorage;
+
+/// Version of the stored layout, raised whenever it changes
+pub const SCHEMA_VERSION: u32 = 6;
+
+/// Somewhere an inventory can be saved and loaded again
+pub trait Storage {
+    /// Read the stored inventory, or an empty one if nothing has been saved yet
+    fn load(&self) -> Result<Inventory, StorageError>;
+
+    /// Replace whatever is stored with `inventory`. Either all of it is
+    /// written or, if anything fails, the previous contents are left alone.
+    fn save(&self, inventory: &Inventory) -> Result<(), StorageError>;
+}
+
+/// The backend for a file, chosen by its extension: `.json`, `.csv`, or
+/// `.db`, `.sqlite` and `.sqlite3` for SQLite
+pub fn storage_for(path: &Path) -> Result<Box<dyn Storage>, StorageError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json") => Ok(Box::new(JsonStorage::new(path))),
+        Some("csv") => Ok(Box::new(CsvStorage::new(path))),
+        Some("db" | "sqlite" | "sqlite3") => Ok(Box::new(SqliteStorage::new(path))),
+        _ => Err(StorageError::Format(format!(
+            "cannot tell the storage format of '{}'; use .json, .csv or .db",
+            path.display()
+        ))),
+    }
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+    /// The stored data is damaged or not an inventory at all
+    Format(String),
+    /// Written with a schema this release does not know
+    UnsupportedVersion(u32),
+    /// The stored data breaks a rule of the inventory, such as two items
+    /// with the same ID
+    Inventory(InventoryError),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Io(e) => write!(f, "{}", e),
+            StorageError::Sqlite(e) => write!(f, "SQLite: {}", e),
+            StorageError::Format(message) => write!(f, "{}", message),
+            StorageError::UnsupportedVersion(version) => write!(
+                f,
+                "schema version {} is not supported; this release reads versions 1 to {}",
+                version, SCHEMA_VERSION
+            ),
+            StorageError::Inventory(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> StorageError {
+        StorageError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> StorageError {
+        StorageError::Sqlite(e)
+    }
+}
+
+impl From<InventoryError> for StorageError {
+    fn from(e: InventoryError) -> StorageError {
+        StorageError::Inventory(e)
+    }
+}
+
+/// Refuse versions from the future. Older ones are upgraded by each backend
+/// as it reads them.
+fn check_version(version: u32) -> Result<(), StorageError> {
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// The cost and price of an item stored by version 1, which had a price
+/// with no currency and no cost at all
+fn v1_amounts(price: i64) -> (String, String) {
+    (
+        Money::zero(Currency::NONE).to_string(),
+        Money::new(i128::from(price), Currency::NONE).to_string(),
+    )
+}
+
+/// Make the directory a file is about to be written in
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+/// Write `contents` next to `path` and rename it into place, so a crash
+/// never leaves half a file behind
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    create_parent(path)?;
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = Path::new(&temporary);
+    let mut file = fs::File::create(temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(temporary, path)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CategoryRecord {
+    id: usize,
+    name: String,
+    parent: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ItemRecord {
+    id: i32,
+    name: String,
+    category: usize,
+    quantity: i32,
+    /// Amounts are kept as text, `2.50 USD`, so every backend stores them
+    /// the same way and a person can read them
+    cost: String,
+    price: String,
+    #[serde(default)]
+    supplier: Option<String>,
+    /// The reorder threshold and quantity, both present or both absent
+    #[serde(default)]
+    reorder_threshold: Option<i32>,
+    #[serde(default)]
+    reorder_quantity: Option<i32>,
+    /// `location=quantity` for each location, separated by `;`, and empty
+    /// when all the stock is unplaced
+    #[serde(default)]
+    locations: String,
+}
+
+impl ItemRecord {
+    fn of(item: &Item) -> ItemRecord {
+        ItemRecord {
+            id: item.id,
+            name: item.name.clone(),
+            category: item.category.0,
+            quantity: item.quantity,
+            cost: item.cost.to_string(),
+            price: item.price.to_string(),
+            supplier: item.supplier.clone(),
+            reorder_threshold: item.reorder.map(|r| r.threshold),
+            reorder_quantity: item.reorder.map(|r| r.quantity),
+            locations: item
+                .locations
+                .iter()
+                .map(|stock| format!("{}={}", stock.location, stock.quantity))
+                .collect::<Vec<_>>()
+                .join(";"),
+        }
+    }
+
+    fn into_item(self) -> Result<Item, StorageError> {
+        let amount = |text: &str| {
+            Money::parse(text)
+                .map_err(|e| StorageError::Format(format!("item {}: '{}': {}", self.id, text, e)))
+        };
+        let reorder = match (self.reorder_threshold, self.reorder_quantity) {
+            (Some(threshold), Some(quantity)) => Some(Reorder {
+                threshold,
+                quantity,
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(StorageError::Format(format!(
+                    "item {}: a reorder threshold needs a reorder quantity and the other way round",
+                    self.id
+                )))
+            }
+        };
+        let locations = self
+            .locations
+            .split(';')
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || {
+                    StorageError::Format(format!(
+                        "item {}: '{}' is not a location=quantity pair",
+                        self.id, entry
+                    ))
+                };
+                let (location, quantity) = entry.split_once('=').ok_or_else(invalid)?;
+                Ok(Stock {
+                    location: Location::parse(location).map_err(|_| invalid())?,
+                    quantity: quantity.trim().parse().map_err(|_| invalid())?,
+                })
+            })
+            .collect::<Result<_, StorageError>>()?;
+        Ok(Item {
+            locations,
+            cost: amount(&self.cost)?,
+            price: amount(&self.price)?,
+            supplier: self.supplier,
+            reorder,
+            id: self.id,
+            name: self.name,
+            category: CategoryId(self.category),
+            quantity: self.quantity,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MovementRecord {
+    item_id: i32,
+    kind: String,
+    change: i32,
+    balance: i32,
+}
+
+/// An entry of the audit log, with the item as it was before and after
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditRow {
+    timestamp: u64,
+    actor: String,
+    operation: String,
+    item_id: i32,
+    before: Option<ItemRecord>,
+    after: Option<ItemRecord>,
+    /// Added in version 6
+    #[serde(default)]
+    with_previous: bool,
+}
+
+/// An inventory laid out the way the backends store it
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    schema_version: u32,
+    categories: Vec<CategoryRecord>,
+    items: Vec<ItemRecord>,
+    movements: Vec<MovementRecord>,
+    /// Added in version 4
+    #[serde(default)]
+    audit: Vec<AuditRow>,
+}
+
+impl Snapshot {
+    fn of(inventory: &Inventory) -> Snapshot {
+        let taxonomy = &inventory.categories;
+        Snapshot {
+            schema_version: SCHEMA_VERSION,
+            categories: taxonomy
+                .ids()
+                .map(|id| CategoryRecord {
+                    id: id.0,
+                    name: taxonomy.name(id).to_string(),
+                    parent: taxonomy.parent(id).map(|parent| parent.0),
+                })
+                .collect(),
+            items: inventory.items().iter().map(ItemRecord::of).collect(),
+            movements: inventory
+                .movements()
+                .iter()
+                .map(|movement| MovementRecord {
+                    item_id: movement.item_id,
+                    kind: movement.kind.name().to_string(),
+                    change: movement.change,
+                    balance: movement.balance,
+                })
+                .collect(),
+            audit: inventory
+                .audit_log()
+                .iter()
+                .map(|record| AuditRow {
+                    timestamp: record.timestamp,
+                    actor: record.actor.clone(),
+                    operation: record.operation.name().to_string(),
+                    item_id: record.item_id,
+                    before: record.before.as_ref().map(ItemRecord::of),
+                    after: record.after.as_ref().map(ItemRecord::of),
+                    with_previous: record.with_previous,
+                })
+                .collect(),
+        }
+    }
+
+    fn into_inventory(self) -> Result<Inventory, StorageError> {
+        check_version(self.schema_version)?;
+
+        // Categories are stored parents first and numbered in order, so
+        // adding them again hands out the same IDs
+        let mut taxonomy = Taxonomy::new();
+        for record in self.categories {
+            let id = taxonomy.add_category(&record.name, record.parent.map(CategoryId))?;
+            if id.0 != record.id {
+                return Err(StorageError::Format(format!(
+                    "category '{}' is numbered {} but should be {}",
+                    record.name, record.id, id.0
+                )));
+            }
+        }
+
+        let items: Vec<Item> = self
+            .items
+            .into_iter()
+            .map(ItemRecord::into_item)
+            .collect::<Result<_, StorageError>>()?;
+
+        let movements = self
+            .movements
+            .into_iter()
+            .map(|record| {
+                let kind = MovementKind::from_name(&record.kind).ok_or_else(|| {
+                    StorageError::Format(format!("unknown movement kind '{}'", record.kind))
+                })?;
+                Ok(Movement {
+                    item_id: record.item_id,
+                    kind,
+                    change: record.change,
+                    balance: record.balance,
+                })
+            })
+            .collect::<Result<_, StorageError>>()?;
+
+        // Before version 4 there was no audit log. The items found are
+        // where it starts, so replaying it still rebuilds them.
+        let audit = if self.schema_version < 4 {
+            items.iter().map(baseline).collect()
+        } else {
+            self.audit
+                .into_iter()
+                .map(|row| {
+                    let operation = Operation::from_name(&row.operation).ok_or_else(|| {
+                        StorageError::Format(format!("unknown audit operation '{}'", row.operation))
+                    })?;
+                    Ok(AuditRecord {
+                        timestamp: row.timestamp,
+                        actor: row.actor,
+                        operation,
+                        item_id: row.item_id,
+                        before: row.before.map(ItemRecord::into_item).transpose()?,
+                        after: row.after.map(ItemRecord::into_item).transpose()?,
+                        with_previous: row.with_previous,
+                    })
+                })
+                .collect::<Result<_, StorageError>>()?
+        };
+
+        Ok(Inventory::from_parts(taxonomy, items, movements, audit)?)
+    }
+}
+
+/// The audit record for an item that was there before the audit log
+fn baseline(item: &Item) -> AuditRecord {
+    AuditRecord {
+        timestamp: 0,
+        actor: "unknown".to_string(),
+        operation: Operation::Baseline,
+        item_id: item.id,
+        before: None,
+        after: Some(item.clone()),
+        with_previous: false,
+    }
+}
@@ -0,0 +1,322 @@
+//! The inventory in an embedded SQLite database, one table each for
+//! categories, items, movements and the audit log, whose before and after
+//! items are kept in `audit_items`. The schema version is kept in
+//! `PRAGMA user_version`, and a save replaces every row in one transaction.
+//! Saving over a database from an older version rebuilds its items table,
+//! which had fewer columns: no locations before version 5, no supplier or
+//! reorder rule before version 3 and a whole-number price with no cost in
+//! version 1. The audit tables of versions 4 and 5 are rebuilt as well: the
+//! items in `audit_items` had no locations before version 5, and `audit`
+//! had no `with_previous` column before version 6.
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, Row};
+
+use super::{
+    check_version, create_parent, v1_amounts, AuditRow, CategoryRecord, ItemRecord, MovementRecord,
+    Snapshot, Storage, StorageError, SCHEMA_VERSION,
+};
+use crate::inventory::Inventory;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS categories (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        parent INTEGER REFERENCES categories (id)
+    );
+    CREATE TABLE IF NOT EXISTS items (
+        position INTEGER PRIMARY KEY,
+        id INTEGER NOT NULL UNIQUE,
+        name TEXT NOT NULL,
+        category INTEGER NOT NULL REFERENCES categories (id),
+        quantity INTEGER NOT NULL,
+        cost TEXT NOT NULL,
+        price TEXT NOT NULL,
+        supplier TEXT,
+        reorder_threshold INTEGER,
+        reorder_quantity INTEGER,
+        locations TEXT NOT NULL DEFAULT ''
+    );
+    CREATE TABLE IF NOT EXISTS movements (
+        position INTEGER PRIMARY KEY,
+        item_id INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        change INTEGER NOT NULL,
+        balance INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS audit (
+        position INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        actor TEXT NOT NULL,
+        operation TEXT NOT NULL,
+        item_id INTEGER NOT NULL,
+        with_previous INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS audit_items (
+        audit INTEGER NOT NULL REFERENCES audit (position),
+        side TEXT NOT NULL CHECK (side IN ('before', 'after')),
+        id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        category INTEGER NOT NULL,
+        quantity INTEGER NOT NULL,
+        cost TEXT NOT NULL,
+        price TEXT NOT NULL,
+        supplier TEXT,
+        reorder_threshold INTEGER,
+        reorder_quantity INTEGER,
+        locations TEXT NOT NULL DEFAULT '',
+        PRIMARY KEY (audit, side)
+    );
+";
+
+/// The columns of an item, in the order `item_record` reads them
+const ITEM_COLUMNS: &str = "id, name, category, quantity, cost, price, supplier, \
+    reorder_threshold, reorder_quantity, locations";
+
+pub struct SqliteStorage {
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    pub fn new(path: impl AsRef<Path>) -> SqliteStorage {
+        SqliteStorage {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+/// The item in the columns of `row` from `start` on, as `ITEM_COLUMNS` or
+/// the fewer columns of an older `version`
+fn item_record(row: &Row, start: usize, version: u32) -> rusqlite::Result<ItemRecord> {
+    let column = |offset: usize| start + offset;
+    let (cost, price) = match version {
+        1 => v1_amounts(row.get(column(4))?),
+        _ => (row.get(column(4))?, row.get(column(5))?),
+    };
+    Ok(ItemRecord {
+        id: row.get(column(0))?,
+        name: row.get(column(1))?,
+        category: row.get(column(2))?,
+        quantity: row.get(column(3))?,
+        cost,
+        price,
+        supplier: if version < 3 {
+            None
+        } else {
+            row.get(column(6))?
+        },
+        reorder_threshold: if version < 3 {
+            None
+        } else {
+            row.get(column(7))?
+        },
+        reorder_quantity: if version < 3 {
+            None
+        } else {
+            row.get(column(8))?
+        },
+        locations: if version < 5 {
+            String::new()
+        } else {
+            row.get(column(9))?
+        },
+    })
+}
+
+/// The schema version of the database, 0 for one that was never saved to
+fn user_version(db: &Connection) -> Result<u32, StorageError> {
+    Ok(db.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Inventory, StorageError> {
+        if !self.path.exists() {
+            return Ok(Inventory::new());
+        }
+        let db = Connection::open(&self.path)?;
+        let version = user_version(&db)?;
+        if version == 0 {
+            return Ok(Inventory::new());
+        }
+        check_version(version)?;
+
+        let categories = db
+            .prepare("SELECT id, name, parent FROM categories ORDER BY id")?
+            .query_map([], |row| {
+                Ok(CategoryRecord {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent: row.get(2)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        let columns = match version {
+            1 => "id, name, category, quantity, price",
+            2 => "id, name, category, quantity, cost, price",
+            3 | 4 => {
+                "id, name, category, quantity, cost, price, supplier, reorder_threshold, \
+                 reorder_quantity"
+            }
+            _ => ITEM_COLUMNS,
+        };
+        let items = db
+            .prepare(&format!("SELECT {} FROM items ORDER BY position", columns))?
+            .query_map([], |row| item_record(row, 0, version))?
+            .collect::<Result<_, _>>()?;
+        let movements = db
+            .prepare("SELECT item_id, kind, change, balance FROM movements ORDER BY position")?
+            .query_map([], |row| {
+                Ok(MovementRecord {
+                    item_id: row.get(0)?,
+                    kind: row.get(1)?,
+                    change: row.get(2)?,
+                    balance: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        let mut audit: Vec<AuditRow> = Vec::new();
+        if version >= 4 {
+            let with_previous = if version < 6 { "0" } else { "with_previous" };
+            audit = db
+                .prepare(&format!(
+                    "SELECT timestamp, actor, operation, item_id, {} FROM audit ORDER BY position",
+                    with_previous
+                ))?
+                .query_map([], |row| {
+                    Ok(AuditRow {
+                        timestamp: row.get(0)?,
+                        actor: row.get(1)?,
+                        operation: row.get(2)?,
+                        item_id: row.get(3)?,
+                        before: None,
+                        after: None,
+                        with_previous: row.get(4)?,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            let mut sides = db.prepare(&format!(
+                "SELECT audit, side, {} FROM audit_items ORDER BY audit",
+                columns
+            ))?;
+            let mut rows = sides.query([])?;
+            while let Some(row) = rows.next()? {
+                let position: usize = row.get(0)?;
+                let side: String = row.get(1)?;
+                let record = audit.get_mut(position).ok_or_else(|| {
+                    StorageError::Format(format!("audit item for missing entry {}", position))
+                })?;
+                let item = Some(item_record(row, 2, version)?);
+                if side == "before" {
+                    record.before = item;
+                } else {
+                    record.after = item;
+                }
+            }
+        }
+
+        Snapshot {
+            schema_version: version,
+            categories,
+            items,
+            movements,
+            audit,
+        }
+        .into_inventory()
+    }
+
+    fn save(&self, inventory: &Inventory) -> Result<(), StorageError> {
+        create_parent(&self.path)?;
+        let mut db = Connection::open(&self.path)?;
+        let version = user_version(&db)?;
+        if version != 0 {
+            check_version(version)?;
+        }
+
+        let snapshot = Snapshot::of(inventory);
+        let tx = db.transaction()?;
+        if version != 0 && version < SCHEMA_VERSION {
+            tx.execute_batch("DROP TABLE items;")?;
+            if version >= 4 {
+                tx.execute_batch("DROP TABLE audit_items; DROP TABLE audit;")?;
+            }
+        }
+        tx.execute_batch(SCHEMA)?;
+        tx.execute_batch(
+            "DELETE FROM audit_items; DELETE FROM audit; DELETE FROM movements;
+             DELETE FROM items; DELETE FROM categories;",
+        )?;
+        {
+            let mut insert =
+                tx.prepare("INSERT INTO categories (id, name, parent) VALUES (?1, ?2, ?3)")?;
+            for c in &snapshot.categories {
+                insert.execute(params![c.id, c.name, c.parent])?;
+            }
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO items ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                ITEM_COLUMNS
+            ))?;
+            for i in &snapshot.items {
+                insert.execute(params![
+                    i.id,
+                    i.name,
+                    i.category,
+                    i.quantity,
+                    i.cost,
+                    i.price,
+                    i.supplier,
+                    i.reorder_threshold,
+                    i.reorder_quantity,
+                    i.locations
+                ])?;
+            }
+            let mut insert = tx.prepare(
+                "INSERT INTO movements (item_id, kind, change, balance) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for m in &snapshot.movements {
+                insert.execute(params![m.item_id, m.kind, m.change, m.balance])?;
+            }
+            let mut insert = tx.prepare(
+                "INSERT INTO audit (position, timestamp, actor, operation, item_id, with_previous)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let mut insert_item = tx.prepare(&format!(
+                "INSERT INTO audit_items (audit, side, {})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                ITEM_COLUMNS
+            ))?;
+            for (position, a) in snapshot.audit.iter().enumerate() {
+                insert.execute(params![
+                    position,
+                    a.timestamp,
+                    a.actor,
+                    a.operation,
+                    a.item_id,
+                    a.with_previous
+                ])?;
+                for (side, item) in [("before", &a.before), ("after", &a.after)] {
+                    let Some(i) = item else {
+                        continue;
+                    };
+                    insert_item.execute(params![
+                        position,
+                        side,
+                        i.id,
+                        i.name,
+                        i.category,
+                        i.quantity,
+                        i.cost,
+                        i.price,
+                        i.supplier,
+                        i.reorder_threshold,
+                        i.reorder_quantity,
+                        i.locations
+                    ])?;
+                }
+            }
+        }
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(tx.commit()?)
+    }
+}
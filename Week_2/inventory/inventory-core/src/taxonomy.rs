@@ -0,0 +1,136 @@
+//! Categories defined at runtime as a hierarchy, such as
+//! `Electronics > Phones > Android`
+
+use crate::error::InventoryError;
+
+/// Position of a category in its `Taxonomy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CategoryId(pub usize);
+
+/// One node of the category tree, such as `Phones` under `Electronics`
+#[derive(Debug, Clone, PartialEq)]
+struct Category {
+    name: String,
+    parent: Option<CategoryId>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Taxonomy {
+    categories: Vec<Category>,
+}
+
+impl Taxonomy {
+    pub fn new() -> Taxonomy {
+        Taxonomy::default()
+    }
+
+    /// Add a category under `parent`, or at the top level when it has none
+    pub fn add_category(
+        &mut self,
+        name: &str,
+        parent: Option<CategoryId>,
+    ) -> Result<CategoryId, InventoryError> {
+        let name = name.trim();
+        if name.is_empty() || name.contains('>') {
+            return Err(InventoryError::InvalidCategoryName);
+        }
+        if parent.is_some_and(|parent| !self.contains(parent)) {
+            return Err(InventoryError::CategoryNotFound);
+        }
+        if self.child(parent, name).is_some() {
+            return Err(InventoryError::DuplicateCategory);
+        }
+        self.categories.push(Category {
+            name: name.to_string(),
+            parent,
+        });
+        Ok(CategoryId(self.categories.len() - 1))
+    }
+
+    /// Add whatever is missing along a path such as
+    /// `"Electronics > Phones > Android"` and return the last category on it
+    pub fn add_path(&mut self, path: &str) -> Result<CategoryId, InventoryError> {
+        let mut parent = None;
+        for name in path.split('>') {
+            let id = match self.child(parent, name.trim()) {
+                Some(id) => id,
+                None => self.add_category(name, parent)?,
+            };
+            parent = Some(id);
+        }
+        parent.ok_or(InventoryError::InvalidCategoryName)
+    }
+
+    /// Look up a path such as `"Electronics > Phones"` without adding anything
+    pub fn find(&self, path: &str) -> Result<CategoryId, InventoryError> {
+        let mut parent = None;
+        for name in path.split('>') {
+            let id = self
+                .child(parent, name.trim())
+                .ok_or(InventoryError::CategoryNotFound)?;
+            parent = Some(id);
+        }
+        parent.ok_or(InventoryError::CategoryNotFound)
+    }
+
+    pub fn contains(&self, id: CategoryId) -> bool {
+        id.0 < self.categories.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.categories.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty()
+    }
+
+    /// Every category in the order it was added, so parents come before
+    /// their children
+    pub fn ids(&self) -> impl Iterator<Item = CategoryId> {
+        (0..self.categories.len()).map(CategoryId)
+    }
+
+    pub fn name(&self, id: CategoryId) -> &str {
+        &self.categories[id.0].name
+    }
+
+    pub fn parent(&self, id: CategoryId) -> Option<CategoryId> {
+        self.categories[id.0].parent
+    }
+
+    /// The categories directly under `parent`, or the top-level ones for `None`
+    pub fn children(&self, parent: Option<CategoryId>) -> Vec<CategoryId> {
+        self.ids().filter(|&id| self.parent(id) == parent).collect()
+    }
+
+    fn child(&self, parent: Option<CategoryId>, name: &str) -> Option<CategoryId> {
+        self.children(parent)
+            .into_iter()
+            .find(|&id| self.name(id) == name)
+    }
+
+    /// Whether `id` is `ancestor` itself or somewhere below it
+    pub fn is_under(&self, id: CategoryId, ancestor: CategoryId) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.parent(id);
+        }
+        false
+    }
+
+    /// The full name of a category, such as `"Electronics > Phones > Android"`
+    pub fn path(&self, id: CategoryId) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(id);
+        while let Some(id) = current {
+            names.push(self.name(id));
+            current = self.parent(id);
+        }
+        names.reverse();
+        names.join(" > ")
+    }
+}
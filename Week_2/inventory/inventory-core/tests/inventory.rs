@@ -0,0 +1,764 @@
+use std::fs;
+use std::path::PathBuf;
+
+use std::sync::{Arc, Mutex};
+
+use inventory_core::{
+    Amounts, CsvStorage, Currency, ExchangeRates, Inventory, InventoryError, Item, ItemUpdate,
+    JsonStorage, Location, LocationFilter, Money, MovementKind, Operation, Reorder, SqliteStorage,
+    Stock, Storage, StorageError, SCHEMA_VERSION,
+};
+
+fn money(text: &str) -> Money {
+    Money::parse(text).unwrap()
+}
+
+/// A phone under Electronics > Phones > Android and a case on Phones itself,
+/// both in dollars and reordered from Acme, and bread in naira under
+/// Groceries that nobody watches
+fn shop() -> Inventory {
+    let mut inv = Inventory::new();
+    let android = inv
+        .categories
+        .add_path("Electronics > Phones > Android")
+        .unwrap();
+    let phones = inv.categories.find("Electronics > Phones").unwrap();
+    let groceries = inv.categories.add_path("Groceries").unwrap();
+    let acme = |threshold, quantity| {
+        (
+            Some("Acme".to_string()),
+            Some(Reorder {
+                threshold,
+                quantity,
+            }),
+        )
+    };
+    for (id, name, category, quantity, cost, price, (supplier, reorder)) in [
+        (1, "Phone", android, 5, "800 USD", "1000 USD", acme(3, 10)),
+        (2, "Phone Case", phones, 10, "5 USD", "15 USD", acme(5, 20)),
+        (
+            3,
+            "Bread",
+            groceries,
+            2,
+            "1200 NGN",
+            "1500 NGN",
+            (None, None),
+        ),
+    ] {
+        inv.add_item(Item {
+            id,
+            name: name.to_string(),
+            category,
+            quantity,
+            cost: money(cost),
+            price: money(price),
+            supplier,
+            reorder,
+            locations: Vec::new(),
+        })
+        .unwrap();
+    }
+    inv
+}
+
+/// A fresh path under the system temporary directory
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("inventory-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn location(text: &str) -> Location {
+    Location::parse(text).unwrap()
+}
+
+fn filter(text: &str) -> LocationFilter {
+    LocationFilter::parse(text).unwrap()
+}
+
+// Categories
+
+#[test]
+fn paths_reuse_existing_categories() {
+    let mut inv = shop();
+    let phones = inv.categories.find("Electronics > Phones").unwrap();
+    assert_eq!(inv.categories.add_path("Electronics>Phones"), Ok(phones));
+    assert_eq!(
+        inv.categories
+            .add_category("Phones", inv.categories.find("Electronics").ok()),
+        Err(InventoryError::DuplicateCategory)
+    );
+    assert_eq!(
+        inv.categories.add_path("Electronics > > Tablets"),
+        Err(InventoryError::InvalidCategoryName)
+    );
+    assert_eq!(
+        inv.categories.find("Groceries > Dairy"),
+        Err(InventoryError::CategoryNotFound)
+    );
+    let android = inv
+        .categories
+        .find("Electronics > Phones > Android")
+        .unwrap();
+    assert_eq!(
+        inv.categories.path(android),
+        "Electronics > Phones > Android"
+    );
+    assert!(inv.categories.is_under(android, phones));
+    assert!(!inv.categories.is_under(phones, android));
+}
+
+#[test]
+fn categorize_rolls_up_counts_and_values() {
+    let inv = shop();
+    let tree = inv.categorize();
+    assert_eq!(tree.len(), 2);
+
+    let electronics = &tree[0];
+    assert_eq!(electronics.name, "Electronics");
+    assert!(electronics.items.is_empty());
+    assert_eq!((electronics.item_count, electronics.quantity), (2, 15));
+    assert_eq!(electronics.value.to_string(), "5150.00 USD");
+
+    let phones = &electronics.subcategories[0];
+    assert_eq!(phones.items[0].name, "Phone Case");
+    assert_eq!(phones.subcategories[0].name, "Android");
+    assert_eq!(phones.subcategories[0].value.to_string(), "5000.00 USD");
+
+    assert_eq!((tree[1].name, tree[1].item_count), ("Groceries", 1));
+    assert_eq!(tree[1].value.to_string(), "3000.00 NGN");
+}
+
+// Stock
+
+#[test]
+fn stock_moves_only_through_restock_and_sell() {
+    let mut inv = shop();
+    assert_eq!(inv.restock(2, 5), Ok(15));
+    assert_eq!(inv.sell(2, 12), Ok(3));
+    assert_eq!(inv.sell(2, 4), Err(InventoryError::InsufficientStock));
+    assert_eq!(inv.restock(2, 0), Err(InventoryError::InvalidQuantity));
+    assert_eq!(inv.sell(99, 1), Err(InventoryError::ItemNotFound));
+
+    let history: Vec<(MovementKind, i32, i32)> = inv
+        .movements_for(2)
+        .iter()
+        .map(|m| (m.kind, m.change, m.balance))
+        .collect();
+    assert_eq!(
+        history,
+        [
+            (MovementKind::Added, 10, 10),
+            (MovementKind::Restock, 5, 15),
+            (MovementKind::Sale, -12, 3),
+        ]
+    );
+}
+
+#[test]
+fn updates_and_removals_are_checked() {
+    let mut inv = shop();
+    let update = ItemUpdate {
+        name: Some("Phone Cover".to_string()),
+        price: Some(money("12.50 USD")),
+        ..ItemUpdate::default()
+    };
+    let item = inv.update_item(2, update).unwrap();
+    assert_eq!(
+        (item.name.as_str(), item.quantity, item.price),
+        ("Phone Cover", 10, money("12.50 USD"))
+    );
+
+    let negative = ItemUpdate {
+        cost: Some(money("-0.01 USD")),
+        ..ItemUpdate::default()
+    };
+    assert_eq!(
+        inv.update_item(2, negative),
+        Err(InventoryError::InvalidPrice)
+    );
+
+    assert_eq!(inv.remove_item(3).unwrap().name, "Bread");
+    assert_eq!(inv.remove_item(3), Err(InventoryError::ItemNotFound));
+    let written_off = inv.movements().last().unwrap();
+    assert_eq!(
+        (written_off.kind, written_off.change),
+        (MovementKind::Removed, -2)
+    );
+}
+
+// Reordering
+
+#[test]
+fn sales_that_take_an_item_below_its_threshold_call_the_hooks() {
+    let mut inv = shop();
+    let alerts = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&alerts);
+    inv.on_low_stock(move |item| seen.lock().unwrap().push((item.id, item.quantity)));
+
+    inv.sell(1, 2).unwrap(); // 3 left, not below 3
+    inv.sell(1, 1).unwrap(); // crosses
+    inv.sell(1, 1).unwrap(); // already low
+    inv.restock(1, 10).unwrap();
+    inv.sell(1, 10).unwrap(); // crosses again
+    inv.sell(3, 2).unwrap(); // no rule
+    assert_eq!(*alerts.lock().unwrap(), [(1, 2), (1, 1)]);
+
+    let bad = ItemUpdate {
+        reorder: Some(Some(Reorder {
+            threshold: 5,
+            quantity: 0,
+        })),
+        ..ItemUpdate::default()
+    };
+    assert_eq!(
+        inv.update_item(2, bad),
+        Err(InventoryError::InvalidQuantity)
+    );
+}
+
+#[test]
+fn low_items_are_drafted_into_orders_by_supplier() {
+    let mut inv = shop();
+    assert!(inv.low_stock().is_empty());
+    assert!(inv.purchase_orders().is_empty());
+
+    inv.sell(1, 4).unwrap();
+    inv.sell(2, 8).unwrap();
+    let update = ItemUpdate {
+        supplier: Some(None),
+        reorder: Some(Some(Reorder {
+            threshold: 1,
+            quantity: 12,
+        })),
+        ..ItemUpdate::default()
+    };
+    inv.update_item(3, update).unwrap();
+    inv.sell(3, 2).unwrap();
+    let low: Vec<i32> = inv.low_stock().iter().map(|item| item.id).collect();
+    assert_eq!(low, [1, 2, 3]);
+
+    let orders = inv.purchase_orders();
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders[0].supplier, Some("Acme"));
+    let lines: Vec<(i32, i32, Money)> = orders[0]
+        .lines
+        .iter()
+        .map(|line| (line.item.id, line.quantity, line.cost))
+        .collect();
+    assert_eq!(
+        lines,
+        [(1, 10, money("8000 USD")), (2, 20, money("100 USD"))]
+    );
+    assert_eq!(orders[0].total.to_string(), "8100.00 USD");
+    assert_eq!(orders[1].supplier, None);
+    assert_eq!(orders[1].total.to_string(), "14400.00 NGN");
+}
+
+// Audit log
+
+#[test]
+fn every_change_is_logged_with_who_made_it() {
+    let mut inv = shop();
+    inv.set_actor("ana");
+    inv.sell(1, 2).unwrap();
+    let update = ItemUpdate {
+        price: Some(money("900 USD")),
+        ..ItemUpdate::default()
+    };
+    inv.update_item(1, update).unwrap();
+    inv.remove_item(1).unwrap();
+    assert_eq!(inv.sell(1, 1), Err(InventoryError::ItemNotFound));
+
+    let history: Vec<(Operation, &str, Option<i32>, Option<i32>)> = inv
+        .history(1)
+        .iter()
+        .map(|record| {
+            (
+                record.operation,
+                record.actor.as_str(),
+                record.before.as_ref().map(|item| item.quantity),
+                record.after.as_ref().map(|item| item.quantity),
+            )
+        })
+        .collect();
+    assert_eq!(
+        history,
+        [
+            (Operation::Add, "unknown", None, Some(5)),
+            (Operation::Sale, "ana", Some(5), Some(3)),
+            (Operation::Update, "ana", Some(3), Some(3)),
+            (Operation::Remove, "ana", Some(3), None),
+        ]
+    );
+    let log = inv.audit_log();
+    assert!(log.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    assert_eq!(log[4].after.as_ref().unwrap().price, money("900 USD"));
+}
+
+#[test]
+fn undo_and_redo_step_through_the_changes() {
+    let mut inv = shop();
+    let start = inv.clone();
+    assert_eq!(inv.redo(), Err(InventoryError::NothingToRedo));
+
+    inv.sell(1, 2).unwrap();
+    inv.remove_item(3).unwrap();
+    assert_eq!(inv.undo().unwrap()[0].operation, Operation::Remove);
+    assert_eq!(inv.get_item_by_id(3).unwrap().quantity, 2);
+    assert_eq!(inv.undo().unwrap()[0].operation, Operation::Sale);
+    assert_eq!(inv.items(), start.items());
+
+    assert_eq!(inv.redo().unwrap()[0].operation, Operation::Sale);
+    assert_eq!(inv.get_item_by_id(1).unwrap().quantity, 3);
+
+    // A new change forgets what could have been redone
+    inv.restock(2, 1).unwrap();
+    assert_eq!(inv.redo(), Err(InventoryError::NothingToRedo));
+
+    let kinds: Vec<(MovementKind, i32)> = inv
+        .movements_for(1)
+        .iter()
+        .map(|m| (m.kind, m.change))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (MovementKind::Added, 5),
+            (MovementKind::Sale, -2),
+            (MovementKind::Undo, 2),
+            (MovementKind::Redo, -2),
+        ]
+    );
+
+    // Undo reaches back past the items that were added
+    while inv.undo().is_ok() {}
+    assert!(inv.items().is_empty());
+}
+
+#[test]
+fn grouped_changes_undo_in_one_step() {
+    let mut inv = shop();
+    let start = inv.clone();
+    inv.grouped(|inv| {
+        inv.sell(1, 1)?;
+        inv.sell(1, 1)?;
+        inv.remove_item(3)?;
+        Ok(())
+    })
+    .unwrap();
+    let done = inv.clone();
+
+    let undone = inv.undo().unwrap();
+    let operations: Vec<Operation> = undone.iter().map(|record| record.operation).collect();
+    assert_eq!(
+        operations,
+        [Operation::Sale, Operation::Sale, Operation::Remove]
+    );
+    assert_eq!(inv.items(), start.items());
+    assert_eq!(inv.redo().unwrap().len(), 3);
+    assert_eq!(inv.items(), done.items());
+
+    // The earlier changes are still undone one at a time
+    inv.undo().unwrap();
+    assert_eq!(inv.undo().unwrap()[0].operation, Operation::Add);
+    assert_eq!(inv.items().len(), 2);
+
+    // A group that fails leaves nothing behind
+    let before = inv.clone();
+    assert_eq!(
+        inv.grouped(|inv| {
+            inv.sell(1, 1)?;
+            inv.sell(2, 1_000)
+        }),
+        Err(InventoryError::InsufficientStock)
+    );
+    assert_eq!(inv, before);
+}
+
+#[test]
+fn replaying_the_log_rebuilds_past_states() {
+    let mut inv = shop();
+    let mut states = vec![inv.clone()];
+    inv.sell(1, 2).unwrap();
+    states.push(inv.clone());
+    inv.remove_item(2).unwrap();
+    states.push(inv.clone());
+    inv.undo().unwrap();
+    states.push(inv.clone());
+
+    let added = inv.audit_log().len() - states.len() + 1;
+    for (step, state) in states.iter().enumerate() {
+        assert_eq!(&inv.replay(added + step), state);
+    }
+    assert!(inv.as_of(0).items().is_empty());
+    assert_eq!(inv.as_of(u64::MAX), inv);
+}
+
+// Money
+
+#[test]
+fn amounts_parse_and_print_exactly() {
+    assert_eq!(
+        money("2.50 USD"),
+        Money::new(250, Currency::new("usd").unwrap())
+    );
+    assert_eq!(money("USD 2.5"), money("2.50 USD"));
+    assert_eq!(money("-0.05 NGN").to_string(), "-0.05 NGN");
+    assert_eq!(money("1000 JPY").to_string(), "1000 JPY");
+    assert_eq!(money("1.005 KWD").minor, 1005);
+
+    assert_eq!(Money::parse("2.505 USD"), Err(InventoryError::InvalidMoney));
+    assert_eq!(Money::parse("1.5 JPY"), Err(InventoryError::InvalidMoney));
+    assert_eq!(Money::parse("2.50"), Err(InventoryError::InvalidMoney));
+    assert_eq!(
+        Money::parse("2.50 US$"),
+        Err(InventoryError::InvalidCurrency)
+    );
+    assert_eq!(
+        Money::parse("99999999999999999999 USD"),
+        Err(InventoryError::AmountTooLarge)
+    );
+    assert_eq!(
+        money("1 USD").checked_add(money("1 NGN")),
+        Err(InventoryError::CurrencyMismatch)
+    );
+
+    let mut total = Amounts::new();
+    for text in ["12.50 USD", "5000 NGN", "0.50 USD"] {
+        total.add(money(text));
+    }
+    assert_eq!(total.to_string(), "5000.00 NGN + 13.00 USD");
+    assert_eq!(Amounts::new().to_string(), "0");
+}
+
+#[test]
+fn rates_convert_through_the_base_and_round_half_away() {
+    let rates =
+        ExchangeRates::parse("# month end\nbase = USD\nNGN = 1500\nEUR = 0.8 # rounded\n").unwrap();
+    let usd = Currency::new("USD").unwrap();
+    let ngn = Currency::new("NGN").unwrap();
+    let eur = Currency::new("EUR").unwrap();
+
+    assert_eq!(
+        rates.convert(money("10.01 USD"), ngn),
+        Ok(money("15015 NGN"))
+    );
+    assert_eq!(rates.convert(money("7.50 NGN"), usd), Ok(money("0.01 USD")));
+    assert_eq!(rates.convert(money("7.49 NGN"), usd), Ok(money("0.00 USD")));
+    assert_eq!(
+        rates.convert(money("-7.50 NGN"), usd),
+        Ok(money("-0.01 USD"))
+    );
+    assert_eq!(rates.convert(money("1500 NGN"), eur), Ok(money("0.80 EUR")));
+    assert_eq!(
+        rates.convert(money("1 GBP"), usd),
+        Err(InventoryError::MissingExchangeRate)
+    );
+
+    assert_eq!(
+        ExchangeRates::parse("base = USD\nNGN = lots\n").unwrap_err(),
+        "line 2: 'lots' is not a positive rate"
+    );
+    assert!(ExchangeRates::parse("NGN = 1500\n").is_err());
+}
+
+#[test]
+fn valuation_and_margins_use_the_rates() {
+    let mut inv = shop();
+    let valuation = inv.valuation();
+    assert_eq!(valuation.cost.to_string(), "2400.00 NGN + 4050.00 USD");
+    assert_eq!(valuation.retail.to_string(), "3000.00 NGN + 5150.00 USD");
+
+    let rates = ExchangeRates::parse("base = USD\nNGN = 1500\n").unwrap();
+    let usd = Currency::new("USD").unwrap();
+    assert_eq!(rates.total(&valuation.retail, usd), Ok(money("5152 USD")));
+
+    // Bought in naira and sold in dollars
+    let update = ItemUpdate {
+        cost: Some(money("1200000 NGN")),
+        ..ItemUpdate::default()
+    };
+    inv.update_item(1, update).unwrap();
+    let margins: Vec<(i32, Money, Option<i64>)> = inv
+        .margins(&rates)
+        .unwrap()
+        .iter()
+        .map(|m| (m.item.id, m.margin, m.basis_points))
+        .collect();
+    assert_eq!(
+        margins,
+        [
+            (1, money("200 USD"), Some(2000)),
+            (2, money("10 USD"), Some(6667)),
+            (3, money("300 NGN"), Some(2000)),
+        ]
+    );
+
+    let no_rates = ExchangeRates::new(usd);
+    assert_eq!(
+        inv.margins(&no_rates).unwrap_err(),
+        InventoryError::MissingExchangeRate
+    );
+}
+
+// Locations
+
+#[test]
+fn transfers_move_stock_in_one_logged_change() {
+    let mut inv = shop();
+    let (shelf, annex) = (location("Main/A3/07"), location("Annex/A1/01"));
+    let phone = inv.transfer(1, None, Some(&shelf), 3).unwrap();
+    assert_eq!((phone.quantity, phone.unplaced()), (5, 2));
+
+    // Nothing moves unless all of it can
+    let before = inv.clone();
+    assert_eq!(
+        inv.transfer(1, Some(&shelf), Some(&annex), 4),
+        Err(InventoryError::InsufficientStock)
+    );
+    assert_eq!(
+        inv.transfer(1, Some(&shelf), Some(&shelf), 1),
+        Err(InventoryError::SameLocation)
+    );
+    assert_eq!(inv, before);
+
+    let phone = inv.transfer(1, Some(&shelf), Some(&annex), 2).unwrap();
+    assert_eq!(
+        phone.locations,
+        [
+            Stock {
+                location: shelf.clone(),
+                quantity: 1
+            },
+            Stock {
+                location: annex.clone(),
+                quantity: 2
+            },
+        ]
+    );
+    let record = inv.audit_log().last().unwrap();
+    assert_eq!(record.operation, Operation::Transfer);
+    assert_eq!(inv.movements_for(1).len(), 1);
+
+    inv.undo().unwrap();
+    assert_eq!(inv.items(), before.items());
+    inv.redo().unwrap();
+
+    // A sale from a location takes only what is there; any other sale
+    // takes the unplaced stock first
+    assert_eq!(
+        inv.sell_from(1, &annex, 3),
+        Err(InventoryError::InsufficientStock)
+    );
+    assert_eq!(inv.sell_from(1, &annex, 2), Ok(3));
+    assert_eq!(inv.sell(1, 2), Ok(1));
+    assert_eq!(inv.get_item_by_id(1).unwrap().unplaced(), 0);
+    assert_eq!(inv.sell(1, 1), Ok(0));
+    assert!(inv.get_item_by_id(1).unwrap().locations.is_empty());
+    assert_eq!(inv.restock_at(1, &annex, 4), Ok(4));
+    assert_eq!(
+        inv.get_item_by_id(1).unwrap().quantity_at(&filter("Annex")),
+        4
+    );
+}
+
+#[test]
+fn stock_is_counted_by_location_or_across_all() {
+    let mut inv = shop();
+    inv.transfer(1, None, Some(&location("Main/A3/07")), 3)
+        .unwrap();
+    inv.restock_at(2, &location("Main/B1/02"), 5).unwrap();
+    inv.transfer(2, None, Some(&location("Annex/A1/01")), 10)
+        .unwrap();
+
+    let main: Vec<(i32, String, i32)> = inv
+        .stock_levels(Some(&filter("Main")))
+        .iter()
+        .map(|level| {
+            (
+                level.item.id,
+                level.location.unwrap().to_string(),
+                level.quantity,
+            )
+        })
+        .collect();
+    assert_eq!(
+        main,
+        [
+            (1, "Main/A3/07".to_string(), 3),
+            (2, "Main/B1/02".to_string(), 5),
+        ]
+    );
+    assert_eq!(inv.stock_levels(None).len(), 5);
+    assert_eq!(inv.total_stock(None), 22);
+    assert_eq!(inv.total_stock(Some(&filter("Main"))), 8);
+    assert_eq!(inv.total_stock(Some(&filter("Main/A3"))), 3);
+    let names: Vec<String> = inv.locations().iter().map(|l| l.to_string()).collect();
+    assert_eq!(names, ["Annex/A1/01", "Main/A3/07", "Main/B1/02"]);
+
+    let tree = inv.categorize_at(&filter("Main"));
+    assert_eq!((tree[0].item_count, tree[0].quantity), (2, 8));
+    assert_eq!(tree[0].value.to_string(), "3075.00 USD");
+    assert_eq!(tree.len(), 1);
+    assert!(inv.categorize_at(&filter("Main/C9")).is_empty());
+
+    for text in ["Main/A3", "Main//07", "Main/A3/07/1", "Main/A=3/07"] {
+        assert_eq!(Location::parse(text), Err(InventoryError::InvalidLocation));
+    }
+    assert_eq!(
+        LocationFilter::parse("a/b/c/d"),
+        Err(InventoryError::InvalidLocation)
+    );
+
+    let overfilled = Item {
+        id: 4,
+        quantity: 1,
+        locations: vec![Stock {
+            location: location("Main/A3/07"),
+            quantity: 2,
+        }],
+        ..inv.get_item_by_id(1).unwrap().clone()
+    };
+    assert_eq!(
+        inv.add_item(overfilled),
+        Err(InventoryError::InsufficientStock)
+    );
+}
+
+// Storage
+
+#[test]
+fn every_backend_round_trips_the_inventory() {
+    let mut inv = shop();
+    inv.sell(1, 2).unwrap();
+    inv.remove_item(3).unwrap();
+    inv.categories.add_path("Clothing, Shoes & Bags").unwrap();
+    inv.restock_at(2, &location("Main/B1/02"), 3).unwrap();
+    inv.transfer(2, None, Some(&location("Annex/A1/01")), 4)
+        .unwrap();
+    inv.grouped(|inv| {
+        inv.restock(1, 1)?;
+        inv.restock(2, 1)
+    })
+    .unwrap();
+
+    let backends: Vec<Box<dyn Storage>> = vec![
+        Box::new(JsonStorage::new(scratch("round-trip.json"))),
+        Box::new(CsvStorage::new(scratch("round-trip.csv"))),
+        Box::new(SqliteStorage::new(scratch("round-trip.db"))),
+    ];
+    for storage in backends {
+        assert_eq!(storage.load().unwrap(), Inventory::new());
+        storage.save(&inv).unwrap();
+        assert_eq!(storage.load().unwrap(), inv);
+
+        // Saving again replaces rather than appends
+        inv.restock(1, 1).unwrap();
+        storage.save(&inv).unwrap();
+        assert_eq!(storage.load().unwrap(), inv);
+    }
+}
+
+#[test]
+fn newer_schema_versions_are_refused() {
+    let json = scratch("future.json");
+    fs::write(&json, r#"{"schema_version": 99, "items": "whatever"}"#).unwrap();
+    assert!(matches!(
+        JsonStorage::new(&json).load(),
+        Err(StorageError::UnsupportedVersion(99))
+    ));
+
+    let csv = scratch("future.csv");
+    let next = SCHEMA_VERSION + 1;
+    fs::write(&csv, format!("inventory,{}\n", next)).unwrap();
+    assert!(matches!(
+        CsvStorage::new(&csv).load(),
+        Err(StorageError::UnsupportedVersion(version)) if version == next
+    ));
+
+    let csv = scratch("broken.csv");
+    fs::write(&csv, "inventory,1\nitem,1,Phone,0,five,1000\n").unwrap();
+    match CsvStorage::new(&csv).load() {
+        Err(StorageError::Format(message)) => {
+            assert!(message.starts_with("line 2:"), "{}", message)
+        }
+        other => panic!("expected a format error, got {:?}", other),
+    }
+}
+
+#[test]
+fn stored_data_must_follow_the_inventory_rules() {
+    let csv = scratch("duplicate.csv");
+    fs::write(
+        &csv,
+        "inventory,1\ncategory,0,Electronics,\nitem,1,Phone,0,5,1000\nitem,1,Laptop,0,2,1500\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        CsvStorage::new(&csv).load(),
+        Err(StorageError::Inventory(InventoryError::DuplicateItemId))
+    ));
+}
+
+#[test]
+fn version_1_prices_load_as_amounts_in_no_currency() {
+    let none = |minor| Money::new(minor, Currency::NONE);
+
+    let json = scratch("v1.json");
+    fs::write(
+        &json,
+        r#"{"schema_version": 1,
+            "categories": [{"id": 0, "name": "Electronics", "parent": null}],
+            "items": [{"id": 1, "name": "Phone", "category": 0, "quantity": 5, "price": 1000}],
+            "movements": []}"#,
+    )
+    .unwrap();
+    let inv = JsonStorage::new(&json).load().unwrap();
+    let item = inv.items()[0].clone();
+    assert_eq!((item.cost, item.price), (none(0), none(1000)));
+
+    // The audit log starts from the items that were there
+    let log = inv.audit_log();
+    assert_eq!((log.len(), log[0].operation), (1, Operation::Baseline));
+    assert_eq!(inv.as_of(0).items(), inv.items());
+
+    let csv = scratch("v1.csv");
+    fs::write(
+        &csv,
+        "inventory,1\ncategory,0,Electronics,\nitem,1,Phone,0,5,1000\n",
+    )
+    .unwrap();
+    let inv = CsvStorage::new(&csv).load().unwrap();
+    assert_eq!(inv.items()[0].price, none(1000));
+
+    // An old database is read as it is, then rebuilt on the next save
+    let db = scratch("v1.db");
+    let old = rusqlite::Connection::open(&db).unwrap();
+    old.execute_batch(
+        "CREATE TABLE categories (id INTEGER PRIMARY KEY, name TEXT NOT NULL, parent INTEGER);
+         CREATE TABLE items (position INTEGER PRIMARY KEY, id INTEGER NOT NULL UNIQUE,
+             name TEXT NOT NULL, category INTEGER NOT NULL, quantity INTEGER NOT NULL,
+             price INTEGER NOT NULL);
+         CREATE TABLE movements (position INTEGER PRIMARY KEY, item_id INTEGER NOT NULL,
+             kind TEXT NOT NULL, change INTEGER NOT NULL, balance INTEGER NOT NULL);
+         INSERT INTO categories VALUES (0, 'Electronics', NULL);
+         INSERT INTO items VALUES (1, 1, 'Phone', 0, 5, 1000);
+         PRAGMA user_version = 1;",
+    )
+    .unwrap();
+    drop(old);
+    let storage = SqliteStorage::new(&db);
+    let mut inv = storage.load().unwrap();
+    assert_eq!(inv.items()[0].price, none(1000));
+    let update = ItemUpdate {
+        price: Some(money("10.00 USD")),
+        ..ItemUpdate::default()
+    };
+    inv.update_item(1, update).unwrap();
+    storage.save(&inv).unwrap();
+    assert_eq!(storage.load().unwrap(), inv);
+}